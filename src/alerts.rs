@@ -0,0 +1,317 @@
+use log::{info, warn};
+use serde_json::json;
+use std::time::{Duration, Instant};
+
+use crate::collector::Collector;
+use crate::config::{AlertAggregate, AlertOptions, AlertRule, AlertWebhookKind};
+use crate::prometheus::JsonFamily;
+
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Default)]
+struct AlertState {
+    // First evaluation of the current breach.
+    breached_since: Option<Instant>,
+    firing: bool,
+}
+
+impl AlertState {
+    // Some(true) when the alert starts firing, once the rule has been
+    // breached for `pending`, and Some(false) when a firing alert resolves.
+    fn update(&mut self, breached: bool, pending: Duration, now: Instant) -> Option<bool> {
+        if !breached {
+            self.breached_since = None;
+            return std::mem::take(&mut self.firing).then_some(false);
+        }
+        let since = *self.breached_since.get_or_insert(now);
+        if !self.firing && now.duration_since(since) >= pending {
+            self.firing = true;
+            return Some(true);
+        }
+        None
+    }
+}
+
+/// Evaluates the alert rules on the collected metrics and calls the webhook
+/// of a rule when its alert fires and when it resolves, for setups without
+/// Alertmanager.
+pub struct Alerter {
+    interval: Duration,
+    rules: Vec<(String, AlertRule, AlertState)>,
+    client: reqwest::Client,
+    collector: Collector,
+}
+
+// Aggregate of the series of the rule metric with the rule labels, no
+// series sums to 0 and has no minimum or maximum.
+fn evaluate(rule: &AlertRule, families: &[JsonFamily]) -> Option<f64> {
+    let values = families
+        .iter()
+        .flat_map(|family| &family.samples)
+        .filter(|sample| {
+            sample.name == rule.metric
+                && rule
+                    .labels
+                    .iter()
+                    .all(|(label, value)| sample.labels.get(label) == Some(value))
+        })
+        .map(|sample| sample.value);
+    match rule.aggregate {
+        AlertAggregate::Sum => Some(values.fold(0.0, |sum, value| sum + value)),
+        AlertAggregate::Min => values.reduce(f64::min),
+        AlertAggregate::Max => values.reduce(f64::max),
+    }
+}
+
+fn breached(rule: &AlertRule, value: Option<f64>) -> bool {
+    let Some(value) = value else {
+        return false;
+    };
+    rule.above.is_some_and(|above| value > above) || rule.below.is_some_and(|below| value < below)
+}
+
+fn message(name: &str, rule: &AlertRule, value: Option<f64>, firing: bool) -> String {
+    let value = value.map_or("absent".to_string(), |value| value.to_string());
+    match (firing, rule.above, rule.below) {
+        (true, Some(above), _) => {
+            format!(
+                "homers alert {name} firing: {} is {value}, above {above}",
+                rule.metric
+            )
+        }
+        (true, _, Some(below)) => {
+            format!(
+                "homers alert {name} firing: {} is {value}, below {below}",
+                rule.metric
+            )
+        }
+        _ => format!("homers alert {name} resolved: {} is {value}", rule.metric),
+    }
+}
+
+impl Alerter {
+    pub fn new(options: &AlertOptions, collector: Collector) -> anyhow::Result<Self> {
+        let mut rules = Vec::new();
+        for (name, rule) in &options.rules {
+            if rule.above.is_none() && rule.below.is_none() {
+                anyhow::bail!("Alert rule {name} needs `above` or `below`");
+            }
+            rules.push((name.clone(), rule.clone(), AlertState::default()));
+        }
+        Ok(Alerter {
+            interval: options.interval(),
+            rules,
+            client: reqwest::Client::builder()
+                .timeout(WEBHOOK_TIMEOUT)
+                .build()?,
+            collector,
+        })
+    }
+
+    /// Evaluates at startup, then every `interval`.
+    pub fn spawn(mut self) {
+        info!(
+            "Evaluating {} alert rules every {:?}",
+            self.rules.len(),
+            self.interval
+        );
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.interval);
+            loop {
+                interval.tick().await;
+                match self
+                    .collector
+                    .latest()
+                    .await
+                    .and_then(|latest| latest.families())
+                {
+                    Ok(families) => self.evaluate(&families).await,
+                    Err(e) => warn!("Failed to collect metrics for the alert rules: {e}"),
+                }
+            }
+        });
+    }
+
+    async fn evaluate(&mut self, families: &[JsonFamily]) {
+        let now = Instant::now();
+        let mut notifications = Vec::new();
+        for (name, rule, state) in &mut self.rules {
+            let value = evaluate(rule, families);
+            let pending = Duration::from_secs(rule.for_seconds.unwrap_or(0));
+            if let Some(firing) = state.update(breached(rule, value), pending, now) {
+                notifications.push((name.clone(), rule.clone(), value, firing));
+            }
+        }
+        for (name, rule, value, firing) in notifications {
+            let message = message(&name, &rule, value, firing);
+            info!("{message}");
+            if let Err(e) = self.notify(&name, &rule, value, firing, &message).await {
+                warn!("Failed to call the webhook of alert {name}: {e}");
+            }
+        }
+    }
+
+    async fn notify(
+        &self,
+        name: &str,
+        rule: &AlertRule,
+        value: Option<f64>,
+        firing: bool,
+        message: &str,
+    ) -> anyhow::Result<()> {
+        let body = match rule.kind {
+            AlertWebhookKind::Discord => json!({ "content": message }),
+            AlertWebhookKind::Slack => json!({ "text": message }),
+            AlertWebhookKind::Generic => json!({
+                "alert": name,
+                "status": if firing { "firing" } else { "resolved" },
+                "metric": rule.metric,
+                "labels": rule.labels,
+                "value": value,
+                "above": rule.above,
+                "below": rule.below,
+                "message": message,
+            }),
+        };
+        self.client
+            .post(&rule.webhook)
+            .json(&body)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AlertWebhookKind;
+    use crate::prometheus::JsonSample;
+    use std::collections::{BTreeMap, HashMap};
+
+    fn rule(aggregate: AlertAggregate, above: Option<f64>, below: Option<f64>) -> AlertRule {
+        AlertRule {
+            metric: "homers_plex_sessions".to_string(),
+            labels: HashMap::from([("name".to_string(), "main".to_string())]),
+            aggregate,
+            above,
+            below,
+            for_seconds: None,
+            webhook: "http://localhost/hook".to_string(),
+            kind: AlertWebhookKind::Generic,
+        }
+    }
+
+    fn sample(name: &str, instance: &str, value: f64) -> JsonSample {
+        JsonSample {
+            name: name.to_string(),
+            labels: BTreeMap::from([("name".to_string(), instance.to_string())]),
+            value,
+        }
+    }
+
+    fn families() -> Vec<JsonFamily> {
+        vec![JsonFamily {
+            name: "homers_plex_sessions".to_string(),
+            metric_type: "gauge".to_string(),
+            help: String::new(),
+            samples: vec![
+                sample("homers_plex_sessions", "main", 2.0),
+                sample("homers_plex_sessions", "main", 5.0),
+                sample("homers_plex_sessions", "other", 100.0),
+                sample("homers_plex_sessions_total", "main", 1000.0),
+            ],
+        }]
+    }
+
+    #[test]
+    fn evaluate_aggregates_matching_series() {
+        let families = families();
+        assert_eq!(
+            evaluate(&rule(AlertAggregate::Sum, None, None), &families),
+            Some(7.0)
+        );
+        assert_eq!(
+            evaluate(&rule(AlertAggregate::Min, None, None), &families),
+            Some(2.0)
+        );
+        assert_eq!(
+            evaluate(&rule(AlertAggregate::Max, None, None), &families),
+            Some(5.0)
+        );
+    }
+
+    #[test]
+    fn evaluate_without_series() {
+        assert_eq!(
+            evaluate(&rule(AlertAggregate::Sum, None, None), &[]),
+            Some(0.0)
+        );
+        assert_eq!(evaluate(&rule(AlertAggregate::Min, None, None), &[]), None);
+        assert_eq!(evaluate(&rule(AlertAggregate::Max, None, None), &[]), None);
+    }
+
+    #[test]
+    fn breached_thresholds_are_strict() {
+        let above = rule(AlertAggregate::Sum, Some(5.0), None);
+        assert!(breached(&above, Some(5.5)));
+        assert!(!breached(&above, Some(5.0)));
+        assert!(!breached(&above, None));
+        let below = rule(AlertAggregate::Sum, None, Some(1.0));
+        assert!(breached(&below, Some(0.0)));
+        assert!(!breached(&below, Some(1.0)));
+        assert!(!breached(&below, None));
+    }
+
+    #[test]
+    fn alert_fires_and_resolves_once() {
+        let mut state = AlertState::default();
+        let now = Instant::now();
+        assert_eq!(state.update(true, Duration::ZERO, now), Some(true));
+        assert_eq!(state.update(true, Duration::ZERO, now), None);
+        assert_eq!(state.update(false, Duration::ZERO, now), Some(false));
+        assert_eq!(state.update(false, Duration::ZERO, now), None);
+    }
+
+    #[test]
+    fn alert_fires_after_for() {
+        let mut state = AlertState::default();
+        let pending = Duration::from_secs(60);
+        let now = Instant::now();
+        assert_eq!(state.update(true, pending, now), None);
+        assert_eq!(
+            state.update(true, pending, now + Duration::from_secs(30)),
+            None
+        );
+        assert_eq!(
+            state.update(true, pending, now + Duration::from_secs(60)),
+            Some(true)
+        );
+    }
+
+    #[test]
+    fn alert_pending_resets_when_cleared() {
+        let mut state = AlertState::default();
+        let pending = Duration::from_secs(60);
+        let now = Instant::now();
+        assert_eq!(state.update(true, pending, now), None);
+        // Resolving an alert that never fired sends nothing.
+        assert_eq!(
+            state.update(false, pending, now + Duration::from_secs(30)),
+            None
+        );
+        assert_eq!(
+            state.update(true, pending, now + Duration::from_secs(40)),
+            None
+        );
+        assert_eq!(
+            state.update(true, pending, now + Duration::from_secs(90)),
+            None
+        );
+        assert_eq!(
+            state.update(true, pending, now + Duration::from_secs(100)),
+            Some(true)
+        );
+    }
+}
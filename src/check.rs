@@ -0,0 +1,344 @@
+use futures::future::join_all;
+use log::Level;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::config::{self, Config};
+use crate::tasks::Task;
+
+const LIVE_CHECK_TIMEOUT_SECONDS: u64 = 10;
+
+enum Severity {
+    Ok,
+    Warning,
+    Error,
+}
+
+struct Report {
+    lines: Vec<(Severity, String)>,
+}
+impl Report {
+    fn push(&mut self, severity: Severity, message: String) {
+        self.lines.push((severity, message));
+    }
+    fn count(&self, wanted: fn(&Severity) -> bool) -> usize {
+        self.lines
+            .iter()
+            .filter(|(severity, _)| wanted(severity))
+            .count()
+    }
+    fn print(&self) {
+        for (severity, message) in &self.lines {
+            let label = match severity {
+                Severity::Ok => "ok",
+                Severity::Warning => "warning",
+                Severity::Error => "error",
+            };
+            println!("{:>8}  {}", label, message);
+        }
+    }
+}
+
+struct Instance<'a> {
+    provider: &'static str,
+    name: &'a str,
+    address: &'a str,
+    key_name: &'static str,
+    key: &'a str,
+}
+
+fn instances(config: &Config) -> Vec<Instance<'_>> {
+    let mut instances = Vec::new();
+    for (name, sonarr) in config.sonarr.iter().flatten() {
+        instances.push(Instance {
+            provider: "sonarr",
+            name,
+            address: &sonarr.address,
+            key_name: "apikey",
+            key: &sonarr.api_key,
+        });
+    }
+    for (name, radarr) in config.radarr.iter().flatten() {
+        instances.push(Instance {
+            provider: "radarr",
+            name,
+            address: &radarr.address,
+            key_name: "apikey",
+            key: &radarr.api_key,
+        });
+    }
+    if let Some(tautulli) = &config.tautulli {
+        instances.push(Instance {
+            provider: "tautulli",
+            name: &tautulli.name,
+            address: &tautulli.address,
+            key_name: "apikey",
+            key: &tautulli.api_key,
+        });
+    }
+    for (name, overseerr) in config.overseerr.iter().flatten() {
+        instances.push(Instance {
+            provider: "overseerr",
+            name,
+            address: &overseerr.address,
+            key_name: "apikey",
+            key: &overseerr.api_key,
+        });
+    }
+    for (name, jellyseerr) in config.jellyseerr.iter().flatten() {
+        instances.push(Instance {
+            provider: "jellyseerr",
+            name,
+            address: &jellyseerr.address,
+            key_name: "apikey",
+            key: &jellyseerr.api_key,
+        });
+    }
+    for (name, plex) in config.plex.iter().flatten() {
+        instances.push(Instance {
+            provider: "plex",
+            name,
+            address: &plex.address,
+            key_name: "token",
+            key: &plex.token,
+        });
+    }
+    for (name, jellyfin) in config.jellyfin.iter().flatten() {
+        instances.push(Instance {
+            provider: "jellyfin",
+            name,
+            address: &jellyfin.address,
+            key_name: "apikey",
+            key: &jellyfin.api_key,
+        });
+    }
+    instances
+}
+
+fn check_instance(instance: &Instance, report: &mut Report) {
+    let label = format!("{} {}", instance.provider, instance.name);
+    let mut valid = true;
+    match reqwest::Url::parse(instance.address) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") && url.has_host() => {}
+        Ok(_) => {
+            valid = false;
+            report.push(
+                Severity::Error,
+                format!(
+                    "{label}: address {:?} must be an http:// or https:// URL",
+                    instance.address
+                ),
+            );
+        }
+        Err(e) => {
+            valid = false;
+            report.push(
+                Severity::Error,
+                format!("{label}: address {:?} is invalid: {e}", instance.address),
+            );
+        }
+    }
+    if instance.key.is_empty() {
+        valid = false;
+        report.push(
+            Severity::Error,
+            format!("{label}: {} is empty", instance.key_name),
+        );
+    } else if instance.key.chars().any(|c| c.is_control() || c == ' ') {
+        valid = false;
+        report.push(
+            Severity::Error,
+            format!(
+                "{label}: {} contains spaces or control characters",
+                instance.key_name
+            ),
+        );
+    } else if matches!(
+        instance.provider,
+        "sonarr" | "radarr" | "tautulli" | "jellyfin"
+    ) && !(instance.key.len() == 32
+        && instance.key.chars().all(|c| c.is_ascii_hexdigit()))
+    {
+        // Not fatal, only the live check can tell for sure.
+        report.push(
+            Severity::Warning,
+            format!(
+                "{label}: {} does not look like a {} API key (32 hexadecimal characters)",
+                instance.key_name, instance.provider
+            ),
+        );
+    }
+    if valid {
+        report.push(Severity::Ok, format!("{label}: {}", instance.address));
+    }
+}
+
+// Building the clients catches proxy, CA file and header errors, `live` then
+// sends one authenticated request per provider instance.
+async fn check_clients(config: Config, live: bool, report: &mut Report) {
+    let tasks = match config::get_tasks(config) {
+        Ok(tasks) => tasks,
+        Err(e) => {
+            report.push(
+                Severity::Error,
+                format!("failed to create provider clients: {e}"),
+            );
+            return;
+        }
+    };
+    if !live {
+        return;
+    }
+    let mut seen = HashSet::new();
+    let tasks: Vec<_> = tasks
+        .into_iter()
+        .filter(|task| {
+            let (provider, name, _) = task.provider();
+            seen.insert((provider, name.to_string()))
+        })
+        .collect();
+    let results = join_all(tasks.iter().map(|task| {
+        tokio::time::timeout(
+            Duration::from_secs(LIVE_CHECK_TIMEOUT_SECONDS),
+            task.check(),
+        )
+    }))
+    .await;
+    for (task, result) in tasks.iter().zip(results) {
+        let (provider, name, _) = task.provider();
+        let label = format!("{provider} {name}");
+        match result {
+            Ok(Ok(())) => report.push(Severity::Ok, format!("{label}: reachable")),
+            Ok(Err(e)) => report.push(Severity::Error, format!("{label}: {e}")),
+            Err(_) => report.push(
+                Severity::Error,
+                format!("{label}: no answer after {LIVE_CHECK_TIMEOUT_SECONDS}s"),
+            ),
+        }
+    }
+}
+
+/// Validates the configuration and prints a report, returns false when an error was found.
+pub async fn run(config_file: PathBuf, log_level: Level, strict: bool, live: bool) -> bool {
+    let mut report = Report { lines: Vec::new() };
+    println!("Checking {}", config_file.display());
+    // A missing file is not an error for figment, the defaults and environment still apply.
+    if !config_file.is_file() {
+        report.push(
+            Severity::Warning,
+            format!("{} does not exist", config_file.display()),
+        );
+    }
+    match config::read(config_file, log_level, strict) {
+        Ok(config) => {
+            report.push(Severity::Ok, "configuration parsed".to_string());
+            for key in &config.unknown_keys {
+                report.push(Severity::Warning, format!("unknown key {key}"));
+            }
+            let instances = instances(&config);
+            if instances.is_empty() {
+                report.push(Severity::Warning, "no provider is configured".to_string());
+            }
+            for instance in &instances {
+                check_instance(instance, &mut report);
+            }
+            if report.count(|severity| matches!(severity, Severity::Error)) == 0 {
+                check_clients(config, live, &mut report).await;
+            }
+        }
+        Err(e) => report.push(
+            Severity::Error,
+            format!("failed to read the configuration: {e}"),
+        ),
+    }
+    report.print();
+    let errors = report.count(|severity| matches!(severity, Severity::Error));
+    let warnings = report.count(|severity| matches!(severity, Severity::Warning));
+    println!("{errors} error(s), {warnings} warning(s)");
+    errors == 0
+}
+
+// Credentials given in the URL itself are masked, API keys are never part of it.
+fn mask_address(address: &str) -> String {
+    match reqwest::Url::parse(address) {
+        Ok(mut url) if url.password().is_some() => {
+            let _ = url.set_password(Some("********"));
+            url.to_string()
+        }
+        _ => address.to_string(),
+    }
+}
+
+/// Prints the tasks `get_tasks` builds from the configuration.
+pub fn list_tasks(config: Config) -> anyhow::Result<()> {
+    let timeout = match config.http_options.timeout() {
+        Some(timeout) => format!("{}s", timeout.as_secs()),
+        None => "none".to_string(),
+    };
+    let tasks = config::get_tasks(config)?;
+    println!("Tasks run on every scrape of /metrics, timeout {timeout}");
+    let rows: Vec<[String; 4]> = tasks
+        .iter()
+        .map(|task| {
+            let (provider, name, address) = task.provider();
+            [
+                task.kind().to_string(),
+                provider.to_string(),
+                name.to_string(),
+                mask_address(address),
+            ]
+        })
+        .collect();
+    let header = ["TASK", "PROVIDER", "INSTANCE", "ADDRESS"].map(String::from);
+    let widths: Vec<usize> = (0..3)
+        .map(|column| {
+            rows.iter()
+                .chain(std::iter::once(&header))
+                .map(|row| row[column].len())
+                .max()
+                .unwrap_or_default()
+        })
+        .collect();
+    for row in std::iter::once(&header).chain(rows.iter()) {
+        println!(
+            "{:<w0$}  {:<w1$}  {:<w2$}  {}",
+            row[0],
+            row[1],
+            row[2],
+            row[3],
+            w0 = widths[0],
+            w1 = widths[1],
+            w2 = widths[2]
+        );
+    }
+    Ok(())
+}
+
+/// Fetches the raw payload of a provider endpoint, for bug reports about parse errors.
+pub async fn debug(
+    config: Config,
+    provider: &str,
+    instance: &str,
+    endpoint: Option<&str>,
+) -> anyhow::Result<String> {
+    let tasks = config::get_tasks(config)?;
+    let task = tasks
+        .iter()
+        .find(|task| matches!(task.provider(), (p, name, _) if p == provider && name == instance));
+    match task {
+        Some(task) => Ok(task.debug(endpoint).await?),
+        None => {
+            let mut instances: Vec<String> = tasks
+                .iter()
+                .map(Task::provider)
+                .map(|(provider, name, _)| format!("{provider} {name}"))
+                .collect();
+            instances.dedup();
+            anyhow::bail!(
+                "No {provider} instance named {instance}, configured instances: {}",
+                instances.join(", ")
+            )
+        }
+    }
+}
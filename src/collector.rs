@@ -0,0 +1,106 @@
+use log::{info, warn};
+use rocket::tokio::sync::{watch, Mutex};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::history::HistoryStore;
+use crate::http_server::{process_tasks, CollectionStatus};
+use crate::prometheus::{Exposition, MetricRegistry};
+use crate::result_cache::ResultCache;
+use crate::tasks::{TaskList, TaskResult};
+
+/// Runs every task like an unfiltered scrape of `/metrics`, once per
+/// `interval` for all the exporters that push instead of being scraped and
+/// the alert rules.
+pub struct CollectionLoop {
+    pub tasks: TaskList,
+    pub registry: Arc<Mutex<MetricRegistry>>,
+    pub timeout: Option<Duration>,
+    pub status: CollectionStatus,
+    pub cache: Option<Arc<ResultCache>>,
+    pub history: Option<Arc<HistoryStore>>,
+}
+
+/// Latest collection, applied once to the registry it shares with `/metrics`
+/// and encoded for all the exporters.
+#[derive(Clone)]
+pub struct Collector {
+    latest: watch::Receiver<Option<Arc<Exposition>>>,
+}
+
+impl CollectionLoop {
+    /// Collects at startup, then every `interval`.
+    pub fn spawn(self, interval: Duration) -> Collector {
+        let (sender, latest) = watch::channel(None);
+        let collector = Collector { latest };
+        info!("Collecting metrics for the exporters every {interval:?}");
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(interval);
+            loop {
+                interval.tick().await;
+                match self.collect().await {
+                    Ok(exposition) => {
+                        sender.send_replace(Some(Arc::new(exposition)));
+                    }
+                    Err(e) => warn!("Failed to collect metrics for the exporters: {e}"),
+                }
+            }
+        });
+        collector
+    }
+
+    async fn collect(&self) -> anyhow::Result<Exposition> {
+        let mut task_results = process_tasks(
+            self.tasks.get(),
+            self.timeout,
+            Some(&self.status),
+            None,
+            self.cache.as_deref(),
+        )
+        .await?;
+        if let Some(history) = &self.history {
+            let derived = history.record(&task_results).await;
+            task_results.push(TaskResult::History(derived));
+        }
+        self.registry.lock().await.exposition(task_results)
+    }
+}
+
+impl Collector {
+    /// Latest collection, waiting for the first one at startup.
+    pub async fn latest(&self) -> anyhow::Result<Arc<Exposition>> {
+        let mut latest = self.latest.clone();
+        let exposition = latest.wait_for(Option::is_some).await?;
+        exposition
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("No collection yet"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prometheus::WebhookMetrics;
+
+    #[rocket::async_test]
+    async fn exporters_share_one_collection() {
+        let registry = Arc::new(Mutex::new(MetricRegistry::new(
+            &WebhookMetrics::default(),
+            None,
+        )));
+        let collector = CollectionLoop {
+            tasks: TaskList::new(Vec::new()),
+            registry,
+            timeout: None,
+            status: CollectionStatus::default(),
+            cache: None,
+            history: None,
+        }
+        .spawn(Duration::from_secs(3600));
+        let first = collector.latest().await.unwrap();
+        let second = collector.clone().latest().await.unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        assert!(first.prometheus().contains("# TYPE homers_"));
+        assert!(!first.families().unwrap().is_empty());
+    }
+}
@@ -1,20 +1,28 @@
+use anyhow::Context;
 use figment::{
     providers::{Env, Format, Toml},
+    value::Value,
     Figment,
 };
-use log::{debug, info, Level};
+use ipnet::IpNet;
+use log::{debug, info, warn, Level};
 use rocket::figment::providers::Serialized;
 use rocket::serde::Serialize;
+use serde::de::DeserializeOwned;
 use serde::Deserialize;
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
+use crate::providers::geoip::{GeoipService, Geolocator};
 use crate::providers::jellyfin::Jellyfin;
 use crate::providers::overseerr::Overseerr;
 use crate::providers::plex::Plex;
 use crate::providers::radarr::Radarr;
 use crate::providers::sonarr::Sonarr;
 use crate::providers::tautulli::Tautulli;
+use crate::providers::ClientOptions;
 
 use crate::tasks::Task;
 
@@ -23,14 +31,403 @@ pub struct Config {
     pub tautulli: Option<Tautulli>,
     pub sonarr: Option<HashMap<String, Sonarr>>,
     pub radarr: Option<HashMap<String, Radarr>>,
-    pub overseerr: Option<Overseerr>,
-    pub jellyseerr: Option<Overseerr>,
+    pub overseerr: Option<HashMap<String, Overseerr>>,
+    pub jellyseerr: Option<HashMap<String, Overseerr>>,
     pub plex: Option<HashMap<String, Plex>>,
     pub jellyfin: Option<HashMap<String, Jellyfin>>,
+    pub webhooks: Option<Webhooks>,
+    pub probe: Option<HashMap<String, ProbeCredentials>>,
+    pub tls: Option<TlsOptions>,
+    pub geoip: Option<GeoipOptions>,
+    pub log: Option<LogOptions>,
+    pub tracing: Option<TracingOptions>,
+    pub sentry: Option<SentryOptions>,
+    pub cache: Option<CacheOptions>,
+    pub history: Option<HistoryOptions>,
+    pub push: Option<PushOptions>,
+    pub graphite: Option<GraphiteOptions>,
+    pub otlp: Option<OtlpOptions>,
+    pub alerts: Option<AlertOptions>,
     pub http: rocket::Config,
+    #[serde(skip)]
+    pub http_options: HttpOptions,
+    #[serde(skip)]
+    pub unknown_keys: Vec<String>,
 }
+
+#[derive(Debug, Deserialize, Clone, Serialize, Default)]
+pub struct HttpOptions {
+    pub basic_auth: Option<BasicAuth>,
+    pub bearer_token: Option<String>,
+    pub metrics_timeout: Option<u64>,
+    #[serde(default)]
+    pub cors_allowed_origins: Vec<String>,
+    pub rate_limit: Option<RateLimit>,
+    pub sessions_stream_interval: Option<u64>,
+    #[serde(default)]
+    pub access_log: bool,
+    // Upper bounds in seconds of the provider request latency histogram.
+    pub provider_latency_buckets: Option<Vec<f64>>,
+    #[serde(skip)]
+    pub listeners: Vec<SocketAddr>,
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize)]
+pub struct RateLimit {
+    pub requests: u32,
+    pub period: u64,
+}
+
+const DEFAULT_SESSIONS_STREAM_INTERVAL_SECONDS: u64 = 10;
+
+impl HttpOptions {
+    pub fn timeout(&self) -> Option<Duration> {
+        self.metrics_timeout.map(Duration::from_secs)
+    }
+    pub fn sessions_stream_interval(&self) -> Duration {
+        Duration::from_secs(
+            self.sessions_stream_interval
+                .unwrap_or(DEFAULT_SESSIONS_STREAM_INTERVAL_SECONDS)
+                .max(1),
+        )
+    }
+    // A bucket without requests or period would never let a scrape through.
+    pub fn rate_limit(&self) -> anyhow::Result<Option<RateLimit>> {
+        match &self.rate_limit {
+            Some(limit) if limit.requests == 0 || limit.period == 0 => anyhow::bail!(
+                "Rate limit requests and period must be at least 1, got {} per {} seconds",
+                limit.requests,
+                limit.period
+            ),
+            limit => Ok(limit.clone()),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize)]
+pub struct BasicAuth {
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize)]
+pub struct ProbeCredentials {
+    #[serde(rename = "apikey")]
+    pub api_key: String,
+    // URL prefixes the key may be sent to, e.g. `http://sonarr:8989`.
+    #[serde(default)]
+    pub targets: Vec<String>,
+}
+
+impl ProbeCredentials {
+    /// Whether `target` has the scheme, host and port of an allowed prefix
+    /// and a path below it.
+    pub fn allows(&self, target: &str) -> bool {
+        let Ok(target) = reqwest::Url::parse(target) else {
+            return false;
+        };
+        self.targets.iter().any(|allowed| {
+            let Ok(allowed) = reqwest::Url::parse(allowed) else {
+                return false;
+            };
+            let prefix = allowed.path().trim_end_matches('/');
+            allowed.scheme() == target.scheme()
+                && allowed.host() == target.host()
+                && allowed.port_or_known_default() == target.port_or_known_default()
+                && target
+                    .path()
+                    .strip_prefix(prefix)
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        })
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize)]
+pub struct TlsOptions {
+    pub cert_file: PathBuf,
+    pub key_file: PathBuf,
+    pub client_ca_file: Option<PathBuf>,
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize)]
+pub struct GeoipOptions {
+    #[serde(default = "default_geoip_enabled")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub service: GeoipService,
+    #[serde(rename = "apikey")]
+    pub api_key: Option<String>,
+    // Resolved as LAN on top of the private, loopback and CGNAT ranges.
+    #[serde(default)]
+    pub lan_networks: Vec<IpNet>,
+    pub lan_label: Option<String>,
+    // Seconds a resolved address is kept before being looked up again.
+    pub cache_ttl: Option<u64>,
+    #[serde(default)]
+    pub reverse_dns: bool,
+}
+
+impl Default for GeoipOptions {
+    fn default() -> Self {
+        GeoipOptions {
+            enabled: true,
+            service: GeoipService::default(),
+            api_key: None,
+            lan_networks: Vec::new(),
+            lan_label: None,
+            cache_ttl: None,
+            reverse_dns: false,
+        }
+    }
+}
+
+impl GeoipOptions {
+    pub fn lan_label(&self) -> String {
+        self.lan_label.clone().unwrap_or_else(|| "LAN".to_string())
+    }
+    pub fn cache_ttl(&self) -> Duration {
+        Duration::from_secs(self.cache_ttl.unwrap_or(DEFAULT_GEOIP_CACHE_TTL_SECONDS))
+    }
+}
+
+const DEFAULT_GEOIP_CACHE_TTL_SECONDS: u64 = 86400;
+
+fn default_geoip_enabled() -> bool {
+    true
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize, Default)]
+pub struct LogOptions {
+    // Module path to level, e.g. "homers::providers::plex" = "debug".
+    #[serde(default)]
+    pub levels: HashMap<String, String>,
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize)]
+pub struct TracingOptions {
+    pub otlp_endpoint: String,
+    pub service_name: Option<String>,
+    pub sample_ratio: Option<f64>,
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize)]
+pub struct SentryOptions {
+    pub dsn: String,
+    pub environment: Option<String>,
+    pub sample_rate: Option<f32>,
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize)]
+pub struct CacheOptions {
+    pub path: PathBuf,
+    // Seconds after which a cached result is not served anymore.
+    pub max_age: Option<u64>,
+}
+
+const DEFAULT_CACHE_MAX_AGE_SECONDS: u64 = 86400;
+
+impl CacheOptions {
+    pub fn max_age(&self) -> Duration {
+        Duration::from_secs(self.max_age.unwrap_or(DEFAULT_CACHE_MAX_AGE_SECONDS))
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize)]
+pub struct HistoryOptions {
+    pub path: PathBuf,
+    // Seconds snapshots are kept, 2 days at least so the snapshot of the day
+    // before is still there.
+    pub retention: Option<u64>,
+}
+
+const MIN_HISTORY_RETENTION_SECONDS: u64 = 2 * 86400;
+
+impl HistoryOptions {
+    pub fn retention(&self) -> anyhow::Result<Duration> {
+        let retention = self.retention.unwrap_or(MIN_HISTORY_RETENTION_SECONDS);
+        if retention < MIN_HISTORY_RETENTION_SECONDS {
+            anyhow::bail!(
+                "History retention must be at least {MIN_HISTORY_RETENTION_SECONDS} seconds, got {retention}"
+            );
+        }
+        Ok(Duration::from_secs(retention))
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize)]
+pub struct PushOptions {
+    // Pushgateway address, e.g. http://pushgateway:9091.
+    pub url: String,
+    pub job: Option<String>,
+    pub instance: Option<String>,
+    // Grouping labels on top of job and instance.
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+    // Seconds between two pushes.
+    pub interval: Option<u64>,
+    pub basic_auth: Option<BasicAuth>,
+}
+
+const DEFAULT_PUSH_INTERVAL_SECONDS: u64 = 60;
+
+impl PushOptions {
+    pub fn job(&self) -> &str {
+        self.job.as_deref().unwrap_or("homers")
+    }
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(
+            self.interval
+                .unwrap_or(DEFAULT_PUSH_INTERVAL_SECONDS)
+                .max(1),
+        )
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize)]
+pub struct GraphiteOptions {
+    pub host: String,
+    // Plaintext protocol port of carbon.
+    pub port: Option<u16>,
+    // First node of every metric path.
+    pub prefix: Option<String>,
+    // Seconds between two pushes.
+    pub interval: Option<u64>,
+}
+
+const DEFAULT_GRAPHITE_PORT: u16 = 2003;
+
+impl GraphiteOptions {
+    pub fn port(&self) -> u16 {
+        self.port.unwrap_or(DEFAULT_GRAPHITE_PORT)
+    }
+    pub fn prefix(&self) -> &str {
+        self.prefix.as_deref().unwrap_or("homers")
+    }
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(
+            self.interval
+                .unwrap_or(DEFAULT_PUSH_INTERVAL_SECONDS)
+                .max(1),
+        )
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize)]
+pub struct OtlpOptions {
+    // OTLP/gRPC endpoint, e.g. http://otel-collector:4317.
+    pub endpoint: String,
+    pub service_name: Option<String>,
+    // Seconds between two exports.
+    pub interval: Option<u64>,
+}
+
+impl OtlpOptions {
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(
+            self.interval
+                .unwrap_or(DEFAULT_PUSH_INTERVAL_SECONDS)
+                .max(1),
+        )
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize)]
+pub struct AlertOptions {
+    // Seconds between two evaluations of the rules.
+    pub interval: Option<u64>,
+    #[serde(default)]
+    pub rules: HashMap<String, AlertRule>,
+}
+
+impl AlertOptions {
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(
+            self.interval
+                .unwrap_or(DEFAULT_PUSH_INTERVAL_SECONDS)
+                .max(1),
+        )
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Serialize, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertAggregate {
+    #[default]
+    Sum,
+    Min,
+    Max,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Serialize, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertWebhookKind {
+    Discord,
+    Slack,
+    #[default]
+    Generic,
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize)]
+pub struct AlertRule {
+    // Sample name, e.g. homers_plex_sessions.
+    pub metric: String,
+    // Only the series with these label values are evaluated.
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+    #[serde(default)]
+    pub aggregate: AlertAggregate,
+    pub above: Option<f64>,
+    pub below: Option<f64>,
+    // Seconds the condition must hold before the alert fires.
+    #[serde(rename = "for")]
+    pub for_seconds: Option<u64>,
+    pub webhook: String,
+    #[serde(default)]
+    pub kind: AlertWebhookKind,
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize, Default)]
+pub struct Webhooks {
+    #[serde(default)]
+    pub plex: bool,
+    #[serde(default)]
+    pub tautulli: bool,
+    #[serde(default)]
+    pub jellyfin: bool,
+    // Shared secret each sender passes in the `token` query parameter or the
+    // X-Webhook-Token header.
+    pub plex_token: Option<String>,
+    pub tautulli_token: Option<String>,
+    pub jellyfin_token: Option<String>,
+}
+
+impl Webhooks {
+    /// Listeners that are enabled without a token are turned off, anyone
+    /// could post to them.
+    pub fn served(mut self) -> Self {
+        for (name, enabled, token) in [
+            ("plex", &mut self.plex, &self.plex_token),
+            ("tautulli", &mut self.tautulli, &self.tautulli_token),
+            ("jellyfin", &mut self.jellyfin, &self.jellyfin_token),
+        ] {
+            if *enabled && token.is_none() {
+                warn!("Webhook listener {name} needs {name}_token, /webhooks/{name} is not served");
+                *enabled = false;
+            }
+        }
+        self
+    }
+}
+// Scrapes fan out to every provider and can take several seconds, so give
+// in-flight requests longer than Rocket's default to finish on shutdown.
+const SHUTDOWN_GRACE_SECONDS: u32 = 10;
+const SHUTDOWN_MERCY_SECONDS: u32 = 5;
+
 impl Default for Config {
     fn default() -> Self {
+        let mut http = rocket::Config::default();
+        http.shutdown.grace = SHUTDOWN_GRACE_SECONDS;
+        http.shutdown.mercy = SHUTDOWN_MERCY_SECONDS;
         Self {
             tautulli: None,
             sonarr: None,
@@ -39,15 +436,83 @@ impl Default for Config {
             jellyseerr: None,
             plex: None,
             jellyfin: None,
-            http: rocket::Config::default(),
+            webhooks: None,
+            probe: None,
+            tls: None,
+            geoip: None,
+            log: None,
+            tracing: None,
+            sentry: None,
+            cache: None,
+            history: None,
+            push: None,
+            graphite: None,
+            otlp: None,
+            alerts: None,
+            http,
+            http_options: HttpOptions::default(),
+            unknown_keys: Vec::new(),
+        }
+    }
+}
+
+const REDACTED_KEYS: [&str; 11] = [
+    "apikey",
+    "token",
+    "plex_token",
+    "tautulli_token",
+    "jellyfin_token",
+    "password",
+    "bearer_token",
+    "secret_key",
+    "headers",
+    "dsn",
+    "webhook",
+];
+
+impl Config {
+    /// Effective configuration with credentials masked, including the extra `[http]` options.
+    pub fn redacted(&self) -> serde_json::Value {
+        let mut value = serde_json::to_value(self).unwrap_or_default();
+        if let (Some(http), Ok(serde_json::Value::Object(options))) = (
+            value.get_mut("http").and_then(|http| http.as_object_mut()),
+            serde_json::to_value(&self.http_options),
+        ) {
+            http.extend(options);
         }
+        redact(&mut value);
+        value
     }
 }
 
-pub fn read(config_file: PathBuf, log_level: Level) -> anyhow::Result<Config> {
+fn redact(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                if REDACTED_KEYS.contains(&key.as_str()) && !value.is_null() {
+                    *value = serde_json::Value::String("********".to_string());
+                } else {
+                    redact(value);
+                }
+            }
+        }
+        serde_json::Value::Array(values) => values.iter_mut().for_each(redact),
+        _ => {}
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ConfigSource {
+    pub path: PathBuf,
+    pub log_level: Level,
+    pub strict: bool,
+}
+
+/// Reads the configuration, unknown keys are logged or rejected when `strict` is set.
+pub fn read(config_file: PathBuf, log_level: Level, strict: bool) -> anyhow::Result<Config> {
     info!("Reading config file {config_file:?}");
 
-    let config: Config = Figment::new()
+    let figment = Figment::new()
         .merge(Serialized::defaults(Config::default()))
         .merge(Toml::file(config_file))
         .merge((
@@ -58,14 +523,190 @@ pub fn read(config_file: PathBuf, log_level: Level) -> anyhow::Result<Config> {
                 Level::Error => rocket::log::LogLevel::Critical,
             },
         ))
-        .merge(Env::prefixed("HOMERS_").split("_"))
-        .extract()?;
+        .merge(
+            Env::prefixed("HOMERS_")
+                .filter(|key| !is_file_env(key.as_str()))
+                .split("_"),
+        )
+        .merge(
+            Env::prefixed("HOMERS_")
+                .filter(|key| is_file_env(key.as_str()))
+                .map(|key| {
+                    let key = key.as_str();
+                    format!("{}_file", key[..key.len() - 5].replace('_', ".")).into()
+                }),
+        );
+    let figment = resolve_secret_files(figment)?;
+    let listeners = read_listeners(&figment)?;
+    let figment = match listeners.first() {
+        Some(listener) => figment
+            .merge(("http.address", listener.ip()))
+            .merge(("http.port", listener.port())),
+        None => figment,
+    };
+    let mut config: Config = figment.extract()?;
+    config.http_options = figment.extract_inner("http")?;
+    config.http_options.listeners = listeners;
+    config.unknown_keys = unknown_keys(&figment, &config)?;
+    if strict && !config.unknown_keys.is_empty() {
+        anyhow::bail!(
+            "Unknown configuration keys: {}",
+            config.unknown_keys.join(", ")
+        );
+    }
+    for key in &config.unknown_keys {
+        warn!("Unknown configuration key {key}, it is ignored");
+    }
 
     debug!("Read config is {:?}", config);
 
     Ok(config)
 }
 
+// Logging and error reporting are set up before the configuration is read, so
+// the `[tracing]` and `[sentry]` sections are read on their own first.
+// Changing them requires a restart.
+pub fn read_section<T: DeserializeOwned>(
+    config_file: &Path,
+    section: &str,
+) -> anyhow::Result<Option<T>> {
+    let figment = Figment::new()
+        .merge(Toml::file(config_file))
+        .merge(Env::prefixed("HOMERS_").split("_"));
+    if figment.find_value(section).is_err() {
+        return Ok(None);
+    }
+    Ok(Some(figment.extract_inner(section)?))
+}
+
+const SECRET_KEYS: [&str; 7] = [
+    "apikey",
+    "token",
+    "password",
+    "bearer_token",
+    "plex_token",
+    "tautulli_token",
+    "jellyfin_token",
+];
+
+// HOMERS_SONARR_MAIN_APIKEY_FILE is read as `sonarr.main.apikey_file`.
+fn is_file_env(key: &str) -> bool {
+    key.to_lowercase().ends_with("_file")
+}
+
+// `<secret>_file` keys (and `api_key_file` for `apikey`) are replaced by the
+// content of the file they point to, for Docker and Kubernetes secrets.
+fn resolve_secret_files(figment: Figment) -> anyhow::Result<Figment> {
+    let mut secrets = Vec::new();
+    collect_secret_files(&figment.extract::<Value>()?, "", &mut secrets);
+    secrets
+        .into_iter()
+        .try_fold(figment, |figment, (key, path)| {
+            debug!("Reading {key} from {path}");
+            let secret = std::fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {key} from {path}"))?;
+            Ok(figment.merge((key, secret.trim().to_string())))
+        })
+}
+
+fn collect_secret_files(value: &Value, path: &str, secrets: &mut Vec<(String, String)>) {
+    let dict = match value {
+        Value::Dict(_, dict) => dict,
+        _ => return,
+    };
+    for (key, value) in dict {
+        let prefix = if path.is_empty() {
+            String::new()
+        } else {
+            format!("{path}.")
+        };
+        let secret = match key.strip_suffix("_file") {
+            Some("api_key") => Some("apikey"),
+            Some(secret) if SECRET_KEYS.contains(&secret) => Some(secret),
+            _ => None,
+        };
+        match (secret, value.as_str()) {
+            (Some(secret), Some(file)) => {
+                secrets.push((format!("{prefix}{secret}"), file.to_string()))
+            }
+            _ => collect_secret_files(value, &format!("{prefix}{key}"), secrets),
+        }
+    }
+}
+
+// Every key read by homers shows up when the parsed configuration is serialized
+// again, so anything else in the figment is a typo or an unsupported option.
+fn unknown_keys(figment: &Figment, config: &Config) -> anyhow::Result<Vec<String>> {
+    let mut known = serde_json::to_value(config)?;
+    if let (Some(http), serde_json::Value::Object(options)) = (
+        known.get_mut("http").and_then(|http| http.as_object_mut()),
+        serde_json::to_value(&config.http_options)?,
+    ) {
+        http.extend(options);
+    }
+    let mut unknown = Vec::new();
+    collect_unknown_keys(&figment.extract::<Value>()?, &known, "", &mut unknown);
+    Ok(unknown)
+}
+
+fn collect_unknown_keys(
+    value: &Value,
+    known: &serde_json::Value,
+    path: &str,
+    unknown: &mut Vec<String>,
+) {
+    let (dict, known) = match (value, known) {
+        (Value::Dict(_, dict), serde_json::Value::Object(known)) => (dict, known),
+        _ => return,
+    };
+    for (key, value) in dict {
+        let key_path = if path.is_empty() {
+            key.to_string()
+        } else {
+            format!("{path}.{key}")
+        };
+        match known.get(key) {
+            Some(known) => collect_unknown_keys(value, known, &key_path, unknown),
+            None if is_secret_file(key) || IGNORED_KEYS.contains(&key_path.as_str()) => {}
+            None => unknown.push(key_path),
+        }
+    }
+}
+
+// Read by Rocket but not serialized back.
+const IGNORED_KEYS: [&str; 2] = ["http.secret_key", "http.profile"];
+
+fn is_secret_file(key: &str) -> bool {
+    matches!(key.strip_suffix("_file"), Some(secret) if secret == "api_key" || SECRET_KEYS.contains(&secret))
+}
+
+// `http.address` may be a list of `ip:port` (or bare ip using `http.port`)
+// to listen on several addresses, e.g. both IPv4 and IPv6.
+fn read_listeners(figment: &Figment) -> anyhow::Result<Vec<SocketAddr>> {
+    let addresses = match figment.find_value("http.address") {
+        Ok(Value::Array(_, addresses)) => addresses,
+        _ => return Ok(Vec::new()),
+    };
+    let port: u16 = figment.extract_inner("http.port")?;
+    addresses
+        .iter()
+        .map(|address| {
+            let address = address
+                .as_str()
+                .ok_or_else(|| anyhow::anyhow!("Invalid http.address {address:?}"))?;
+            match address.parse::<SocketAddr>() {
+                Ok(listener) => Ok(listener),
+                Err(_) => Ok(SocketAddr::new(
+                    address
+                        .trim_matches(|c| c == '[' || c == ']')
+                        .parse::<IpAddr>()?,
+                    port,
+                )),
+            }
+        })
+        .collect()
+}
+
 fn remove_trailing_slash(s: &str) -> &str {
     if s.ends_with('/') {
         debug!("Removing trailing slash from {}", s);
@@ -77,61 +718,267 @@ fn remove_trailing_slash(s: &str) -> &str {
 
 pub fn get_tasks(config: Config) -> anyhow::Result<Vec<Task>> {
     let mut tasks = Vec::new();
+    let geoip = Geolocator::new(&config.geoip.unwrap_or_default());
     if let Some(sonarr) = config.sonarr {
         for (name, s) in sonarr {
-            let client = Sonarr::new(&name, remove_trailing_slash(&s.address), &s.api_key)?;
-            tasks.push(Task::SonarrToday(client.clone()));
-            tasks.push(Task::SonarrMissing(client));
+            let mut client = Sonarr::new(
+                &name,
+                remove_trailing_slash(&s.address),
+                &s.api_key,
+                &s.client_options,
+            )?;
+            client.today = s.today;
+            client.missing = s.missing;
+            tasks.extend(Task::all(client));
         }
     }
     if let Some(tautulli) = config.tautulli {
-        let tautulli = Tautulli::new(remove_trailing_slash(&tautulli.address), &tautulli.api_key)?;
-        tasks.push(Task::TautulliSession(tautulli.clone()));
-        tasks.push(Task::TautulliLibrary(tautulli));
+        let mut client = Tautulli::new(
+            &tautulli.name,
+            remove_trailing_slash(&tautulli.address),
+            &tautulli.api_key,
+            tautulli.home_stats_days.unwrap_or(30),
+            tautulli.home_stats_count.unwrap_or(10),
+            tautulli.plays_by_date_days.unwrap_or(7),
+            &tautulli.client_options,
+        )?;
+        client.sessions = tautulli.sessions;
+        client.libraries = tautulli.libraries;
+        client.home_stats = tautulli.home_stats;
+        client.history = tautulli.history;
+        client.user_watch_time = tautulli.user_watch_time;
+        client.server = tautulli.server;
+        client.notifications = tautulli.notifications;
+        client.plays_by_date = tautulli.plays_by_date;
+        client.geoip = geoip.clone();
+        tasks.extend(Task::all(client));
     }
     if let Some(radarr) = config.radarr {
         for (name, r) in radarr {
-            let client = Radarr::new(&name, remove_trailing_slash(&r.address), &r.api_key)?;
-            tasks.push(Task::Radarr(client));
+            let client = Radarr::new(
+                &name,
+                remove_trailing_slash(&r.address),
+                &r.api_key,
+                &r.client_options,
+            )?;
+            tasks.extend(Task::all(client));
         }
     }
     if let Some(overseerr) = config.overseerr {
-        let mut reqs = 20;
-        if let Some(requests) = overseerr.requests {
-            reqs = requests;
-        }
-        let overseerr = Overseerr::new(
-            remove_trailing_slash(&overseerr.address),
-            &overseerr.api_key,
-            reqs,
-        )?;
-        tasks.push(Task::Overseerr(overseerr));
+        for (name, o) in overseerr {
+            let reqs = if o.all_requests {
+                None
+            } else {
+                Some(o.requests.unwrap_or(20))
+            };
+            let mut client = Overseerr::new(
+                &name,
+                remove_trailing_slash(&o.address),
+                &o.api_key,
+                reqs,
+                o.aggregate,
+                &o.client_options,
+            )?;
+            client.request_metrics = o.request_metrics;
+            client.jobs = o.jobs;
+            tasks.extend(Task::all(client));
+        }
     }
     if let Some(jellyseerr) = config.jellyseerr {
-        let mut reqs = 20;
-        if let Some(requests) = jellyseerr.requests {
-            reqs = requests;
-        }
-        let jellyseerr = Overseerr::new(
-            remove_trailing_slash(&jellyseerr.address),
-            &jellyseerr.api_key,
-            reqs,
-        )?;
-        tasks.push(Task::Jellyseerr(jellyseerr));
+        for (name, j) in jellyseerr {
+            let reqs = if j.all_requests {
+                None
+            } else {
+                Some(j.requests.unwrap_or(20))
+            };
+            let mut client = Overseerr::new(
+                &name,
+                remove_trailing_slash(&j.address),
+                &j.api_key,
+                reqs,
+                j.aggregate,
+                &j.client_options,
+            )?;
+            client.request_metrics = j.request_metrics;
+            client.jobs = j.jobs;
+            client.jellyseerr = true;
+            tasks.extend(Task::all(client));
+        }
     }
     if let Some(plex) = config.plex {
         for (name, p) in plex {
-            let client = Plex::new(&name, remove_trailing_slash(&p.address), &p.token)?;
-            tasks.push(Task::PlexSession(client.clone()));
-            tasks.push(Task::PlexLibrary(client));
+            let mut client = Plex::new(
+                &name,
+                remove_trailing_slash(&p.address),
+                &p.token,
+                &p.client_options,
+            )?;
+            client.sessions = p.sessions;
+            client.library = p.library;
+            client.accounts = p.accounts;
+            client.geoip = geoip.clone();
+            tasks.extend(Task::all(client));
         }
     }
     if let Some(jellyfin) = config.jellyfin {
         for (name, j) in jellyfin {
-            let client = Jellyfin::new(&name, remove_trailing_slash(&j.address), &j.api_key)?;
-            tasks.push(Task::JellyfinSession(client.clone()));
-            tasks.push(Task::JellyfinLibrary(client));
+            let mut client = Jellyfin::new(
+                &name,
+                remove_trailing_slash(&j.address),
+                &j.api_key,
+                j.lan_networks,
+                j.recently_added_hours.unwrap_or(24),
+                &j.client_options,
+            )?;
+            client.sessions = j.sessions;
+            client.library = j.library;
+            client.devices = j.devices;
+            client.plugins = j.plugins;
+            client.info = j.info;
+            client.recently_added = j.recently_added;
+            client.storage = j.storage;
+            client.geoip = geoip.clone();
+            tasks.extend(Task::all(client));
         }
     }
     Ok(tasks)
 }
+
+pub fn get_probe_tasks(module: &str, target: &str, api_key: &str) -> anyhow::Result<Vec<Task>> {
+    let address = remove_trailing_slash(target);
+    let options = ClientOptions::default();
+    let tasks = match module {
+        "sonarr" => Task::all(Sonarr::new(target, address, api_key, &options)?),
+        "radarr" => Task::all(Radarr::new(target, address, api_key, &options)?),
+        "tautulli" => Task::all(Tautulli::new(
+            target, address, api_key, 30, 10, 7, &options,
+        )?),
+        "overseerr" | "jellyseerr" => {
+            let mut client = Overseerr::new(target, address, api_key, Some(20), false, &options)?;
+            client.jellyseerr = module == "jellyseerr";
+            Task::all(client)
+        }
+        "plex" => Task::all(Plex::new(target, address, api_key, &options)?),
+        "jellyfin" => Task::all(Jellyfin::new(
+            target,
+            address,
+            api_key,
+            Vec::new(),
+            24,
+            &options,
+        )?),
+        _ => anyhow::bail!("Unknown probe module {module}"),
+    };
+    Ok(tasks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn redact_masks_secret_keys() {
+        let mut value = json!({
+            "sonarr": {"main": {"address": "http://sonarr:8989", "apikey": "key"}},
+            "plex": {"main": {"token": "token", "lan_networks": ["10.0.0.0/8"]}},
+            "http": {"basic_auth": {"username": "admin", "password": "secret"}, "bearer_token": "bearer"},
+            "sentry": {"dsn": "https://key@sentry.io/1"},
+            "alerts": {"rules": {"busy": {"webhook": "https://discord.com/api/webhooks/1", "metric": "m"}}},
+            "tracing": {"headers": {"authorization": "secret"}},
+            "webhooks": {"tautulli": true, "tautulli_token": "hook"},
+            "geoip": {"secret_key": null},
+        });
+        redact(&mut value);
+        assert_eq!(
+            value,
+            json!({
+                "sonarr": {"main": {"address": "http://sonarr:8989", "apikey": "********"}},
+                "plex": {"main": {"token": "********", "lan_networks": ["10.0.0.0/8"]}},
+                "http": {"basic_auth": {"username": "admin", "password": "********"}, "bearer_token": "********"},
+                "sentry": {"dsn": "********"},
+                "alerts": {"rules": {"busy": {"webhook": "********", "metric": "m"}}},
+                "tracing": {"headers": "********"},
+                "webhooks": {"tautulli": true, "tautulli_token": "********"},
+                "geoip": {"secret_key": null},
+            })
+        );
+    }
+
+    #[test]
+    fn redact_covers_every_secret_file_key() {
+        for key in SECRET_KEYS {
+            assert!(REDACTED_KEYS.contains(&key), "{key} is not redacted");
+        }
+    }
+
+    #[test]
+    fn collect_secret_files_finds_nested_keys() {
+        let value = Figment::from(Toml::string(
+            r#"
+            [sonarr.main]
+            address = "http://sonarr:8989"
+            api_key_file = "/run/secrets/sonarr"
+            [plex.main]
+            token_file = "/run/secrets/plex"
+            [http.basic_auth]
+            username = "admin"
+            password_file = "/run/secrets/password"
+            [webhooks]
+            jellyfin_token_file = "/run/secrets/jellyfin"
+            [log]
+            log_file = "/var/log/homers.log"
+            "#,
+        ))
+        .extract::<Value>()
+        .unwrap();
+        let mut secrets = Vec::new();
+        collect_secret_files(&value, "", &mut secrets);
+        secrets.sort();
+        assert_eq!(
+            secrets,
+            [
+                ("http.basic_auth.password", "/run/secrets/password"),
+                ("plex.main.token", "/run/secrets/plex"),
+                ("sonarr.main.apikey", "/run/secrets/sonarr"),
+                ("webhooks.jellyfin_token", "/run/secrets/jellyfin"),
+            ]
+            .map(|(key, file)| (key.to_string(), file.to_string()))
+        );
+    }
+
+    #[test]
+    fn rate_limit_rejects_zero_requests_or_period() {
+        let options = |requests, period| HttpOptions {
+            rate_limit: Some(RateLimit { requests, period }),
+            ..Default::default()
+        };
+        assert!(options(0, 60).rate_limit().is_err());
+        assert!(options(10, 0).rate_limit().is_err());
+        let limit = options(10, 60).rate_limit().unwrap().unwrap();
+        assert_eq!((limit.requests, limit.period), (10, 60));
+        assert!(HttpOptions::default().rate_limit().unwrap().is_none());
+    }
+
+    #[test]
+    fn probe_tasks_come_from_the_provider() {
+        let tasks = get_probe_tasks("jellyseerr", "http://jellyseerr:5055/", "key").unwrap();
+        let kinds: Vec<_> = tasks
+            .iter()
+            .map(|task| (task.provider(), task.kind(), task.flag()))
+            .collect();
+        let provider = (
+            "jellyseerr",
+            "http://jellyseerr:5055/",
+            "http://jellyseerr:5055",
+        );
+        assert_eq!(
+            kinds,
+            [
+                (provider, "jellyseerr", Some("request_metrics")),
+                (provider, "jellyseerr_jobs", Some("jobs")),
+            ]
+        );
+        assert!(get_probe_tasks("unifi", "http://unifi", "key").is_err());
+    }
+}
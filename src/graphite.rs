@@ -0,0 +1,97 @@
+use log::{debug, info, warn};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+
+use crate::collector::Collector;
+use crate::config::GraphiteOptions;
+use crate::prometheus::JsonSample;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Sends the metrics of every task to carbon over the plaintext protocol,
+/// for Graphite based stacks.
+pub struct GraphitePusher {
+    address: String,
+    prefix: String,
+    interval: Duration,
+    collector: Collector,
+}
+
+// Graphite nodes are separated by dots, anything but letters, digits, `_`
+// and `-` is replaced.
+fn node(value: &str) -> String {
+    let node: String = value
+        .chars()
+        .map(
+            |c| match c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                true => c,
+                false => '_',
+            },
+        )
+        .collect();
+    match node.is_empty() {
+        true => "_".to_string(),
+        false => node,
+    }
+}
+
+// `homers_plex_sessions{name="main",user="alice"} 1` is sent as
+// `<prefix>.plex_sessions.name.main.user.alice 1 <timestamp>`, labels
+// sorted by name.
+fn line(prefix: &str, sample: &JsonSample, timestamp: u64) -> String {
+    let name = sample.name.strip_prefix("homers_").unwrap_or(&sample.name);
+    let mut path = format!("{prefix}.{}", node(name));
+    for (label, value) in &sample.labels {
+        path.push_str(&format!(".{}.{}", node(label), node(value)));
+    }
+    format!("{path} {} {timestamp}\n", sample.value)
+}
+
+impl GraphitePusher {
+    pub fn new(options: &GraphiteOptions, collector: Collector) -> Self {
+        GraphitePusher {
+            address: format!("{}:{}", options.host, options.port()),
+            prefix: options.prefix().to_string(),
+            interval: options.interval(),
+            collector,
+        }
+    }
+
+    /// Sends at startup, then every `interval`.
+    pub fn spawn(self) {
+        info!(
+            "Sending metrics to Graphite at {} every {:?}",
+            self.address, self.interval
+        );
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.interval);
+            loop {
+                interval.tick().await;
+                match self.push().await {
+                    Ok(count) => debug!("Sent {count} metrics to Graphite at {}", self.address),
+                    Err(e) => warn!(
+                        "Failed to send metrics to Graphite at {}: {e}",
+                        self.address
+                    ),
+                }
+            }
+        });
+    }
+
+    async fn push(&self) -> anyhow::Result<usize> {
+        let families = self.collector.latest().await?.families()?;
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let lines: Vec<String> = families
+            .iter()
+            .flat_map(|family| &family.samples)
+            .filter(|sample| sample.value.is_finite())
+            .map(|sample| line(&self.prefix, sample, timestamp))
+            .collect();
+        let mut stream =
+            tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect(&self.address)).await??;
+        stream.write_all(lines.concat().as_bytes()).await?;
+        stream.shutdown().await?;
+        Ok(lines.len())
+    }
+}
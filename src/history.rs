@@ -0,0 +1,411 @@
+use chrono::{Local, NaiveTime, TimeZone};
+use log::{info, warn};
+use rusqlite::types::ToSqlOutput;
+use rusqlite::{params_from_iter, Connection, OptionalExtension, ParamsFromIter, ToSql};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::config::HistoryOptions;
+use crate::tasks::{HistoryResult, HistoryValue, TaskResult};
+
+// Scrapes closer than this to the last snapshot of a series are not recorded.
+const SNAPSHOT_INTERVAL_SECONDS: i64 = 300;
+const DAY_SECONDS: i64 = 24 * 60 * 60;
+
+// Snapshots are only appended, and deleted once older than the retention.
+const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS snapshots (
+    kind TEXT NOT NULL,
+    provider TEXT NOT NULL,
+    name TEXT NOT NULL,
+    library TEXT NOT NULL,
+    time INTEGER NOT NULL,
+    value REAL NOT NULL
+)";
+const SERIES_INDEX: &str = "CREATE INDEX IF NOT EXISTS snapshots_series
+    ON snapshots (kind, provider, name, library, time)";
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum SeriesKind {
+    LibraryItems,
+    Sessions,
+    Requests,
+}
+
+impl SeriesKind {
+    fn name(&self) -> &'static str {
+        match self {
+            SeriesKind::LibraryItems => "library_items",
+            SeriesKind::Sessions => "sessions",
+            SeriesKind::Requests => "requests",
+        }
+    }
+}
+
+impl ToSql for SeriesKind {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(self.name().into())
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct SeriesKey {
+    kind: SeriesKind,
+    provider: String,
+    name: String,
+    // Empty but for library series.
+    library: String,
+}
+
+impl SeriesKey {
+    // Bound to `?1` to `?4`, `extra` follows from `?5`.
+    fn params<'a>(&'a self, extra: &[&'a dyn ToSql]) -> ParamsFromIter<Vec<&'a dyn ToSql>> {
+        let mut params: Vec<&dyn ToSql> =
+            vec![&self.kind, &self.provider, &self.name, &self.library];
+        params.extend(extra);
+        params_from_iter(params)
+    }
+}
+
+/// Snapshots of library, session and request counts, kept in an SQLite
+/// database so deltas over a day survive restarts even though providers only
+/// expose the current state.
+pub struct HistoryStore {
+    connection: Arc<Mutex<Connection>>,
+    retention: Duration,
+}
+
+// Current values of the series found in a scrape.
+fn current_values(results: &[TaskResult]) -> Vec<(SeriesKey, f64)> {
+    let key = |kind, provider: &str, name: &str, library: &str| SeriesKey {
+        kind,
+        provider: provider.to_string(),
+        name: name.to_string(),
+        library: library.to_string(),
+    };
+    let mut values = Vec::new();
+    for result in results {
+        match result {
+            TaskResult::PlexLibrary(result) | TaskResult::JellyfinLibrary(result) => {
+                for library in &result.libraries {
+                    values.push((
+                        key(
+                            SeriesKind::LibraryItems,
+                            &result.kind,
+                            &result.name,
+                            &library.name,
+                        ),
+                        library.count as f64,
+                    ));
+                }
+            }
+            TaskResult::PlexSession(result) | TaskResult::JellyfinSession(result) => {
+                values.push((
+                    key(SeriesKind::Sessions, &result.kind, &result.name, ""),
+                    result.sessions.len() as f64,
+                ));
+            }
+            TaskResult::Overseerr(result) | TaskResult::Jellyseerr(result) => {
+                if let Some(counts) = &result.counts {
+                    values.push((
+                        key(SeriesKind::Requests, &result.kind, &result.name, ""),
+                        (counts.movie + counts.tv) as f64,
+                    ));
+                }
+            }
+            _ => {}
+        }
+    }
+    values
+}
+
+fn start_of_today() -> i64 {
+    let midnight = Local::now().date_naive().and_time(NaiveTime::MIN);
+    Local
+        .from_local_datetime(&midnight)
+        .earliest()
+        .map(|midnight| midnight.timestamp())
+        .unwrap_or_default()
+}
+
+// Takes the snapshots of a scrape in one transaction and derives the deltas.
+fn record(
+    connection: &Mutex<Connection>,
+    values: Vec<(SeriesKey, f64)>,
+    retention: Duration,
+) -> anyhow::Result<HistoryResult> {
+    let mut connection = connection
+        .lock()
+        .map_err(|_| anyhow::anyhow!("the history store lock is poisoned"))?;
+    let transaction = connection.transaction()?;
+    let history = snapshot(
+        &transaction,
+        values,
+        retention,
+        chrono::Utc::now().timestamp(),
+        start_of_today(),
+    )?;
+    transaction.commit()?;
+    Ok(history)
+}
+
+// `today` is the time of the last midnight, both are in seconds.
+fn snapshot(
+    connection: &Connection,
+    values: Vec<(SeriesKey, f64)>,
+    retention: Duration,
+    now: i64,
+    today: i64,
+) -> anyhow::Result<HistoryResult> {
+    let mut history = HistoryResult::default();
+    let mut recorded = false;
+    for (key, value) in values {
+        // Value of the last snapshot taken at or before a time.
+        let value_at = |at: i64| {
+            connection
+                .query_row(
+                    "SELECT value FROM snapshots
+                    WHERE kind = ?1 AND provider = ?2 AND name = ?3 AND library = ?4 AND time <= ?5
+                    ORDER BY time DESC LIMIT 1",
+                    key.params(&[&at]),
+                    |row| row.get::<_, f64>(0),
+                )
+                .optional()
+        };
+        let previous = match key.kind {
+            SeriesKind::LibraryItems => value_at(now - DAY_SECONDS)?,
+            SeriesKind::Requests => value_at(today)?,
+            SeriesKind::Sessions => connection
+                .query_row(
+                    "SELECT MAX(value) FROM snapshots
+                    WHERE kind = ?1 AND provider = ?2 AND name = ?3 AND library = ?4 AND time > ?5",
+                    key.params(&[&(now - DAY_SECONDS)]),
+                    |row| row.get::<_, Option<f64>>(0),
+                )?
+                .or(Some(value)),
+        };
+        let last = connection.query_row(
+            "SELECT MAX(time) FROM snapshots
+            WHERE kind = ?1 AND provider = ?2 AND name = ?3 AND library = ?4",
+            key.params(&[]),
+            |row| row.get::<_, Option<i64>>(0),
+        )?;
+        if last.map_or(true, |time| now - time >= SNAPSHOT_INTERVAL_SECONDS) {
+            connection.execute(
+                "INSERT INTO snapshots (kind, provider, name, library, time, value)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                key.params(&[&now, &value]),
+            )?;
+            recorded = true;
+        }
+        let Some(previous) = previous else {
+            continue;
+        };
+        let derived = |value| HistoryValue {
+            provider: key.provider.clone(),
+            name: key.name.clone(),
+            library: key.library.clone(),
+            value,
+        };
+        match key.kind {
+            SeriesKind::LibraryItems => history.library_items_added.push(derived(value - previous)),
+            SeriesKind::Requests => history.requests_added_today.push(derived(value - previous)),
+            SeriesKind::Sessions => history.sessions_peak.push(derived(previous.max(value))),
+        }
+    }
+    if recorded {
+        connection.execute(
+            "DELETE FROM snapshots WHERE time < ?1",
+            [now - retention.as_secs() as i64],
+        )?;
+    }
+    Ok(history)
+}
+
+impl HistoryStore {
+    pub fn open(options: &HistoryOptions) -> anyhow::Result<Self> {
+        let retention = options.retention()?;
+        let connection = Connection::open(&options.path)
+            .and_then(|connection| {
+                connection.execute(SCHEMA, [])?;
+                connection.execute(SERIES_INDEX, [])?;
+                Ok(connection)
+            })
+            .map_err(|e| {
+                anyhow::anyhow!("Failed to open the history store {:?}: {e}", options.path)
+            })?;
+        info!("Recording history snapshots in {:?}", options.path);
+        Ok(HistoryStore {
+            connection: Arc::new(Mutex::new(connection)),
+            retention,
+        })
+    }
+
+    /// Records the counts of a scrape and returns the deltas against the
+    /// snapshots of the day before and of midnight.
+    pub async fn record(&self, results: &[TaskResult]) -> HistoryResult {
+        let values = current_values(results);
+        let connection = self.connection.clone();
+        let retention = self.retention;
+        tokio::task::spawn_blocking(move || record(&connection, values, retention))
+            .await
+            .map_err(anyhow::Error::from)
+            .and_then(|history| history)
+            .unwrap_or_else(|e| {
+                warn!("Failed to record the history snapshots: {e}");
+                HistoryResult::default()
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    // Noon, so the midnight before is well inside the day.
+    const NOW: i64 = 1_700_049_600;
+    const MIDNIGHT: i64 = NOW - 12 * 60 * 60;
+
+    fn store(directory: &TempDir) -> HistoryStore {
+        HistoryStore::open(&HistoryOptions {
+            path: directory.path().join("history.db"),
+            retention: None,
+        })
+        .unwrap()
+    }
+
+    fn key(kind: SeriesKind) -> SeriesKey {
+        SeriesKey {
+            kind,
+            provider: "plex".to_string(),
+            name: "main".to_string(),
+            library: match kind {
+                SeriesKind::LibraryItems => "Movies".to_string(),
+                _ => String::new(),
+            },
+        }
+    }
+
+    fn take(store: &HistoryStore, kind: SeriesKind, value: f64, now: i64) -> HistoryResult {
+        let connection = store.connection.lock().unwrap();
+        snapshot(
+            &connection,
+            vec![(key(kind), value)],
+            store.retention,
+            now,
+            MIDNIGHT,
+        )
+        .unwrap()
+    }
+
+    fn snapshots(store: &HistoryStore) -> Vec<(i64, f64)> {
+        let connection = store.connection.lock().unwrap();
+        let mut statement = connection
+            .prepare("SELECT time, value FROM snapshots ORDER BY time")
+            .unwrap();
+        let rows = statement
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap();
+        rows.map(Result::unwrap).collect()
+    }
+
+    fn values(values: &[HistoryValue]) -> Vec<f64> {
+        values.iter().map(|value| value.value).collect()
+    }
+
+    #[test]
+    fn open_rejects_short_retention() {
+        let directory = TempDir::new().unwrap();
+        let options = HistoryOptions {
+            path: directory.path().join("history.db"),
+            retention: Some(86400),
+        };
+        assert!(HistoryStore::open(&options).is_err());
+        assert!(!options.path.exists());
+    }
+
+    #[test]
+    fn open_keeps_snapshots_across_restarts() {
+        let directory = TempDir::new().unwrap();
+        take(&store(&directory), SeriesKind::Sessions, 3.0, NOW);
+        assert_eq!(snapshots(&store(&directory)), [(NOW, 3.0)]);
+    }
+
+    #[test]
+    fn snapshots_are_throttled() {
+        let directory = TempDir::new().unwrap();
+        let store = store(&directory);
+        take(&store, SeriesKind::Sessions, 1.0, NOW);
+        take(
+            &store,
+            SeriesKind::Sessions,
+            2.0,
+            NOW + SNAPSHOT_INTERVAL_SECONDS - 1,
+        );
+        take(
+            &store,
+            SeriesKind::Sessions,
+            3.0,
+            NOW + SNAPSHOT_INTERVAL_SECONDS,
+        );
+        assert_eq!(
+            snapshots(&store),
+            [(NOW, 1.0), (NOW + SNAPSHOT_INTERVAL_SECONDS, 3.0)]
+        );
+    }
+
+    #[test]
+    fn library_items_compare_with_the_day_before() {
+        let directory = TempDir::new().unwrap();
+        let store = store(&directory);
+        let history = take(&store, SeriesKind::LibraryItems, 100.0, NOW - DAY_SECONDS);
+        // Nothing to compare with yet.
+        assert!(history.library_items_added.is_empty());
+        take(&store, SeriesKind::LibraryItems, 104.0, NOW - 3600);
+        let history = take(&store, SeriesKind::LibraryItems, 110.0, NOW);
+        assert_eq!(values(&history.library_items_added), [10.0]);
+        assert_eq!(history.library_items_added[0].library, "Movies");
+    }
+
+    #[test]
+    fn requests_compare_with_midnight() {
+        let directory = TempDir::new().unwrap();
+        let store = store(&directory);
+        take(&store, SeriesKind::Requests, 40.0, MIDNIGHT - 3600);
+        take(&store, SeriesKind::Requests, 42.0, MIDNIGHT + 3600);
+        let history = take(&store, SeriesKind::Requests, 45.0, NOW);
+        assert_eq!(values(&history.requests_added_today), [5.0]);
+    }
+
+    #[test]
+    fn sessions_peak_over_a_day() {
+        let directory = TempDir::new().unwrap();
+        let store = store(&directory);
+        assert_eq!(
+            values(&take(&store, SeriesKind::Sessions, 2.0, NOW - DAY_SECONDS).sessions_peak),
+            [2.0]
+        );
+        take(&store, SeriesKind::Sessions, 6.0, NOW - 3600);
+        let history = take(&store, SeriesKind::Sessions, 1.0, NOW);
+        assert_eq!(values(&history.sessions_peak), [6.0]);
+        // The snapshot from a day ago has left the window.
+        let history = take(&store, SeriesKind::Sessions, 1.0, NOW + DAY_SECONDS - 3600);
+        assert_eq!(values(&history.sessions_peak), [1.0]);
+    }
+
+    #[test]
+    fn old_snapshots_are_pruned() {
+        let directory = TempDir::new().unwrap();
+        let store = store(&directory);
+        let retention = store.retention.as_secs() as i64;
+        take(
+            &store,
+            SeriesKind::Sessions,
+            1.0,
+            NOW - retention - SNAPSHOT_INTERVAL_SECONDS,
+        );
+        take(&store, SeriesKind::Sessions, 2.0, NOW - retention);
+        take(&store, SeriesKind::Sessions, 3.0, NOW);
+        assert_eq!(snapshots(&store), [(NOW - retention, 2.0), (NOW, 3.0)]);
+    }
+}
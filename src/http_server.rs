@@ -1,18 +1,59 @@
 use anyhow::Result;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::{DateTime, Utc};
 use futures::future::try_join_all;
-use log::{error, info};
-use rocket::http::{Accept, ContentType, Status};
+use futures::{SinkExt, StreamExt};
+use log::{error, info, warn};
+use rocket::config::{MutualTls, TlsConfig};
+use rocket::fairing::{AdHoc, Fairing, Info, Kind};
+use rocket::form::{Form, FromForm};
+use rocket::http::{Accept, ContentType, Header, Method, Status};
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::response::content::RawHtml;
+use rocket::response::stream::{Event, EventStream};
+use rocket::serde::json::Json;
+use rocket::tokio::select;
+use rocket::tokio::sync::broadcast::error::RecvError;
+use rocket::tokio::sync::Mutex;
 use rocket::tokio::task;
-use rocket::{get, routes, Build, Responder, Rocket, State};
-use std::process::exit;
+use rocket::{
+    catch, catchers, get, post, routes, Build, Responder, Response, Rocket, Shutdown, State,
+};
+use rocket_ws::{Channel, Message, WebSocket};
+use serde::Serialize;
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
+use std::io::Cursor;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::task::JoinError;
+use tracing::Instrument;
 
-use crate::config::{get_tasks, Config};
-use crate::prometheus::{format_metrics, Format};
+use crate::alerts::Alerter;
+use crate::collector::CollectionLoop;
+use crate::config::{
+    self, get_probe_tasks, get_tasks, AlertOptions, Config, ConfigSource, GraphiteOptions,
+    HttpOptions, OtlpOptions, ProbeCredentials, PushOptions, RateLimit, Webhooks,
+};
+use crate::graphite::GraphitePusher;
+use crate::history::HistoryStore;
+use crate::otlp::OtlpExporter;
+use crate::prometheus::{
+    format_metrics, set_provider_latency_buckets, Format, HttpMetrics, MetricRegistry,
+    WebhookMetrics,
+};
+use crate::providers::jellyfin::WebhookPayload as JellyfinWebhookPayload;
+use crate::providers::plex::WebhookPayload as PlexWebhookPayload;
+use crate::providers::tautulli::WebhookPayload as TautulliWebhookPayload;
+use crate::providers::track_failure;
+use crate::pushgateway::Pusher;
+use crate::result_cache::ResultCache;
+use crate::session_feed::SessionFeed;
 use crate::tasks::{
-    LibraryResult, OverseerrRequestResult, RadarrMovieResult, SessionResult, SonarrEpisodeResult,
-    SonarrMissingResult, Task, TaskResult, TautulliLibraryResult, TautulliSessionResult,
+    CollectionOutcome, CollectionResult, LibraryResult, SessionResult, Task, TaskList, TaskResult,
 };
+use crate::version_check;
 
 #[derive(Responder, Debug, PartialEq, Eq)]
 #[response(content_type = "text/plain; charset=utf-8")]
@@ -29,10 +70,10 @@ pub struct MetricsResponse {
 
 impl MetricsResponse {
     fn new(status: Status, content_type: Format, response: String) -> Self {
-        let content_type = if status.class().is_success() && content_type == Format::OpenMetrics {
-            get_openmetrics_content_type()
-        } else {
-            get_text_plain_content_type()
+        let content_type = match content_type {
+            Format::OpenMetrics if status.class().is_success() => get_openmetrics_content_type(),
+            Format::Json if status.class().is_success() => ContentType::JSON,
+            _ => get_text_plain_content_type(),
         };
 
         Self {
@@ -41,154 +82,1443 @@ impl MetricsResponse {
         }
     }
 }
-pub async fn configure_rocket(config: Config) -> Rocket<Build> {
+
+#[derive(Responder, Debug)]
+#[response(status = 401, content_type = "text/plain; charset=utf-8")]
+pub struct Unauthorized {
+    message: &'static str,
+    authenticate: Header<'static>,
+}
+
+pub struct Authenticated;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Authenticated {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let options = match request.rocket().state::<HttpOptions>() {
+            Some(options) => options,
+            None => return Outcome::Success(Authenticated),
+        };
+        if options.basic_auth.is_none() && options.bearer_token.is_none() {
+            return Outcome::Success(Authenticated);
+        }
+        let header = request.headers().get_one("Authorization").unwrap_or("");
+        let basic_authorized = match &options.basic_auth {
+            Some(basic_auth) => {
+                let expected = format!("{}:{}", basic_auth.username, basic_auth.password);
+                header
+                    .strip_prefix("Basic ")
+                    .and_then(|credentials| STANDARD.decode(credentials.trim()).ok())
+                    .map(|credentials| credentials == expected.as_bytes())
+                    .unwrap_or(false)
+            }
+            None => false,
+        };
+        let bearer_authorized = match &options.bearer_token {
+            Some(token) => header
+                .strip_prefix("Bearer ")
+                .map(|bearer| bearer.trim() == token)
+                .unwrap_or(false),
+            None => false,
+        };
+        if basic_authorized || bearer_authorized {
+            Outcome::Success(Authenticated)
+        } else {
+            Outcome::Error((Status::Unauthorized, ()))
+        }
+    }
+}
+
+#[derive(Responder, Debug)]
+#[response(status = 429, content_type = "text/plain; charset=utf-8")]
+pub struct TooManyRequests {
+    message: &'static str,
+    retry_after: Header<'static>,
+}
+
+// Token bucket per client address: each client may burst up to `requests`
+// scrapes, refilled evenly over `period` seconds.
+#[derive(Clone)]
+pub struct RateLimiter {
+    limit: RateLimit,
+    buckets: Arc<std::sync::Mutex<HashMap<IpAddr, (f64, Instant)>>>,
+}
+
+impl RateLimiter {
+    fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            buckets: Arc::new(std::sync::Mutex::new(HashMap::new())),
+        }
+    }
+    fn allow(&self, client: IpAddr) -> bool {
+        self.allow_at(client, Instant::now())
+    }
+    fn allow_at(&self, client: IpAddr, now: Instant) -> bool {
+        let capacity = f64::from(self.limit.requests);
+        let period = self.limit.period.max(1) as f64;
+        let refill = capacity / period;
+        let mut buckets = match self.buckets.lock() {
+            Ok(buckets) => buckets,
+            Err(_) => return true,
+        };
+        // A bucket full for a whole period is the same as a new one.
+        buckets.retain(|_, (tokens, last)| {
+            now.saturating_duration_since(*last).as_secs_f64()
+                < (capacity - *tokens) / refill + period
+        });
+        let (tokens, last) = buckets.entry(client).or_insert((capacity, now));
+        *tokens = (*tokens + now.duration_since(*last).as_secs_f64() * refill).min(capacity);
+        *last = now;
+        if *tokens >= 1.0 {
+            *tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+    fn retry_after(&self) -> u64 {
+        (self.limit.period / u64::from(self.limit.requests.max(1))).max(1)
+    }
+}
+
+pub struct RateLimited;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for RateLimited {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let limiter = match request.rocket().state::<Option<RateLimiter>>() {
+            Some(Some(limiter)) => limiter,
+            _ => return Outcome::Success(RateLimited),
+        };
+        match request.client_ip() {
+            Some(client) if !limiter.allow(client) => {
+                info!("Rate limiting scrape from {client}");
+                Outcome::Error((Status::TooManyRequests, ()))
+            }
+            _ => Outcome::Success(RateLimited),
+        }
+    }
+}
+
+const READINESS_CACHE_DURATION: Duration = Duration::from_secs(30);
+const READINESS_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ProviderReadiness {
+    provider: String,
+    name: String,
+    reachable: bool,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Readiness {
+    ready: bool,
+    providers: Vec<ProviderReadiness>,
+}
+
+// Table response of the SimpleJSON datasource, also understood by Infinity.
+#[derive(Serialize, Debug)]
+pub struct QueryTable {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    columns: Vec<QueryColumn>,
+    rows: Vec<Vec<serde_json::Value>>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct QueryColumn {
+    text: &'static str,
+    #[serde(rename = "type")]
+    kind: &'static str,
+}
+
+impl QueryTable {
+    fn new(columns: &[(&'static str, &'static str)]) -> Self {
+        Self {
+            kind: "table",
+            columns: columns
+                .iter()
+                .map(|(text, kind)| QueryColumn { text, kind })
+                .collect(),
+            rows: Vec::new(),
+        }
+    }
+}
+
+#[derive(Default, Clone)]
+pub struct ReadinessCache {
+    last: Arc<Mutex<Option<(Instant, Readiness)>>>,
+}
+
+pub struct Cors {
+    allowed_origins: Vec<String>,
+}
+
+#[rocket::async_trait]
+impl Fairing for Cors {
+    fn info(&self) -> Info {
+        Info {
+            name: "CORS",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let origin = match request.headers().get_one("Origin") {
+            Some(origin) => origin,
+            None => return,
+        };
+        let allowed_origin = if self.allowed_origins.iter().any(|allowed| allowed == "*") {
+            "*"
+        } else if self.allowed_origins.iter().any(|allowed| allowed == origin) {
+            response.set_header(Header::new("Vary", "Origin"));
+            origin
+        } else {
+            return;
+        };
+        response.set_header(Header::new(
+            "Access-Control-Allow-Origin",
+            allowed_origin.to_string(),
+        ));
+        response.set_header(Header::new("Access-Control-Allow-Methods", "GET, OPTIONS"));
+        response.set_header(Header::new(
+            "Access-Control-Allow-Headers",
+            "Authorization, Content-Type",
+        ));
+        if request.method() == Method::Options {
+            response.set_status(Status::NoContent);
+            response.set_sized_body(0, Cursor::new(""));
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CollectionRecord {
+    collected_at: DateTime<Utc>,
+    timed_out: bool,
+}
+
+type CollectionKey = (String, String, String);
+
+#[derive(Default, Clone)]
+pub struct CollectionStatus {
+    last: Arc<std::sync::Mutex<HashMap<CollectionKey, CollectionRecord>>>,
+}
+
+impl CollectionStatus {
+    fn record(&self, task: CollectionKey, timed_out: bool) {
+        if let Ok(mut last) = self.last.lock() {
+            last.insert(
+                task,
+                CollectionRecord {
+                    collected_at: Utc::now(),
+                    timed_out,
+                },
+            );
+        }
+    }
+    fn get(&self, task: &CollectionKey) -> Option<CollectionRecord> {
+        self.last
+            .lock()
+            .ok()
+            .and_then(|last| last.get(task).cloned())
+    }
+}
+
+struct RequestStart(Instant);
+
+// Time spent on each task of a scrape, `None` when the task timed out.
+#[derive(Default)]
+pub struct ScrapeTimings(std::sync::Mutex<Vec<(String, Option<Duration>)>>);
+
+impl ScrapeTimings {
+    fn record(&self, task: String, duration: Option<Duration>) {
+        if let Ok(mut timings) = self.0.lock() {
+            timings.push((task, duration));
+        }
+    }
+    // One entry per provider instance, the slowest first. Tasks of the same
+    // instance run concurrently, so the slowest one is the time spent on it.
+    fn summary(&self) -> String {
+        let mut instances: Vec<(String, Option<Duration>)> = Vec::new();
+        for (task, duration) in self.0.lock().map(|t| t.clone()).unwrap_or_default() {
+            match instances.iter_mut().find(|(name, _)| *name == task) {
+                Some((_, slowest)) => {
+                    *slowest = match (*slowest, duration) {
+                        (Some(a), Some(b)) => Some(a.max(b)),
+                        _ => None,
+                    }
+                }
+                None => instances.push((task, duration)),
+            }
+        }
+        instances.sort_by_key(|(_, duration)| std::cmp::Reverse(duration.unwrap_or(Duration::MAX)));
+        instances
+            .iter()
+            .map(|(task, duration)| match duration {
+                Some(duration) => format!("{task}={:.3}s", duration.as_secs_f64()),
+                None => format!("{task}=timeout"),
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for &'r ScrapeTimings {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(request.local_cache(ScrapeTimings::default))
+    }
+}
+
+// Logs one line per scrape with the time spent on each provider instance.
+pub struct AccessLogFairing;
+
+#[rocket::async_trait]
+impl Fairing for AccessLogFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Access log",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        if !matches!(
+            request.route().and_then(|route| route.name.as_deref()),
+            Some("metrics" | "metrics_json" | "probe")
+        ) {
+            return;
+        }
+        let start = request.local_cache(|| RequestStart(Instant::now()));
+        let timings = request.local_cache(ScrapeTimings::default);
+        info!(
+            target: "homers::access",
+            "{} {} {} {} {:.3}s {}",
+            request
+                .client_ip()
+                .map_or("-".to_string(), |ip| ip.to_string()),
+            request.method(),
+            request.uri(),
+            response.status().code,
+            start.0.elapsed().as_secs_f64(),
+            timings.summary()
+        );
+    }
+}
+
+pub struct HttpMetricsFairing;
+
+#[rocket::async_trait]
+impl Fairing for HttpMetricsFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "HTTP metrics",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut rocket::Data<'_>) {
+        request.local_cache(|| RequestStart(Instant::now()));
+        if let Some(http) = request.rocket().state::<HttpMetrics>() {
+            http.start_request();
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let http = match request.rocket().state::<HttpMetrics>() {
+            Some(http) => http,
+            None => return,
+        };
+        let start = request.local_cache(|| RequestStart(Instant::now()));
+        let route = request
+            .route()
+            .map(|route| route.uri.path())
+            .unwrap_or("unmatched");
+        http.end_request(
+            request.method().as_str(),
+            route,
+            response.status().code,
+            start.0.elapsed().as_secs_f64(),
+        );
+    }
+}
+
+#[derive(Clone)]
+pub struct RedactedConfig(serde_json::Value);
+
+#[derive(FromForm)]
+struct PlexWebhookForm<'r> {
+    payload: &'r str,
+}
+
+// Rocket binds a single address, so one instance is built per listener. The
+// managed state is shared between them so every listener reports the same
+// counters.
+pub async fn configure_rockets(
+    config: Config,
+    source: ConfigSource,
+) -> anyhow::Result<Vec<Rocket<Build>>> {
     let config_clone = config.clone();
-    let tasks = task::spawn_blocking(move || get_tasks(config_clone))
-        .await
-        .unwrap_or_else(exit_if_handle_fatal)
-        .unwrap_or_else(exit_if_handle_fatal);
-    rocket::custom(config.http)
-        .manage(tasks)
-        .mount("/", routes![index, metrics])
+    let redacted_config = RedactedConfig(config.redacted());
+    let tasks = task::spawn_blocking(move || get_tasks(config_clone)).await??;
+    if let Some(buckets) = config.http_options.provider_latency_buckets.clone() {
+        set_provider_latency_buckets(buckets);
+    }
+    let tasks = TaskList::new(tasks);
+    version_check::spawn(tasks.clone());
+    #[cfg(unix)]
+    reload_on_sighup(tasks.clone(), source.clone());
+    let webhooks = config.webhooks.unwrap_or_default().served();
+    let mut http = config.http;
+    if let Some(tls) = config.tls {
+        info!("Serving over HTTPS with certificate {:?}", tls.cert_file);
+        let mut tls_config = TlsConfig::from_paths(tls.cert_file, tls.key_file);
+        if let Some(client_ca_file) = tls.client_ca_file {
+            info!(
+                "Requiring client certificates signed by {:?}",
+                client_ca_file
+            );
+            tls_config =
+                tls_config.with_mutual(MutualTls::from_path(client_ca_file).mandatory(true));
+        }
+        http.tls = Some(tls_config);
+    }
+    let cors_allowed_origins = config.http_options.cors_allowed_origins.clone();
+    let rate_limiter = config.http_options.rate_limit()?.map(|limit| {
+        info!(
+            "Rate limiting scrapes to {} per {} seconds per client",
+            limit.requests, limit.period
+        );
+        RateLimiter::new(limit)
+    });
+    let listeners = if config.http_options.listeners.is_empty() {
+        vec![SocketAddr::new(http.address, http.port)]
+    } else {
+        config.http_options.listeners.clone()
+    };
+    let readiness = ReadinessCache::default();
+    let status = CollectionStatus::default();
+    let http_metrics = HttpMetrics::default();
+    let webhook_metrics = WebhookMetrics::new(&webhooks);
+    let metric_registry = Arc::new(Mutex::new(MetricRegistry::new(
+        &webhook_metrics,
+        Some(&http_metrics),
+    )));
+    let session_feed = SessionFeed::new(config.http_options.sessions_stream_interval());
+    let result_cache = config
+        .cache
+        .as_ref()
+        .map(|cache| Arc::new(ResultCache::load(cache)));
+    let history = config
+        .history
+        .as_ref()
+        .map(HistoryStore::open)
+        .transpose()?
+        .map(Arc::new);
+    // One collection for all the exporters, as often as the most frequent one.
+    let export_interval = [
+        config.push.as_ref().map(PushOptions::interval),
+        config.graphite.as_ref().map(GraphiteOptions::interval),
+        config.otlp.as_ref().map(OtlpOptions::interval),
+        config.alerts.as_ref().map(AlertOptions::interval),
+    ]
+    .into_iter()
+    .flatten()
+    .min();
+    if let Some(export_interval) = export_interval {
+        let collector = CollectionLoop {
+            tasks: tasks.clone(),
+            registry: metric_registry.clone(),
+            timeout: config.http_options.timeout(),
+            status: status.clone(),
+            cache: result_cache.clone(),
+            history: history.clone(),
+        }
+        .spawn(export_interval);
+        if let Some(push) = &config.push {
+            Pusher::new(push, collector.clone())?.spawn();
+        }
+        if let Some(graphite) = &config.graphite {
+            GraphitePusher::new(graphite, collector.clone()).spawn();
+        }
+        if let Some(otlp) = &config.otlp {
+            OtlpExporter::new(otlp, collector.clone())?.spawn();
+        }
+        if let Some(alerts) = &config.alerts {
+            Alerter::new(alerts, collector.clone())?.spawn();
+        }
+    }
+    let probe_credentials = config.probe.unwrap_or_default();
+    // The probe sends stored API keys, it is only served behind authentication.
+    let probe =
+        config.http_options.basic_auth.is_some() || config.http_options.bearer_token.is_some();
+    if !probe_credentials.is_empty() && !probe {
+        warn!("/probe is disabled, it needs basic_auth or bearer_token in [http]");
+    }
+    Ok(listeners
+        .into_iter()
+        .map(|listener| {
+            let mut http = http.clone();
+            http.address = listener.ip();
+            http.port = listener.port();
+            info!("Listening on {listener}");
+            build_rocket(
+                rocket::custom(http)
+                    .manage(tasks.clone())
+                    .manage(config.http_options.clone())
+                    .manage(readiness.clone())
+                    .manage(status.clone())
+                    .manage(redacted_config.clone())
+                    .manage(http_metrics.clone())
+                    .manage(probe_credentials.clone())
+                    .manage(rate_limiter.clone())
+                    .manage(webhook_metrics.clone())
+                    .manage(webhooks.clone())
+                    .manage(metric_registry.clone())
+                    .manage(session_feed.clone())
+                    .manage(result_cache.clone())
+                    .manage(history.clone())
+                    .manage(source.clone()),
+                &cors_allowed_origins,
+                &webhooks,
+                probe,
+                config.http_options.access_log,
+            )
+        })
+        .collect())
+}
+
+// Only the provider tasks are reloaded, listener and HTTP options need a restart.
+pub async fn reload_tasks(tasks: &TaskList, source: &ConfigSource) -> anyhow::Result<usize> {
+    let source = source.clone();
+    let reloaded = task::spawn_blocking(move || {
+        config::read(source.path, source.log_level, source.strict).and_then(get_tasks)
+    })
+    .await??;
+    let count = reloaded.len();
+    tasks.replace(reloaded);
+    // New clients start on the current APIs again.
+    let reloaded = tasks.get();
+    tokio::spawn(async move { version_check::check_versions(&reloaded).await });
+    Ok(count)
+}
+
+#[cfg(unix)]
+fn reload_on_sighup(tasks: TaskList, source: ConfigSource) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    tokio::spawn(async move {
+        let mut hangup = match signal(SignalKind::hangup()) {
+            Ok(hangup) => hangup,
+            Err(e) => {
+                error!("Failed to listen for SIGHUP, configuration reload is disabled: {e}");
+                return;
+            }
+        };
+        while hangup.recv().await.is_some() {
+            info!("SIGHUP received, reloading {:?}", source.path);
+            match reload_tasks(&tasks, &source).await {
+                Ok(count) => info!("Configuration reloaded with {count} tasks"),
+                Err(e) => error!("Failed to reload configuration, keeping the current one: {e}"),
+            }
+        }
+    });
+}
+
+fn build_rocket(
+    rocket: Rocket<Build>,
+    cors_allowed_origins: &[String],
+    webhooks: &Webhooks,
+    probe: bool,
+    access_log: bool,
+) -> Rocket<Build> {
+    let mut rocket = rocket
+        .attach(HttpMetricsFairing)
+        .register("/", catchers![unauthorized, too_many_requests])
+        .attach(AdHoc::on_shutdown("Shutdown", |_| {
+            Box::pin(async {
+                info!("Shutdown requested, finishing in-flight scrapes");
+            })
+        }))
+        .mount(
+            "/",
+            routes![
+                index,
+                health,
+                ready,
+                metrics,
+                metrics_json,
+                effective_config,
+                reload,
+                api_sessions,
+                api_sessions_stream,
+                api_query,
+                sessions_websocket,
+                api_libraries
+            ],
+        );
+    if !cors_allowed_origins.is_empty() {
+        info!("CORS enabled for {:?}", cors_allowed_origins);
+        rocket = rocket.attach(Cors {
+            allowed_origins: cors_allowed_origins.to_vec(),
+        });
+    }
+    if access_log {
+        rocket = rocket.attach(AccessLogFairing);
+    }
+    if probe {
+        rocket = rocket.mount("/", routes![probe]);
+    }
+    if webhooks.plex {
+        info!("Plex webhook listener enabled on /webhooks/plex");
+        rocket = rocket.mount("/", routes![plex_webhook]);
+    }
+    if webhooks.tautulli {
+        info!("Tautulli webhook listener enabled on /webhooks/tautulli");
+        rocket = rocket.mount("/", routes![tautulli_webhook]);
+    }
+    if webhooks.jellyfin {
+        info!("Jellyfin webhook listener enabled on /webhooks/jellyfin");
+        rocket = rocket.mount("/", routes![jellyfin_webhook]);
+    }
+    rocket
 }
 
 #[get("/")]
-#[allow(clippy::needless_pass_by_value)]
-fn index() -> Result<String, MetricsError> {
-    let response = "Hello Homers".to_string();
-    Ok(response)
+fn index(
+    _auth: Authenticated,
+    tasks: &State<TaskList>,
+    status: &State<CollectionStatus>,
+) -> RawHtml<String> {
+    let unscheduled_tasks = tasks.get();
+    let rows: String = unscheduled_tasks
+        .iter()
+        .map(|task| {
+            let (provider, name, _) = task.provider();
+            let key = (
+                provider.to_string(),
+                name.to_string(),
+                task.kind().to_string(),
+            );
+            let (collected_at, state) = match status.get(&key) {
+                Some(record) if record.timed_out => (record.collected_at.to_rfc3339(), "timed out"),
+                Some(record) => (record.collected_at.to_rfc3339(), "collected"),
+                None => ("never".to_string(), "pending"),
+            };
+            format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                escape_html(provider),
+                escape_html(name),
+                escape_html(task.kind()),
+                collected_at,
+                state
+            )
+        })
+        .collect();
+    let rows = if rows.is_empty() {
+        "<tr><td colspan=\"5\">No provider configured</td></tr>".to_string()
+    } else {
+        rows
+    };
+    RawHtml(format!(
+        "<!DOCTYPE html><html><head><title>Homers</title></head><body>\
+         <h1>Homers</h1>\
+         <p><a href=\"/metrics\">Metrics</a> | <a href=\"/health\">Health</a> | <a href=\"/ready\">Ready</a></p>\
+         <table><tr><th>Provider</th><th>Instance</th><th>Task</th><th>Last collection</th><th>Status</th></tr>\
+         {rows}</table></body></html>"
+    ))
 }
 
-#[get("/metrics")]
-async fn metrics(
-    unscheduled_tasks: &State<Vec<Task>>,
-    _accept: &Accept,
-) -> Result<MetricsResponse, MetricsError> {
-    Ok(serve_metrics(Format::Prometheus, unscheduled_tasks).await)
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
 }
-async fn process_tasks(tasks: Vec<Task>) -> Result<Vec<TaskResult>, JoinError> {
-    let task_futures: Vec<_> = tasks
-        .into_iter()
-        .map(|task| async {
-            info!("Requesting data for {:?}", &task,);
-            match task {
-                Task::SonarrToday(sonarr) => {
-                    let name = &sonarr.name;
-                    let result = sonarr.get_today_shows().await;
-                    let result = SonarrEpisodeResult {
-                        name: name.to_string(),
-                        episodes: result,
-                    };
-                    Ok(TaskResult::SonarrToday(result))
+
+#[get("/health")]
+fn health() -> &'static str {
+    "OK"
+}
+
+#[get("/ready")]
+async fn ready(
+    tasks: &State<TaskList>,
+    cache: &State<ReadinessCache>,
+) -> (Status, Json<Readiness>) {
+    // Holding the lock while checking keeps concurrent probes from fanning out to providers.
+    let mut last = cache.last.lock().await;
+    let readiness = match last.as_ref() {
+        Some((checked_at, readiness)) if checked_at.elapsed() < READINESS_CACHE_DURATION => {
+            readiness.clone()
+        }
+        _ => {
+            let readiness = check_readiness(&tasks.get()).await;
+            *last = Some((Instant::now(), readiness.clone()));
+            readiness
+        }
+    };
+    let status = if readiness.ready {
+        Status::Ok
+    } else {
+        Status::ServiceUnavailable
+    };
+    (status, Json(readiness))
+}
+
+async fn check_readiness(tasks: &[Task]) -> Readiness {
+    let mut seen = HashSet::new();
+    let checks = tasks
+        .iter()
+        .filter(|task| {
+            let (provider, name, _) = task.provider();
+            seen.insert((provider, name))
+        })
+        .map(|task| async move {
+            let (provider, name, _) = task.provider();
+            let reachable = match tokio::time::timeout(READINESS_TIMEOUT, task.check()).await {
+                Ok(Ok(())) => true,
+                Ok(Err(e)) => {
+                    error!("{provider} {name} is not ready: {e}");
+                    false
                 }
-                Task::SonarrMissing(sonarr) => {
-                    let name = &sonarr.name;
-                    let result = sonarr.get_last_week_missing_shows().await;
-                    let result = SonarrMissingResult {
-                        name: name.to_string(),
-                        episodes: result,
-                    };
-                    Ok(TaskResult::SonarrMissing(result))
+                Err(_) => {
+                    error!("{provider} {name} did not answer within {READINESS_TIMEOUT:?}");
+                    false
                 }
-                Task::TautulliSession(tautulli) => {
-                    let result = tautulli.get_session_summary().await;
-                    let result = TautulliSessionResult { sessions: result };
-                    Ok(TaskResult::TautulliSession(result))
+            };
+            ProviderReadiness {
+                provider: provider.to_string(),
+                name: name.to_string(),
+                reachable,
+            }
+        });
+    let providers = futures::future::join_all(checks).await;
+    Readiness {
+        ready: providers.iter().all(|provider| provider.reachable),
+        providers,
+    }
+}
+
+#[catch(401)]
+fn unauthorized(request: &Request) -> Unauthorized {
+    let basic_auth = request
+        .rocket()
+        .state::<HttpOptions>()
+        .map(|options| options.basic_auth.is_some())
+        .unwrap_or(false);
+    let scheme = if basic_auth { "Basic" } else { "Bearer" };
+    Unauthorized {
+        message: "Unauthorized",
+        authenticate: Header::new("WWW-Authenticate", format!("{scheme} realm=\"homers\"")),
+    }
+}
+
+#[catch(429)]
+fn too_many_requests(request: &Request) -> TooManyRequests {
+    let retry_after = request
+        .rocket()
+        .state::<Option<RateLimiter>>()
+        .and_then(|limiter| limiter.as_ref().map(RateLimiter::retry_after))
+        .unwrap_or(1);
+    TooManyRequests {
+        message: "Too Many Requests",
+        retry_after: Header::new("Retry-After", retry_after.to_string()),
+    }
+}
+
+#[get("/metrics?<collect>&<format>")]
+#[allow(clippy::too_many_arguments)]
+async fn metrics(
+    _auth: Authenticated,
+    _rate_limit: RateLimited,
+    collect: Vec<&str>,
+    format: Option<&str>,
+    tasks: &State<TaskList>,
+    options: &State<HttpOptions>,
+    status: &State<CollectionStatus>,
+    registry: &State<Arc<Mutex<MetricRegistry>>>,
+    cache: &State<Option<Arc<ResultCache>>>,
+    history: &State<Option<Arc<HistoryStore>>>,
+    timings: &ScrapeTimings,
+    accept: Option<&Accept>,
+) -> Result<MetricsResponse, MetricsError> {
+    let unscheduled_tasks = tasks.get();
+    let format = match format {
+        Some(format) => Format::from_name(format).ok_or_else(|| MetricsError {
+            response: (
+                Status::BadRequest,
+                format!("Unknown format {format}, expected openmetrics, prometheus or json"),
+            ),
+        })?,
+        None => accept.map_or(Format::Prometheus, get_format),
+    };
+    let timeout = options.timeout();
+    let cache = cache.as_deref();
+    if collect.is_empty() {
+        return Ok(serve_metrics(
+            format,
+            unscheduled_tasks,
+            registry,
+            timeout,
+            Some(status),
+            Some(timings),
+            cache,
+            history.as_deref(),
+            None,
+        )
+        .await);
+    }
+    let tasks = unscheduled_tasks
+        .iter()
+        .filter(|task| {
+            let (provider, _, _) = task.provider();
+            collect.contains(&provider)
+                || task
+                    .flag()
+                    .is_some_and(|flag| collect.contains(&format!("{provider}.{flag}").as_str()))
+        })
+        .cloned()
+        .collect();
+    Ok(serve_metrics(
+        format,
+        tasks,
+        registry,
+        timeout,
+        Some(status),
+        Some(timings),
+        cache,
+        None,
+        Some(&collect),
+    )
+    .await)
+}
+
+// Every task result of an unfiltered scrape, as the Prometheus encoder sees them.
+#[get("/metrics.json")]
+#[allow(clippy::too_many_arguments)]
+async fn metrics_json(
+    _auth: Authenticated,
+    _rate_limit: RateLimited,
+    tasks: &State<TaskList>,
+    options: &State<HttpOptions>,
+    status: &State<CollectionStatus>,
+    cache: &State<Option<Arc<ResultCache>>>,
+    history: &State<Option<Arc<HistoryStore>>>,
+    timings: &ScrapeTimings,
+) -> Result<Json<Vec<TaskResult>>, MetricsError> {
+    let mut results = process_tasks(
+        tasks.get(),
+        options.timeout(),
+        Some(status),
+        Some(timings),
+        cache.as_deref(),
+    )
+    .await
+    .map_err(|e| {
+        error!("Error while processing tasks: {e}");
+        MetricsError {
+            response: (
+                Status::InternalServerError,
+                "Error while fetching provider data. Check the logs.".into(),
+            ),
+        }
+    })?;
+    if let Some(history) = history.as_deref() {
+        let derived = history.record(&results).await;
+        results.push(TaskResult::History(derived));
+    }
+    Ok(Json(results))
+}
+
+#[get("/probe?<module>&<target>&<api_key_ref>")]
+#[allow(clippy::too_many_arguments)]
+async fn probe(
+    _auth: Authenticated,
+    _rate_limit: RateLimited,
+    module: &str,
+    target: &str,
+    api_key_ref: &str,
+    credentials: &State<HashMap<String, ProbeCredentials>>,
+    options: &State<HttpOptions>,
+    timings: &ScrapeTimings,
+) -> Result<MetricsResponse, MetricsError> {
+    let credentials = match credentials.get(api_key_ref) {
+        Some(credentials) => credentials,
+        None => {
+            return Err(MetricsError {
+                response: (
+                    Status::BadRequest,
+                    format!("Unknown api_key_ref {api_key_ref}"),
+                ),
+            })
+        }
+    };
+    if !credentials.allows(target) {
+        return Err(MetricsError {
+            response: (
+                Status::Forbidden,
+                format!("Target {target} is not allowed for api_key_ref {api_key_ref}"),
+            ),
+        });
+    }
+    let tasks = match get_probe_tasks(module, target, &credentials.api_key) {
+        Ok(tasks) => tasks,
+        Err(e) => {
+            return Err(MetricsError {
+                response: (Status::BadRequest, e.to_string()),
+            })
+        }
+    };
+    Ok(serve_metrics(
+        Format::Prometheus,
+        tasks,
+        &Mutex::new(MetricRegistry::new(&WebhookMetrics::default(), None)),
+        options.timeout(),
+        None,
+        Some(timings),
+        None,
+        None,
+        None,
+    )
+    .await)
+}
+
+#[get("/config")]
+fn effective_config(
+    _auth: Authenticated,
+    config: &State<RedactedConfig>,
+) -> Json<serde_json::Value> {
+    Json(config.0.clone())
+}
+
+// Same as SIGHUP, for platforms where sending signals is awkward.
+#[post("/-/reload")]
+async fn reload(
+    _auth: Authenticated,
+    tasks: &State<TaskList>,
+    source: &State<ConfigSource>,
+) -> (Status, String) {
+    info!("Reload requested, reloading {:?}", source.path);
+    match reload_tasks(tasks, source).await {
+        Ok(count) => {
+            info!("Configuration reloaded with {count} tasks");
+            (
+                Status::Ok,
+                format!("Configuration reloaded with {count} tasks"),
+            )
+        }
+        Err(e) => {
+            error!("Failed to reload configuration, keeping the current one: {e}");
+            (
+                Status::InternalServerError,
+                format!("Failed to reload configuration: {e}"),
+            )
+        }
+    }
+}
+
+#[get("/api/v1/sessions")]
+async fn api_sessions(
+    _auth: Authenticated,
+    tasks: &State<TaskList>,
+    options: &State<HttpOptions>,
+    status: &State<CollectionStatus>,
+) -> Result<Json<Vec<SessionResult>>, MetricsError> {
+    let unscheduled_tasks = tasks.get();
+    let tasks = unscheduled_tasks
+        .iter()
+        .filter(|task| matches!(task.kind(), "plex_session" | "jellyfin_session"))
+        .cloned()
+        .collect();
+    let results = process_api_tasks(tasks, options.timeout(), status).await?;
+    Ok(Json(
+        results
+            .into_iter()
+            .filter_map(|result| match result {
+                TaskResult::PlexSession(result) | TaskResult::JellyfinSession(result) => {
+                    Some(result)
                 }
-                Task::TautulliLibrary(tautulli) => {
-                    let result = tautulli.get_libraries().await;
-                    let result = TautulliLibraryResult { libraries: result };
-                    Ok(TaskResult::TautulliLibrary(result))
+                _ => None,
+            })
+            .collect(),
+    ))
+}
+
+#[get("/api/v1/sessions/stream")]
+fn api_sessions_stream(
+    _auth: Authenticated,
+    tasks: &State<TaskList>,
+    feed: &State<SessionFeed>,
+    mut shutdown: Shutdown,
+) -> EventStream![] {
+    let mut events = feed.subscribe(tasks);
+    let snapshot = feed.snapshot();
+    EventStream! {
+        for event in snapshot {
+            yield Event::json(&event).event(event.event.as_str());
+        }
+        loop {
+            let event = select! {
+                event = events.recv() => match event {
+                    Ok(event) => event,
+                    Err(RecvError::Closed) => break,
+                    Err(RecvError::Lagged(_)) => continue,
+                },
+                _ = &mut shutdown => break,
+            };
+            yield Event::json(&event).event(event.event.as_str());
+        }
+    }
+}
+
+#[get("/ws")]
+fn sessions_websocket(
+    _auth: Authenticated,
+    ws: WebSocket,
+    tasks: &State<TaskList>,
+    feed: &State<SessionFeed>,
+    mut shutdown: Shutdown,
+) -> Channel<'static> {
+    let mut events = feed.subscribe(tasks);
+    let snapshot = feed.snapshot();
+    ws.channel(move |mut stream| {
+        Box::pin(async move {
+            for event in snapshot {
+                stream
+                    .send(Message::Text(serde_json::to_string(&event).unwrap_or_default()))
+                    .await?;
+            }
+            loop {
+                select! {
+                    event = events.recv() => match event {
+                        Ok(event) => {
+                            stream
+                                .send(Message::Text(serde_json::to_string(&event).unwrap_or_default()))
+                                .await?;
+                        }
+                        Err(RecvError::Closed) => break,
+                        Err(RecvError::Lagged(_)) => continue,
+                    },
+                    message = stream.next() => match message {
+                        Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                        Some(Ok(_)) => continue,
+                    },
+                    _ = &mut shutdown => break,
                 }
-                Task::Radarr(radarr) => {
-                    let name = &radarr.name;
-                    let result = radarr.get_radarr_movies().await;
-                    let result = RadarrMovieResult {
-                        name: name.to_string(),
-                        movies: result,
-                    };
-                    Ok(TaskResult::Radarr(result))
+            }
+            Ok(())
+        })
+    })
+}
+
+#[get("/api/v1/query?<metric>&<provider>&<name>")]
+async fn api_query(
+    _auth: Authenticated,
+    metric: &str,
+    provider: Option<&str>,
+    name: Option<&str>,
+    tasks: &State<TaskList>,
+    options: &State<HttpOptions>,
+    status: &State<CollectionStatus>,
+) -> Result<Json<Vec<QueryTable>>, MetricsError> {
+    let unscheduled_tasks = tasks.get();
+    let selected = |task: &Task| match metric {
+        "sessions" => matches!(task.kind(), "plex_session" | "jellyfin_session"),
+        "libraries" => matches!(task.kind(), "plex_library" | "jellyfin_library"),
+        "requests" => matches!(task.kind(), "overseerr" | "jellyseerr"),
+        _ => false,
+    };
+    let mut table = match metric {
+        "sessions" => QueryTable::new(&[
+            ("provider", "string"),
+            ("name", "string"),
+            ("user", "string"),
+            ("title", "string"),
+            ("media_type", "string"),
+            ("state", "string"),
+            ("progress", "number"),
+            ("decision", "string"),
+            ("quality", "string"),
+            ("platform", "string"),
+            ("address", "string"),
+            ("local", "boolean"),
+        ]),
+        "libraries" => QueryTable::new(&[
+            ("provider", "string"),
+            ("name", "string"),
+            ("library", "string"),
+            ("media_type", "string"),
+            ("count", "number"),
+            ("child_count", "number"),
+            ("grand_child_count", "number"),
+        ]),
+        "requests" => QueryTable::new(&[
+            ("provider", "string"),
+            ("name", "string"),
+            ("media_type", "string"),
+            ("title", "string"),
+            ("requested_by", "string"),
+            ("status", "string"),
+            ("media_status", "string"),
+            ("requested_at", "time"),
+        ]),
+        _ => {
+            return Err(MetricsError {
+                response: (
+                    Status::BadRequest,
+                    format!("Unknown metric {metric}, expected sessions, libraries or requests"),
+                ),
+            })
+        }
+    };
+    let tasks = unscheduled_tasks
+        .iter()
+        .filter(|task| selected(task))
+        .filter(|task| {
+            let (task_provider, task_name, _) = task.provider();
+            provider.map_or(true, |provider| provider == task_provider)
+                && name.map_or(true, |name| name == task_name)
+        })
+        .cloned()
+        .collect();
+    for result in process_api_tasks(tasks, options.timeout(), status).await? {
+        match result {
+            TaskResult::PlexSession(result) | TaskResult::JellyfinSession(result) => {
+                for session in result.sessions {
+                    table.rows.push(vec![
+                        json!(result.kind),
+                        json!(result.name),
+                        json!(session.user),
+                        json!(session.title),
+                        json!(session.media_type),
+                        json!(session.state),
+                        json!(session.progress),
+                        json!(session.stream_decision.to_string()),
+                        json!(session.quality),
+                        json!(session.platform),
+                        json!(session.address),
+                        json!(session.local),
+                    ]);
                 }
-                Task::Overseerr(overseerr) => {
-                    let result = overseerr.get_overseerr_requests().await;
-                    let result = OverseerrRequestResult {
-                        kind: "overseerr".to_string(),
-                        requests: result,
-                    };
-                    Ok(TaskResult::Overseerr(result))
+            }
+            TaskResult::PlexLibrary(result) | TaskResult::JellyfinLibrary(result) => {
+                for library in result.libraries {
+                    table.rows.push(vec![
+                        json!(result.kind),
+                        json!(result.name),
+                        json!(library.name),
+                        json!(library.media_type.to_string()),
+                        json!(library.count),
+                        json!(library.child_count),
+                        json!(library.grand_child_count),
+                    ]);
                 }
-                Task::Jellyseerr(overseerr) => {
-                    let result = overseerr.get_overseerr_requests().await;
-                    let result = OverseerrRequestResult {
-                        kind: "jellyseerr".to_string(),
-                        requests: result,
-                    };
-                    Ok(TaskResult::Jellyseerr(result))
+            }
+            TaskResult::Overseerr(result) | TaskResult::Jellyseerr(result) => {
+                for request in result.requests {
+                    table.rows.push(vec![
+                        json!(result.kind),
+                        json!(result.name),
+                        json!(request.media_type),
+                        json!(request.media_title),
+                        json!(request.requested_by),
+                        json!(request.status.to_string()),
+                        json!(request.media_status.to_string()),
+                        json!(request.requested_at),
+                    ]);
                 }
-                Task::PlexSession(plex) => {
-                    let name = &plex.name;
-                    let result = plex.get_current_sessions().await;
-                    let users = plex.get_users().await;
-                    let result = SessionResult {
-                        name: name.to_string(),
-                        kind: "plex".to_string(),
-                        users,
-                        sessions: result,
-                    };
-                    Ok(TaskResult::PlexSession(result))
+            }
+            _ => {}
+        }
+    }
+    Ok(Json(vec![table]))
+}
+
+#[get("/api/v1/libraries")]
+async fn api_libraries(
+    _auth: Authenticated,
+    tasks: &State<TaskList>,
+    options: &State<HttpOptions>,
+    status: &State<CollectionStatus>,
+) -> Result<Json<Vec<LibraryResult>>, MetricsError> {
+    let unscheduled_tasks = tasks.get();
+    let tasks = unscheduled_tasks
+        .iter()
+        .filter(|task| matches!(task.kind(), "plex_library" | "jellyfin_library"))
+        .cloned()
+        .collect();
+    let results = process_api_tasks(tasks, options.timeout(), status).await?;
+    Ok(Json(
+        results
+            .into_iter()
+            .filter_map(|result| match result {
+                TaskResult::PlexLibrary(result) | TaskResult::JellyfinLibrary(result) => {
+                    Some(result)
                 }
-                Task::PlexLibrary(plex) => {
-                    let name = &plex.name;
-                    let result = plex.get_all_library_size().await;
-                    let result = LibraryResult {
-                        name: name.to_string(),
-                        kind: "plex".to_string(),
-                        libraries: result,
-                    };
-                    Ok(TaskResult::PlexLibrary(result))
+                _ => None,
+            })
+            .collect(),
+    ))
+}
+
+async fn process_api_tasks(
+    tasks: Vec<Task>,
+    timeout: Option<Duration>,
+    status: &CollectionStatus,
+) -> Result<Vec<TaskResult>, MetricsError> {
+    process_tasks(tasks, timeout, Some(status), None, None)
+        .await
+        .map_err(|e| {
+            error!("Error while processing tasks: {e}");
+            MetricsError {
+                response: (
+                    Status::InternalServerError,
+                    "Error while fetching provider data. Check the logs.".into(),
+                ),
+            }
+        })
+}
+
+// Secret a webhook sender passes in the `token` query parameter or the
+// X-Webhook-Token header.
+pub struct WebhookToken<'r>(Option<&'r str>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for WebhookToken<'r> {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let token = request
+            .headers()
+            .get_one("X-Webhook-Token")
+            .or_else(|| request.query_value::<&str>("token").and_then(Result::ok));
+        Outcome::Success(WebhookToken(token))
+    }
+}
+
+impl WebhookToken<'_> {
+    fn matches(&self, expected: &Option<String>) -> bool {
+        self.0.is_some() && self.0 == expected.as_deref()
+    }
+}
+
+#[post("/webhooks/plex", data = "<form>")]
+fn plex_webhook(
+    token: WebhookToken<'_>,
+    form: Form<PlexWebhookForm<'_>>,
+    config: &State<Webhooks>,
+    webhooks: &State<WebhookMetrics>,
+) -> Status {
+    if !token.matches(&config.plex_token) {
+        return Status::Forbidden;
+    }
+    match serde_json::from_str::<PlexWebhookPayload>(form.payload) {
+        Ok(payload) => {
+            webhooks.observe_plex(&payload);
+            Status::Ok
+        }
+        Err(e) => {
+            error!("Failed to parse Plex webhook payload: {e}");
+            Status::BadRequest
+        }
+    }
+}
+#[post("/webhooks/tautulli", data = "<payload>")]
+fn tautulli_webhook(
+    token: WebhookToken<'_>,
+    payload: &str,
+    config: &State<Webhooks>,
+    webhooks: &State<WebhookMetrics>,
+) -> Status {
+    if !token.matches(&config.tautulli_token) {
+        return Status::Forbidden;
+    }
+    match serde_json::from_str::<TautulliWebhookPayload>(payload) {
+        Ok(payload) => {
+            webhooks.observe_tautulli(&payload);
+            Status::Ok
+        }
+        Err(e) => {
+            error!("Failed to parse Tautulli webhook payload: {e}");
+            Status::BadRequest
+        }
+    }
+}
+
+#[post("/webhooks/jellyfin", data = "<payload>")]
+fn jellyfin_webhook(
+    token: WebhookToken<'_>,
+    payload: &str,
+    config: &State<Webhooks>,
+    webhooks: &State<WebhookMetrics>,
+) -> Status {
+    if !token.matches(&config.jellyfin_token) {
+        return Status::Forbidden;
+    }
+    match serde_json::from_str::<JellyfinWebhookPayload>(payload) {
+        Ok(payload) => {
+            webhooks.observe_jellyfin(&payload);
+            Status::Ok
+        }
+        Err(e) => {
+            error!("Failed to parse Jellyfin webhook payload: {e}");
+            Status::BadRequest
+        }
+    }
+}
+
+#[tracing::instrument(name = "scrape", skip_all, fields(tasks = tasks.len()))]
+pub(crate) async fn process_tasks(
+    tasks: Vec<Task>,
+    timeout: Option<Duration>,
+    status: Option<&CollectionStatus>,
+    timings: Option<&ScrapeTimings>,
+    cache: Option<&ResultCache>,
+) -> Result<Vec<TaskResult>, JoinError> {
+    let deadline = timeout.map(|timeout| tokio::time::Instant::now() + timeout);
+    let providers: Vec<(String, String, String)> = tasks
+        .iter()
+        .map(|task| {
+            let (provider, name, _) = task.provider();
+            (
+                provider.to_string(),
+                name.to_string(),
+                task.kind().to_string(),
+            )
+        })
+        .collect();
+    let task_futures: Vec<_> = tasks
+        .into_iter()
+        .map(|task| async move {
+            info!("Requesting data for {:?}", &task,);
+            Ok::<_, JoinError>(task.collect().await)
+        })
+        .collect();
+    let outcomes = std::sync::Mutex::new(Vec::new());
+    let task_futures = task_futures
+        .into_iter()
+        .zip(providers)
+        .map(|(future, provider)| {
+            // Logs emitted while collecting carry these fields in JSON logs.
+            let span = tracing::error_span!(
+                "collect",
+                provider = %provider.0,
+                instance = %provider.1,
+                task = %provider.2
+            );
+            let outcomes = &outcomes;
+            async move {
+                let start = Instant::now();
+                let future = track_failure(future);
+                let ((result, failure), timed_out) = match deadline {
+                    Some(deadline) => match tokio::time::timeout_at(deadline, future).await {
+                        Ok(result) => (result, false),
+                        Err(_) => {
+                            error!(
+                                "Timed out while requesting data for {} {}",
+                                provider.0, provider.1
+                            );
+                            ((Ok(Vec::new()), Some("timeout")), true)
+                        }
+                    },
+                    None => (future.await, false),
+                };
+                if let Some(timings) = timings {
+                    let duration = (!timed_out).then(|| start.elapsed());
+                    timings.record(format!("{}/{}", provider.0, provider.1), duration);
                 }
-                Task::JellyfinSession(jellyfin) => {
-                    let name = &jellyfin.name;
-                    let result = jellyfin.get_current_sessions().await;
-                    let users = jellyfin.get_users().await;
-                    let result = SessionResult {
-                        name: name.to_string(),
-                        kind: "jellyfin".to_string(),
-                        users,
-                        sessions: result,
-                    };
-                    Ok(TaskResult::JellyfinSession(result))
+                if let Some(status) = status {
+                    status.record(provider.clone(), timed_out);
                 }
-                Task::JellyfinLibrary(jellyfin) => {
-                    let name = &jellyfin.name;
-                    let result = jellyfin.get_library().await;
-                    let result = LibraryResult {
-                        name: name.to_string(),
-                        kind: "jellyfin".to_string(),
-                        libraries: result,
-                    };
-                    Ok(TaskResult::JellyfinLibrary(result))
+                let key = format!("{}/{}/{}", provider.0, provider.1, provider.2);
+                // The history result holds the new rows only, replaying it would count them again.
+                let cache = cache.filter(|_| provider.2 != "tautulli_history");
+                let (result, stale) = match (cache, failure, result) {
+                    (Some(cache), None, Ok(result)) => {
+                        cache.store(&key, &result);
+                        (Ok(result), false)
+                    }
+                    (Some(cache), Some(_), result) => match cache.stale(&key) {
+                        Some(cached) => {
+                            info!("Serving the cached result of {key}");
+                            (Ok(cached), true)
+                        }
+                        None => (result, false),
+                    },
+                    (_, _, result) => (result, false),
+                };
+                if let Ok(mut outcomes) = outcomes.lock() {
+                    outcomes.push(CollectionOutcome {
+                        provider: provider.0,
+                        name: provider.1,
+                        task: provider.2,
+                        failure,
+                        stale,
+                    });
                 }
-                Task::Default => Ok(TaskResult::Default),
+                result
             }
-        })
+            .instrument(span)
+        });
+    let mut results: Vec<TaskResult> = try_join_all(task_futures)
+        .await?
+        .into_iter()
+        .flatten()
         .collect();
-    try_join_all(task_futures).await
+    if let Some(cache) = cache {
+        cache.save().await;
+    }
+    let outcomes = outcomes.into_inner().unwrap_or_default();
+    results.push(TaskResult::Collection(CollectionResult { outcomes }));
+    Ok(results)
 }
 
-async fn serve_metrics(format: Format, unscheduled_tasks: &State<Vec<Task>>) -> MetricsResponse {
-    match process_tasks(unscheduled_tasks.inner().clone()).await {
-        Ok(task_results) => match format_metrics(task_results) {
-            Ok(metrics) => MetricsResponse::new(Status::Ok, format, metrics),
-            Err(e) => {
-                error!("Error formatting metrics: {e}");
-                MetricsResponse::new(
-                    Status::InternalServerError,
-                    format,
-                    "Error formatting metrics. Check the logs.".into(),
-                )
+// History snapshots are only taken on full scrapes.
+#[allow(clippy::too_many_arguments)]
+async fn serve_metrics(
+    format: Format,
+    unscheduled_tasks: Vec<Task>,
+    registry: &Mutex<MetricRegistry>,
+    timeout: Option<Duration>,
+    status: Option<&CollectionStatus>,
+    timings: Option<&ScrapeTimings>,
+    cache: Option<&ResultCache>,
+    history: Option<&HistoryStore>,
+    selected: Option<&[&str]>,
+) -> MetricsResponse {
+    match process_tasks(unscheduled_tasks, timeout, status, timings, cache).await {
+        Ok(mut task_results) => {
+            if let Some(history) = history {
+                let derived = history.record(&task_results).await;
+                task_results.push(TaskResult::History(derived));
             }
-        },
+            let mut registry = registry.lock().await;
+            let metrics = match selected {
+                Some(selected) => registry.format_selected(task_results, format, selected),
+                None => registry.format(task_results, format),
+            };
+            match metrics {
+                Ok(metrics) => MetricsResponse::new(Status::Ok, format, metrics),
+                Err(e) => {
+                    error!("Error formatting metrics: {e}");
+                    MetricsResponse::new(
+                        Status::InternalServerError,
+                        format,
+                        "Error formatting metrics. Check the logs.".into(),
+                    )
+                }
+            }
+        }
         Err(e) => {
             error!("Error while processing tasks: {e}");
             MetricsResponse::new(
@@ -200,6 +1530,15 @@ async fn serve_metrics(format: Format, unscheduled_tasks: &State<Vec<Task>>) ->
     }
 }
 
+// Runs every task once outside of Rocket, for `homers print`.
+pub async fn collect_once(config: Config, format: Format) -> anyhow::Result<String> {
+    let timeout = config.http_options.timeout();
+    let tasks = get_tasks(config)?;
+    version_check::check_versions(&tasks).await;
+    let task_results = process_tasks(tasks, timeout, None, None, None).await?;
+    format_metrics(task_results, &WebhookMetrics::default(), None, format)
+}
+
 const fn get_content_type_params(version: &str) -> [(&str, &str); 2] {
     [("charset", "utf-8"), ("version", version)]
 }
@@ -209,15 +1548,65 @@ fn get_openmetrics_content_type() -> ContentType {
         .with_params(get_content_type_params("1.0.0"))
 }
 
+fn get_format(accept: &Accept) -> Format {
+    let preferred = accept.preferred().media_type();
+    if preferred.top() == "application" && preferred.sub() == "openmetrics-text" {
+        Format::OpenMetrics
+    } else if preferred.is_json() {
+        Format::Json
+    } else {
+        Format::Prometheus
+    }
+}
+
 fn get_text_plain_content_type() -> ContentType {
     ContentType::new("text", "plain").with_params(get_content_type_params("0.0.4"))
 }
 
-pub fn exit_if_handle_fatal<E, R>(error: E) -> R
-where
-    E: std::fmt::Display,
-{
-    error!("Fatal error: {error}");
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(requests: u32, period: u64) -> RateLimiter {
+        RateLimiter::new(RateLimit { requests, period })
+    }
+
+    #[test]
+    fn rate_limiter_rejects_after_burst() {
+        let limiter = limiter(3, 60);
+        let client = IpAddr::from([10, 0, 0, 1]);
+        let now = Instant::now();
+        assert!(limiter.allow_at(client, now));
+        assert!(limiter.allow_at(client, now));
+        assert!(limiter.allow_at(client, now));
+        assert!(!limiter.allow_at(client, now));
+        // Other clients have their own bucket.
+        assert!(limiter.allow_at(IpAddr::from([10, 0, 0, 2]), now));
+    }
 
-    exit(1)
+    #[test]
+    fn rate_limiter_refills_over_period() {
+        let limiter = limiter(3, 60);
+        let client = IpAddr::from([10, 0, 0, 1]);
+        let now = Instant::now();
+        for _ in 0..3 {
+            assert!(limiter.allow_at(client, now));
+        }
+        assert!(!limiter.allow_at(client, now + Duration::from_secs(10)));
+        // One token every 20 seconds.
+        assert!(limiter.allow_at(client, now + Duration::from_secs(20)));
+        assert!(!limiter.allow_at(client, now + Duration::from_secs(21)));
+    }
+
+    #[test]
+    fn rate_limiter_drops_full_buckets() {
+        let limiter = limiter(3, 60);
+        let now = Instant::now();
+        assert!(limiter.allow_at(IpAddr::from([10, 0, 0, 1]), now));
+        assert!(limiter.allow_at(IpAddr::from([10, 0, 0, 2]), now + Duration::from_secs(60)));
+        assert_eq!(limiter.buckets.lock().unwrap().len(), 2);
+        // The first bucket was full again 20 seconds after its request.
+        assert!(limiter.allow_at(IpAddr::from([10, 0, 0, 2]), now + Duration::from_secs(80)));
+        assert_eq!(limiter.buckets.lock().unwrap().len(), 1);
+    }
 }
@@ -0,0 +1,63 @@
+use std::io::{BufRead, Write};
+use std::path::Path;
+
+const SAMPLE_CONFIG: &str = include_str!("../config.example.toml");
+
+// Table, prompt label and credential key of the providers offered by `--interactive`.
+const PROVIDERS: [(&str, &str, &str); 7] = [
+    ("sonarr.main", "Sonarr", "apikey"),
+    ("radarr.main", "Radarr", "apikey"),
+    ("tautulli", "Tautulli", "apikey"),
+    ("overseerr.main", "Overseerr", "apikey"),
+    ("jellyseerr.main", "Jellyseerr", "apikey"),
+    ("plex.main", "Plex", "token"),
+    ("jellyfin.main", "Jellyfin", "apikey"),
+];
+
+fn prompt(input: &mut impl BufRead, question: &str) -> anyhow::Result<String> {
+    print!("{question}: ");
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    input.read_line(&mut answer)?;
+    Ok(answer.trim().to_string())
+}
+
+fn prompt_providers() -> anyhow::Result<String> {
+    let mut input = std::io::stdin().lock();
+    let mut config = String::new();
+    for (table, label, key) in PROVIDERS {
+        let address = prompt(&mut input, &format!("{label} address (empty to skip)"))?;
+        if address.is_empty() {
+            continue;
+        }
+        let secret = prompt(&mut input, &format!("{label} {key}"))?;
+        // JSON strings are valid TOML basic strings.
+        config.push_str(&format!(
+            "\n[{table}]\naddress={}\n{key}={}\n",
+            serde_json::to_string(&address)?,
+            serde_json::to_string(&secret)?
+        ));
+    }
+    Ok(config)
+}
+
+/// Writes a commented sample configuration, optionally with the providers asked on stdin.
+pub fn run(path: &Path, force: bool, interactive: bool) -> anyhow::Result<()> {
+    if path.exists() && !force {
+        anyhow::bail!(
+            "{} already exists, use --force to overwrite it",
+            path.display()
+        );
+    }
+    let mut config = SAMPLE_CONFIG.to_string();
+    if interactive {
+        let providers = prompt_providers()?;
+        if !providers.is_empty() {
+            config.push_str("\n# Providers configured by homers init");
+            config.push_str(&providers);
+        }
+    }
+    std::fs::write(path, config)?;
+    println!("Wrote {}", path.display());
+    Ok(())
+}
@@ -0,0 +1,37 @@
+//! Collectors behind the homers exporter, usable without the HTTP server.
+//!
+//! ```no_run
+//! # async fn run() -> anyhow::Result<()> {
+//! use homers::prometheus::{format_metrics, Format, WebhookMetrics};
+//!
+//! let config = homers::config::read("config.toml".into(), log::Level::Info, false)?;
+//! let mut results = Vec::new();
+//! for task in homers::config::get_tasks(config)? {
+//!     results.extend(task.collect().await);
+//! }
+//! let metrics = format_metrics(results, &WebhookMetrics::default(), None, Format::Prometheus)?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! `http_server::collect_once` does the same with the scrape timeout and the
+//! collection metrics of `/metrics`.
+
+mod alerts;
+pub mod check;
+mod collector;
+pub mod config;
+mod graphite;
+mod history;
+pub mod http_server;
+pub mod init;
+pub mod logging;
+mod otlp;
+pub mod prometheus;
+pub mod providers;
+mod pushgateway;
+pub mod reporting;
+mod result_cache;
+mod session_feed;
+pub mod tasks;
+mod version_check;
@@ -0,0 +1,116 @@
+use clap::ValueEnum;
+use log::{info, Level};
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::trace::{Config, Sampler};
+use opentelemetry_sdk::Resource;
+use tracing_subscriber::filter::{EnvFilter, LevelFilter};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{Layer, Registry};
+
+use crate::config::{LogOptions, TracingOptions};
+
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+fn level_filter(level: Level) -> LevelFilter {
+    match level {
+        Level::Error => LevelFilter::ERROR,
+        Level::Warn => LevelFilter::WARN,
+        Level::Info => LevelFilter::INFO,
+        Level::Debug => LevelFilter::DEBUG,
+        Level::Trace => LevelFilter::TRACE,
+    }
+}
+
+// The global level from -v/-q, overridden for the modules listed in `[log] levels`.
+fn env_filter(level: Level, log: Option<&LogOptions>) -> anyhow::Result<EnvFilter> {
+    let mut filter = EnvFilter::default().add_directive(level_filter(level).into());
+    for (module, module_level) in log.map(|log| &log.levels).into_iter().flatten() {
+        let module_level: LevelFilter = module_level
+            .parse()
+            .map_err(|_| anyhow::anyhow!("invalid log level {module_level:?} for {module}"))?;
+        filter = filter.add_directive(format!("{module}={module_level}").parse()?);
+    }
+    Ok(filter)
+}
+
+type BoxedLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
+// Spans are exported whatever the log level, the scrape and provider spans are
+// all at INFO.
+fn otlp_layer(options: &TracingOptions) -> anyhow::Result<BoxedLayer> {
+    let service_name = options
+        .service_name
+        .clone()
+        .unwrap_or_else(|| "homers".to_string());
+    let provider = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(
+            opentelemetry_otlp::new_exporter()
+                .tonic()
+                .with_endpoint(&options.otlp_endpoint),
+        )
+        .with_trace_config(
+            Config::default()
+                .with_resource(Resource::new(vec![KeyValue::new(
+                    "service.name",
+                    service_name,
+                )]))
+                .with_sampler(Sampler::TraceIdRatioBased(
+                    options.sample_ratio.unwrap_or(1.0),
+                )),
+        )
+        .install_batch(opentelemetry_sdk::runtime::Tokio)?;
+    let tracer = provider.tracer("homers");
+    opentelemetry::global::set_tracer_provider(provider);
+    Ok(tracing_opentelemetry::layer()
+        .with_tracer(tracer)
+        .with_filter(LevelFilter::INFO)
+        .boxed())
+}
+
+// Records from the `log` macros are forwarded to tracing. JSON lines carry the
+// fields of the span they were emitted in, e.g. provider, instance and task
+// while collecting.
+pub fn init(
+    level: Level,
+    format: LogFormat,
+    log: Option<&LogOptions>,
+    tracing: Option<&TracingOptions>,
+) -> anyhow::Result<()> {
+    let filter = env_filter(level, log)?;
+    let fmt = tracing_subscriber::fmt::layer().with_writer(std::io::stderr);
+    let fmt: BoxedLayer = match format {
+        LogFormat::Text => fmt.with_filter(filter).boxed(),
+        LogFormat::Json => fmt
+            .json()
+            .with_current_span(true)
+            .with_span_list(false)
+            .with_filter(filter)
+            .boxed(),
+    };
+    let mut layers = vec![fmt];
+    if let Some(tracing) = tracing {
+        layers.push(otlp_layer(tracing)?);
+    }
+    tracing_subscriber::registry()
+        .with(layers)
+        .try_init()
+        .map_err(|e| anyhow::anyhow!("{e}"))?;
+    if let Some(tracing) = tracing {
+        info!("Exporting traces to {}", tracing.otlp_endpoint);
+    }
+    Ok(())
+}
+
+// Flushes the spans still buffered by the OTLP exporter.
+pub fn shutdown() {
+    opentelemetry::global::shutdown_tracer_provider();
+}
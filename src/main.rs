@@ -1,12 +1,10 @@
-use clap::{arg, command, Parser};
-use rocket::{launch, Build, Rocket};
+use anyhow::Context;
+use clap::{Parser, Subcommand};
+use futures::future::try_join_all;
 use std::path::PathBuf;
+use std::process::ExitCode;
 
-mod config;
-mod http_server;
-mod prometheus;
-mod providers;
-mod tasks;
+use homers::{check, config, http_server, init, logging, prometheus, reporting};
 
 #[cfg(debug_assertions)]
 #[derive(Copy, Clone, Debug, Default)]
@@ -32,30 +30,154 @@ struct Args {
     verbose: clap_verbosity_flag::Verbosity<DefaultLogLevel>,
     #[arg(short, long)]
     config: PathBuf,
+    /// Log as plain text or as one JSON object per line
+    #[arg(long, value_enum, default_value_t)]
+    log_format: logging::LogFormat,
+    /// Refuse to start when the configuration has unknown keys
+    #[arg(long)]
+    strict: bool,
+    /// Collect the metrics once, print them and exit, like `print`
+    #[arg(long)]
+    once: bool,
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
-#[launch]
-pub async fn start_server() -> Rocket<Build> {
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Validate the configuration and exit, non-zero when it has errors
+    Check {
+        /// Also connect to every provider with its credentials
+        #[arg(long)]
+        live: bool,
+    },
+    /// Write a commented sample configuration to the config path
+    Init {
+        /// Overwrite the file if it already exists
+        #[arg(long)]
+        force: bool,
+        /// Ask for the provider addresses and credentials
+        #[arg(long)]
+        interactive: bool,
+    },
+    /// Print the tasks built from the configuration
+    ListTasks,
+    /// Print the raw response of a provider endpoint
+    Debug {
+        /// sonarr, radarr, tautulli, overseerr, jellyseerr, plex or jellyfin
+        provider: String,
+        /// Instance name, e.g. `main` for [sonarr.main]
+        instance: String,
+        /// Path relative to the provider API, e.g. `calendar` for Sonarr or
+        /// `get_activity` for Tautulli; a status endpoint by default
+        endpoint: Option<String>,
+    },
+    /// Collect the metrics once, print them to stdout and exit
+    Print {
+        /// prometheus, openmetrics or json
+        #[arg(long, default_value = "prometheus")]
+        format: String,
+    },
+}
+
+#[rocket::main]
+async fn main() -> ExitCode {
     let args = Args::parse();
+    let result = run(args).await;
+    logging::shutdown();
+    match result {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(err) => {
+            eprintln!("{err:#}");
+            ExitCode::FAILURE
+        }
+    }
+}
 
+// Returns false when homers should exit with a failure without an error
+// message, e.g. when `check` already printed its report.
+async fn run(args: Args) -> anyhow::Result<bool> {
     let log_level = match args.verbose.log_level() {
         Some(level) => level,
         None => log::Level::Info,
     };
 
-    match simple_logger::init_with_level(log_level) {
-        Ok(_) => (),
-        Err(err) => {
-            eprintln!("Failed to initialize logger: {}", err);
-            std::process::exit(1);
+    // Only exported while serving or collecting, not for the other subcommands.
+    let (tracing, sentry) = match args.command {
+        None | Some(Command::Print { .. }) => {
+            config::read_section::<config::TracingOptions>(&args.config, "tracing")
+                .and_then(|tracing| Ok((tracing, config::read_section(&args.config, "sentry")?)))
+                .context("Failed to read config file")?
         }
+        _ => (None, None),
     };
-    let config = match config::read(args.config.clone(), log_level) {
-        Ok(config) => config,
-        Err(err) => {
-            eprintln!("Failed to read config file : {}", err);
-            std::process::exit(1);
+    // `check` reports an invalid configuration itself.
+    let log = match config::read_section::<config::LogOptions>(&args.config, "log") {
+        Ok(log) => log,
+        Err(_) if matches!(args.command, Some(Command::Check { .. })) => None,
+        Err(err) => return Err(err.context("Failed to read config file")),
+    };
+    let _sentry = sentry.as_ref().map(reporting::init);
+    logging::init(log_level, args.log_format, log.as_ref(), tracing.as_ref())
+        .context("Failed to initialize logger")?;
+    match args.command {
+        Some(Command::Check { live }) => {
+            return Ok(check::run(args.config, log_level, args.strict, live).await);
+        }
+        Some(Command::Init { force, interactive }) => {
+            init::run(&args.config, force, interactive).context("Failed to write config file")?;
+            return Ok(true);
         }
+        Some(Command::Print { .. })
+        | Some(Command::ListTasks)
+        | Some(Command::Debug { .. })
+        | None => {}
+    }
+    let config = config::read(args.config.clone(), log_level, args.strict)
+        .context("Failed to read config file")?;
+    if let Some(Command::ListTasks) = args.command {
+        check::list_tasks(config).context("Failed to create tasks")?;
+        return Ok(true);
+    }
+    if let Some(Command::Debug {
+        provider,
+        instance,
+        endpoint,
+    }) = &args.command
+    {
+        let payload = check::debug(config, provider, instance, endpoint.as_deref())
+            .await
+            .with_context(|| format!("Failed to fetch {provider} {instance}"))?;
+        println!("{payload}");
+        return Ok(true);
+    }
+    let print_format = match args.command {
+        Some(Command::Print { format }) => Some(format),
+        _ if args.once => Some("prometheus".to_string()),
+        _ => None,
+    };
+    if let Some(format) = print_format {
+        let format = prometheus::Format::from_name(&format).ok_or_else(|| {
+            anyhow::anyhow!("Unknown format {format}, expected openmetrics, prometheus or json")
+        })?;
+        let metrics = http_server::collect_once(config, format)
+            .await
+            .context("Failed to collect metrics")?;
+        print!("{metrics}");
+        return Ok(true);
+    }
+    let source = config::ConfigSource {
+        path: args.config,
+        log_level,
+        strict: args.strict,
     };
-    http_server::configure_rocket(config).await
+    let rockets = http_server::configure_rockets(config, source)
+        .await
+        .context("Failed to create tasks")?;
+    // Rocket logs the details of a launch error, and panics if it is dropped unseen.
+    if let Err(err) = try_join_all(rockets.into_iter().map(|rocket| rocket.launch())).await {
+        anyhow::bail!("{}", err.pretty_print());
+    }
+    Ok(true)
 }
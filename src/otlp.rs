@@ -0,0 +1,145 @@
+use log::{debug, info, warn};
+use opentelemetry::metrics::{AsyncInstrument, Meter, MeterProvider as _};
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::metrics::SdkMeterProvider;
+use opentelemetry_sdk::Resource;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use crate::collector::Collector;
+use crate::config::OtlpOptions;
+use crate::prometheus::JsonFamily;
+
+// Latest value of every series, by sample name.
+type Snapshot = Arc<RwLock<HashMap<String, Vec<(Vec<KeyValue>, f64)>>>>;
+
+/// Exports the metrics of every task over OTLP/gRPC, for setups built around
+/// an OpenTelemetry Collector. Each sample name of the Prometheus output is an
+/// observable instrument reporting the last collected values, with the labels
+/// as attributes.
+pub struct OtlpExporter {
+    endpoint: String,
+    interval: Duration,
+    // Dropping the provider stops the exports.
+    _provider: SdkMeterProvider,
+    meter: Meter,
+    instruments: HashSet<String>,
+    snapshot: Snapshot,
+    collector: Collector,
+}
+
+fn observe(snapshot: &Snapshot, name: &str, instrument: &dyn AsyncInstrument<f64>) {
+    let Ok(snapshot) = snapshot.read() else {
+        return;
+    };
+    for (attributes, value) in snapshot.get(name).into_iter().flatten() {
+        instrument.observe(*value, attributes);
+    }
+}
+
+impl OtlpExporter {
+    pub fn new(options: &OtlpOptions, collector: Collector) -> anyhow::Result<Self> {
+        let service_name = options
+            .service_name
+            .clone()
+            .unwrap_or_else(|| "homers".to_string());
+        let provider = opentelemetry_otlp::new_pipeline()
+            .metrics(opentelemetry_sdk::runtime::Tokio)
+            .with_exporter(
+                opentelemetry_otlp::new_exporter()
+                    .tonic()
+                    .with_endpoint(&options.endpoint),
+            )
+            .with_resource(Resource::new(vec![KeyValue::new(
+                "service.name",
+                service_name,
+            )]))
+            .with_period(options.interval())
+            .build()?;
+        Ok(OtlpExporter {
+            endpoint: options.endpoint.clone(),
+            interval: options.interval(),
+            meter: provider.meter("homers"),
+            _provider: provider,
+            instruments: HashSet::new(),
+            snapshot: Snapshot::default(),
+            collector,
+        })
+    }
+
+    /// Collects at startup, then every `interval`, the provider exports on
+    /// the same period.
+    pub fn spawn(mut self) {
+        info!(
+            "Exporting metrics to {} every {:?}",
+            self.endpoint, self.interval
+        );
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.interval);
+            loop {
+                interval.tick().await;
+                match self
+                    .collector
+                    .latest()
+                    .await
+                    .and_then(|latest| latest.families())
+                {
+                    Ok(families) => self.update(families),
+                    Err(e) => warn!("Failed to collect metrics for {}: {e}", self.endpoint),
+                }
+            }
+        });
+    }
+
+    // Counters are exported as monotonic sums, every other sample, histogram
+    // buckets included, as a gauge.
+    fn update(&mut self, families: Vec<JsonFamily>) {
+        let mut snapshot: HashMap<String, Vec<(Vec<KeyValue>, f64)>> = HashMap::new();
+        for family in families {
+            for sample in family.samples {
+                if !self.instruments.contains(&sample.name) {
+                    self.register(&sample.name, &family.metric_type, &family.help);
+                }
+                let attributes = sample
+                    .labels
+                    .into_iter()
+                    .map(|(label, value)| KeyValue::new(label, value))
+                    .collect();
+                snapshot
+                    .entry(sample.name)
+                    .or_default()
+                    .push((attributes, sample.value));
+            }
+        }
+        debug!("Collected {} metrics for OTLP", snapshot.len());
+        if let Ok(mut current) = self.snapshot.write() {
+            *current = snapshot;
+        }
+    }
+
+    fn register(&mut self, name: &str, metric_type: &str, help: &str) {
+        let snapshot = self.snapshot.clone();
+        let observed = name.to_string();
+        let callback =
+            move |instrument: &dyn AsyncInstrument<f64>| observe(&snapshot, &observed, instrument);
+        match metric_type {
+            "counter" => {
+                self.meter
+                    .f64_observable_counter(name.to_string())
+                    .with_description(help.to_string())
+                    .with_callback(callback)
+                    .init();
+            }
+            _ => {
+                self.meter
+                    .f64_observable_gauge(name.to_string())
+                    .with_description(help.to_string())
+                    .with_callback(callback)
+                    .init();
+            }
+        }
+        self.instruments.insert(name.to_string());
+    }
+}
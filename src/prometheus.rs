@@ -1,32 +1,410 @@
 use log::debug;
+use once_cell::sync::Lazy;
 use prometheus_client::encoding::text::encode;
 use prometheus_client::encoding::EncodeLabelSet;
-use prometheus_client::metrics::family::Family;
+use prometheus_client::metrics::counter::Counter;
+use prometheus_client::metrics::exemplar::CounterWithExemplar;
+use prometheus_client::metrics::family::{Family, MetricConstructor};
 use prometheus_client::metrics::gauge::Gauge;
-use prometheus_client::registry::Registry;
+use prometheus_client::metrics::histogram::{exponential_buckets, linear_buckets, Histogram};
+use prometheus_client::registry::{Metric, Registry};
+use serde::Serialize;
+use std::any::Any;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::hash::Hash;
 use std::sync::atomic::AtomicU64;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
+use crate::config::Webhooks;
+use crate::providers;
+use crate::providers::jellyfin::WebhookPayload as JellyfinWebhookPayload;
 use crate::providers::overseerr::OverseerrRequest;
+use crate::providers::plex::WebhookPayload as PlexWebhookPayload;
 use crate::providers::radarr::RadarrMovie;
 use crate::providers::sonarr::SonarrEpisode;
 use crate::providers::structs::{
-    BandwidthLocation, LibraryCount, MediaType as LibraryMediaType, Session,
+    BandwidthLocation, LibraryCount, MediaType as LibraryMediaType, Session, StreamDecision,
 };
 use crate::providers::tautulli::Library as TautulliLibrary;
 use crate::providers::tautulli::SessionSummary;
+use crate::providers::tautulli::WebhookPayload as TautulliWebhookPayload;
 use crate::tasks::{
-    LibraryResult, OverseerrRequestResult, RadarrMovieResult, SessionResult, SonarrEpisodeResult,
-    SonarrMissingResult, TaskResult, TautulliLibraryResult, TautulliSessionResult,
+    CollectionResult, HistoryResult, JellyfinDevicesResult, JellyfinInfoResult,
+    JellyfinPluginsResult, JellyfinRecentlyAddedResult, JellyfinStorageResult, LibraryResult,
+    OverseerrJobsResult, OverseerrRequestResult, PlexAccountResult, RadarrMovieResult,
+    SessionResult, SonarrEpisodeResult, SonarrMissingResult, TaskResult, TautulliHistoryResult,
+    TautulliHomeStatsResult, TautulliLibraryResult, TautulliNotificationsResult,
+    TautulliPlaysByDateResult, TautulliServerResult, TautulliSessionResult,
+    TautulliUserWatchTimeResult,
 };
 
 #[derive(PartialEq, Debug, Eq, Copy, Clone)]
 pub enum Format {
     Prometheus,
     OpenMetrics,
+    Json,
+}
+impl Format {
+    pub fn from_name(name: &str) -> Option<Format> {
+        match name {
+            "prometheus" => Some(Format::Prometheus),
+            "openmetrics" => Some(Format::OpenMetrics),
+            "json" => Some(Format::Json),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct JsonSample {
+    pub name: String,
+    pub labels: BTreeMap<String, String>,
+    pub value: f64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct JsonFamily {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub metric_type: String,
+    pub help: String,
+    pub samples: Vec<JsonSample>,
 }
 
 pub trait FormatAsPrometheus {
-    fn format_as_prometheus(&self, registry: &mut Registry);
+    fn format_as_prometheus(&self, registry: &mut MetricRegistry);
+}
+
+// Plex and Tautulli report bandwidth in kilobits per second.
+const BITS_PER_KILOBIT: f64 = 1000.0;
+
+type GaugeFamily<L> = Family<L, Gauge<f64, AtomicU64>>;
+type AnyFamily = dyn Any + Send + Sync;
+type ClearFn = fn(&AnyFamily);
+
+// The webhook, HTTP and provider request metrics are created with the first
+// registry, at startup.
+static STARTED_AT: Lazy<f64> = Lazy::new(now);
+
+// Seconds since the epoch, with milliseconds.
+fn now() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_millis() as f64 / 1000.0)
+        .unwrap_or_default()
+}
+
+/// Registry kept across scrapes. Families are registered the first time a
+/// task result asks for them, later scrapes clear and refill the same ones.
+/// The webhook and HTTP counters are registered once and never cleared.
+#[derive(Debug)]
+pub struct MetricRegistry {
+    registry: Registry,
+    // Each family with the function that clears it, none for counters.
+    families: HashMap<String, (Box<AnyFamily>, Option<ClearFn>)>,
+    // Creation time of the metrics that are never cleared, exported as the
+    // `_created` series of the counters in OpenMetrics.
+    created: HashMap<String, f64>,
+    // Sessions of the previous scrape by instance, with its time and the
+    // playback position of each session, to count the plays and watch time since.
+    sessions: HashMap<String, (Instant, HashMap<String, f64>)>,
+    // Families asked for by the task results of the current scrape.
+    touched: HashSet<String>,
+    // `webhooks` or `http`, for the metrics registered outside of `families`.
+    groups: HashMap<String, &'static str>,
+    // Id of the newest Tautulli history row counted, by instance.
+    history_cursors: HashMap<String, i64>,
+}
+
+// Sessions compared with the previous scrape of the same instance.
+struct SessionChanges<'a> {
+    started: Vec<&'a Session>,
+    // Playing and seen on the previous scrape, with the seconds played since:
+    // the position change, capped at the time between the scrapes.
+    watched: Vec<(&'a Session, f64)>,
+}
+
+// A session is the same play as long as the user, the media and the client do not change.
+fn session_key(session: &Session) -> String {
+    format!(
+        "{}|{}|{:?}|{:?}|{:?}|{}|{}",
+        session.user,
+        session.title,
+        session.episode_title,
+        session.season_number,
+        session.episode_number,
+        session.platform,
+        session.address
+    )
+}
+
+// Plex reports "playing", Jellyfin "Playing".
+fn is_playing(session: &Session) -> bool {
+    session.state.eq_ignore_ascii_case("playing")
+}
+
+fn clear_family<S, M, C>(family: &AnyFamily)
+where
+    S: Clone + Hash + Eq + 'static,
+    M: 'static,
+    C: MetricConstructor<M> + 'static,
+{
+    if let Some(family) = family.downcast_ref::<Family<S, M, C>>() {
+        family.clear();
+    }
+}
+impl MetricRegistry {
+    pub fn new(webhooks: &WebhookMetrics, http: Option<&HttpMetrics>) -> Self {
+        let mut registry = Self {
+            registry: Registry::with_prefix("homers"),
+            families: HashMap::new(),
+            created: HashMap::new(),
+            sessions: HashMap::new(),
+            touched: HashSet::new(),
+            groups: HashMap::new(),
+            history_cursors: HashMap::new(),
+        };
+        webhooks.format_as_prometheus(&mut registry);
+        registry.register(
+            None,
+            "provider_request_duration_seconds",
+            "Time taken by the providers to answer homers, by endpoint",
+            PROVIDER_REQUEST_DURATION.clone(),
+        );
+        if let Some(http) = http {
+            http.format_as_prometheus(&mut registry);
+        }
+        registry
+    }
+    // For metrics that live outside of the registry and keep their values.
+    fn register(
+        &mut self,
+        group: Option<&'static str>,
+        name: &str,
+        help: &str,
+        metric: impl Metric,
+    ) {
+        self.registry.register(name, help, metric);
+        self.created.insert(name.to_string(), *STARTED_AT);
+        if let Some(group) = group {
+            self.groups.insert(name.to_string(), group);
+        }
+    }
+    /// Family registered under `name`, created on the first call. A name is
+    /// always used with the same label set.
+    pub fn family<S, M>(&mut self, name: impl Into<String>, help: impl Into<String>) -> Family<S, M>
+    where
+        S: Clone + Hash + Eq + Send + Sync + 'static,
+        M: Default + Send + Sync + 'static,
+        Family<S, M>: Metric,
+    {
+        self.get_or_register(
+            name.into(),
+            help,
+            Some(clear_family::<S, M, fn() -> M>),
+            Family::default,
+        )
+    }
+    /// Histogram family cleared on every scrape like `family`, `new_histogram`
+    /// gives the buckets.
+    pub fn histogram_family<S>(
+        &mut self,
+        name: impl Into<String>,
+        help: impl Into<String>,
+        new_histogram: fn() -> Histogram,
+    ) -> Family<S, Histogram, fn() -> Histogram>
+    where
+        S: Clone + Hash + Eq + Send + Sync + 'static,
+        Family<S, Histogram, fn() -> Histogram>: Metric,
+    {
+        self.get_or_register(
+            name.into(),
+            help,
+            Some(clear_family::<S, Histogram, fn() -> Histogram>),
+            || Family::new_with_constructor(new_histogram),
+        )
+    }
+    /// Like `family`, but the counters keep their values across scrapes so
+    /// `rate()` and `increase()` work on them.
+    pub fn counter<S, M>(
+        &mut self,
+        name: impl Into<String>,
+        help: impl Into<String>,
+    ) -> Family<S, M>
+    where
+        S: Clone + Hash + Eq + Send + Sync + 'static,
+        M: Default + Send + Sync + 'static,
+        Family<S, M>: Metric,
+    {
+        self.get_or_register(name.into(), help, None, Family::default)
+    }
+    fn get_or_register<S, M, C>(
+        &mut self,
+        name: String,
+        help: impl Into<String>,
+        clear: Option<ClearFn>,
+        new_family: impl FnOnce() -> Family<S, M, C>,
+    ) -> Family<S, M, C>
+    where
+        S: Clone + Hash + Eq + Send + Sync + 'static,
+        M: Send + Sync + 'static,
+        C: Clone + Send + Sync + 'static,
+        Family<S, M, C>: Metric,
+    {
+        self.touched.insert(name.clone());
+        if let Some(family) = self
+            .families
+            .get(&name)
+            .and_then(|(family, _)| family.downcast_ref::<Family<S, M, C>>())
+        {
+            return family.clone();
+        }
+        let family = new_family();
+        self.registry.register(&name, help, family.clone());
+        if clear.is_none() {
+            self.created.insert(name.clone(), now());
+        }
+        self.families
+            .insert(name, (Box::new(family.clone()), clear));
+        family
+    }
+    fn observe_sessions<'a>(
+        &mut self,
+        instance: String,
+        sessions: &'a [Session],
+    ) -> SessionChanges<'a> {
+        let now = Instant::now();
+        let current = sessions
+            .iter()
+            .map(|session| (session_key(session), session.offset))
+            .collect();
+        let (previous_scrape, previous) = self
+            .sessions
+            .insert(instance, (now, current))
+            .unwrap_or_else(|| (now, HashMap::new()));
+        let elapsed = now.duration_since(previous_scrape).as_secs_f64();
+        let mut changes = SessionChanges {
+            started: Vec::new(),
+            watched: Vec::new(),
+        };
+        for session in sessions {
+            match previous.get(&session_key(session)) {
+                None => changes.started.push(session),
+                Some(offset) if is_playing(session) => {
+                    let played = (session.offset - offset).clamp(0.0, elapsed);
+                    changes.watched.push((session, played));
+                }
+                Some(_) => {}
+            }
+        }
+        changes
+    }
+    fn clear(&self) {
+        for (family, clear) in self.families.values() {
+            if let Some(clear) = clear {
+                clear(family.as_ref());
+            }
+        }
+    }
+    // Adds a `_created` sample after every sample of the counters that keep
+    // their values, the other counters come from the providers as they are.
+    fn with_created(&self, text: &str) -> String {
+        let mut output = String::with_capacity(text.len());
+        let mut counter = None;
+        for line in text.lines() {
+            output.push_str(line);
+            output.push('\n');
+            if let Some(family) = line
+                .strip_prefix("# TYPE ")
+                .and_then(|family| family.strip_suffix(" counter"))
+            {
+                counter = family
+                    .strip_prefix("homers_")
+                    .and_then(|name| self.created.get(name))
+                    .map(|created| (family, *created));
+                continue;
+            }
+            let Some((family, created)) = counter else {
+                continue;
+            };
+            let (sample, _) = split_exemplar(line);
+            let labels = sample
+                .rsplit_once(' ')
+                .and_then(|(series, _)| series.strip_prefix(family))
+                .and_then(|series| series.strip_prefix("_total"));
+            if let Some(labels) = labels {
+                output.push_str(&format!("{family}_created{labels} {created}\n"));
+            }
+        }
+        output
+    }
+    /// Replaces the series of the previous scrape with `task_results` and encodes the registry.
+    pub fn format(
+        &mut self,
+        task_results: Vec<TaskResult>,
+        format: Format,
+    ) -> anyhow::Result<String> {
+        let buffer = self.encode(task_results)?;
+        self.render(&buffer, format)
+    }
+    /// Like `format` for a scrape limited with `collect`: only the families
+    /// `task_results` asked for are kept, with the webhook and HTTP metrics
+    /// when `webhooks` and `http` are selected.
+    pub fn format_selected(
+        &mut self,
+        task_results: Vec<TaskResult>,
+        format: Format,
+        selected: &[&str],
+    ) -> anyhow::Result<String> {
+        let buffer = self.encode(task_results)?;
+        let buffer = retain_families(&buffer, |name| {
+            self.touched.contains(name)
+                || match self.groups.get(name) {
+                    Some(group) => selected.contains(group),
+                    None => !self.families.contains_key(name),
+                }
+        });
+        self.render(&buffer, format)
+    }
+    fn render(&self, buffer: &str, format: Format) -> anyhow::Result<String> {
+        match format {
+            Format::OpenMetrics => Ok(self.with_created(buffer)),
+            Format::Prometheus => Ok(to_prometheus_text(buffer)),
+            Format::Json => Ok(serde_json::to_string(&parse_exposition(buffer)?)?),
+        }
+    }
+    /// Like `format`, but keeps the encoded registry for the exporters to
+    /// read it in their own format.
+    pub fn exposition(&mut self, task_results: Vec<TaskResult>) -> anyhow::Result<Exposition> {
+        Ok(Exposition(self.encode(task_results)?))
+    }
+    fn encode(&mut self, task_results: Vec<TaskResult>) -> anyhow::Result<String> {
+        self.clear();
+        self.touched.clear();
+        for task_result in task_results {
+            task_result.format_as_prometheus(self);
+        }
+        format_skipped_items(self);
+        let mut buffer = String::new();
+        encode(&mut buffer, &self.registry)?;
+        Ok(buffer)
+    }
+}
+
+/// Registry encoded once for a collection, shared by the exporters.
+#[derive(Debug)]
+pub struct Exposition(String);
+
+impl Exposition {
+    /// Prometheus text format, as pushed to a Pushgateway.
+    pub fn prometheus(&self) -> String {
+        to_prometheus_text(&self.0)
+    }
+    /// Metric families for the exporters that are not Prometheus based.
+    pub fn families(&self) -> anyhow::Result<Vec<JsonFamily>> {
+        parse_exposition(&self.0)
+    }
 }
 
 #[derive(Clone, Hash, Eq, PartialEq, EncodeLabelSet, Debug)]
@@ -48,14 +426,47 @@ struct SessionLabels {
     pub address: String,
     pub media_type: String,
     pub public_address: String,
+    pub hostname: Option<String>,
     pub season_number: Option<String>,
     pub episode_number: Option<String>,
+    pub show: Option<String>,
+    pub season_title: Option<String>,
+    pub episode_title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
     pub quality: String,
     pub city: String,
     pub longitude: String,
     pub latitude: String,
 }
 #[derive(Clone, Hash, Eq, PartialEq, EncodeLabelSet, Debug)]
+struct PlayLabels {
+    pub name: String,
+    pub user: String,
+    pub media_type: String,
+}
+#[derive(Clone, Hash, Eq, PartialEq, EncodeLabelSet, Debug)]
+struct PlayExemplar {
+    pub session_id: String,
+}
+#[derive(Clone, Hash, Eq, PartialEq, EncodeLabelSet, Debug)]
+struct ProgressLabels {
+    pub name: String,
+}
+#[derive(Clone, Hash, Eq, PartialEq, EncodeLabelSet, Debug)]
+struct WatchLabels {
+    pub provider: String,
+    pub name: String,
+    pub user: String,
+    pub media_type: String,
+    pub library: String,
+}
+#[derive(Clone, Hash, Eq, PartialEq, EncodeLabelSet, Debug)]
+struct PlayMethodLabels {
+    pub name: String,
+    pub method: String,
+}
+#[derive(Clone, Hash, Eq, PartialEq, EncodeLabelSet, Debug)]
 struct PlexShowLabels {
     pub name: String,
     pub library_name: String,
@@ -86,6 +497,7 @@ struct PlexCount {
 
 #[derive(Clone, Hash, Eq, PartialEq, EncodeLabelSet, Debug)]
 struct TautulliSessionPercentageLabels {
+    pub name: String,
     pub user: String,
     pub title: String,
     pub state: String,
@@ -98,9 +510,12 @@ struct TautulliSessionPercentageLabels {
     pub city: String,
 }
 #[derive(Clone, Hash, Eq, PartialEq, EncodeLabelSet, Debug)]
-struct EmptyLabel {}
+struct InstanceLabels {
+    pub name: String,
+}
 #[derive(Clone, Hash, Eq, PartialEq, EncodeLabelSet, Debug)]
 struct TautulliSessionLabels {
+    pub name: String,
     pub user: String,
     pub title: String,
     pub state: String,
@@ -116,6 +531,7 @@ struct TautulliSessionLabels {
 }
 #[derive(Clone, Hash, Eq, PartialEq, EncodeLabelSet, Debug)]
 struct TautulliLibraryLabels {
+    pub name: String,
     pub section_name: String,
     pub section_type: String,
     pub count: String,
@@ -123,6 +539,57 @@ struct TautulliLibraryLabels {
     pub child_count: Option<String>,
 }
 #[derive(Clone, Hash, Eq, PartialEq, EncodeLabelSet, Debug)]
+struct TautulliLibrarySizeLabels {
+    pub name: String,
+    pub section_name: String,
+    pub section_type: String,
+}
+#[derive(Clone, Hash, Eq, PartialEq, EncodeLabelSet, Debug)]
+struct TautulliHomeStatLabels {
+    pub name: String,
+    pub stat: String,
+    pub label: String,
+}
+#[derive(Clone, Hash, Eq, PartialEq, EncodeLabelSet, Debug)]
+struct TautulliHistoryLabels {
+    pub name: String,
+    pub user: String,
+    pub media_type: String,
+}
+#[derive(Clone, Hash, Eq, PartialEq, EncodeLabelSet, Debug)]
+struct TautulliWatchTimeLabels {
+    pub name: String,
+    pub user: String,
+    pub days: i64,
+}
+#[derive(Clone, Hash, Eq, PartialEq, EncodeLabelSet, Debug)]
+struct TautulliInfoLabels {
+    pub name: String,
+    pub version: String,
+    pub branch: String,
+}
+#[derive(Clone, Hash, Eq, PartialEq, EncodeLabelSet, Debug)]
+struct TautulliNotificationLabels {
+    pub name: String,
+    pub agent: String,
+}
+#[derive(Clone, Hash, Eq, PartialEq, EncodeLabelSet, Debug)]
+struct TautulliDailyPlaysLabels {
+    pub name: String,
+    pub date: String,
+    pub media_type: String,
+}
+#[derive(Clone, Hash, Eq, PartialEq, EncodeLabelSet, Debug)]
+struct TautulliBandwidthLabels {
+    pub name: String,
+    pub location: String,
+}
+#[derive(Clone, Hash, Eq, PartialEq, EncodeLabelSet, Debug)]
+struct TautulliStreamCountLabels {
+    pub name: String,
+    pub decision: String,
+}
+#[derive(Clone, Hash, Eq, PartialEq, EncodeLabelSet, Debug)]
 struct RadarrLabels {
     pub name: String,
     pub title: String,
@@ -132,6 +599,7 @@ struct RadarrLabels {
 }
 #[derive(Clone, Hash, Eq, PartialEq, EncodeLabelSet, Debug)]
 struct OverseerrLabels {
+    pub name: String,
     pub media_type: String,
     pub requested_by: String,
     pub request_status: String,
@@ -141,28 +609,665 @@ struct OverseerrLabels {
 }
 
 #[derive(Clone, Hash, Eq, PartialEq, EncodeLabelSet, Debug)]
-struct OverseerrRequestsLabels {
-    kind: String,
+struct OverseerrAggregateLabels {
+    pub name: String,
+    pub media_type: String,
+    pub requested_by: String,
+    pub request_status: String,
+    pub media_status: String,
+}
+
+#[derive(Clone, Hash, Eq, PartialEq, EncodeLabelSet, Debug)]
+struct OverseerrStatusCountLabels {
+    pub name: String,
+    pub status: String,
+}
+
+#[derive(Clone, Hash, Eq, PartialEq, EncodeLabelSet, Debug)]
+struct OverseerrMediaTypeCountLabels {
+    pub name: String,
+    pub media_type: String,
+}
+
+#[derive(Clone, Hash, Eq, PartialEq, EncodeLabelSet, Debug)]
+struct OverseerrJobLabels {
+    pub name: String,
+    pub job: String,
+    pub job_name: String,
+    pub job_type: String,
+}
+
+#[derive(Clone, Hash, Eq, PartialEq, EncodeLabelSet, Debug)]
+struct JellyfinClientLabels {
+    pub name: String,
+    pub client: String,
+    pub platform: String,
+}
+
+#[derive(Clone, Hash, Eq, PartialEq, EncodeLabelSet, Debug)]
+struct JellyfinPluginLabels {
+    pub name: String,
+    pub plugin: String,
+    pub version: String,
+    pub status: String,
+}
+#[derive(Clone, Hash, Eq, PartialEq, EncodeLabelSet, Debug)]
+struct JellyfinPluginUpdateLabels {
+    pub name: String,
+    pub plugin: String,
+}
+
+#[derive(Clone, Hash, Eq, PartialEq, EncodeLabelSet, Debug)]
+struct JellyfinInfoLabels {
+    pub name: String,
+    pub version: String,
+    pub product: String,
+    pub operating_system: String,
+}
+
+#[derive(Clone, Hash, Eq, PartialEq, EncodeLabelSet, Debug)]
+struct MediaTypeLabels {
+    pub name: String,
+    pub media_type: String,
+}
+
+#[derive(Clone, Hash, Eq, PartialEq, EncodeLabelSet, Debug)]
+struct StoragePathLabels {
+    pub name: String,
+    pub library_name: String,
+    pub path: String,
+}
+
+#[derive(Clone, Hash, Eq, PartialEq, EncodeLabelSet, Debug)]
+struct PlexWebhookLabels {
+    pub user: String,
+    pub library: String,
+}
+
+#[derive(Clone, Hash, Eq, PartialEq, EncodeLabelSet, Debug)]
+struct WebhookEventLabels {
+    pub event: String,
+}
+#[derive(Clone, Hash, Eq, PartialEq, EncodeLabelSet, Debug)]
+struct WebhookPlayLabels {
+    pub user: String,
+    pub media_type: String,
+}
+
+// Event names and media types kept as label values, anything else is
+// reported as `other` so payloads cannot create new series.
+const PLEX_WEBHOOK_EVENTS: [&str; 12] = [
+    "media.play",
+    "media.pause",
+    "media.resume",
+    "media.stop",
+    "media.scrobble",
+    "media.rate",
+    "library.on.deck",
+    "library.new",
+    "admin.database.backup",
+    "admin.database.corrupted",
+    "device.new",
+    "playback.started",
+];
+const TAUTULLI_WEBHOOK_ACTIONS: [&str; 19] = [
+    "play",
+    "stop",
+    "pause",
+    "resume",
+    "change",
+    "buffer",
+    "error",
+    "watched",
+    "created",
+    "intdown",
+    "intup",
+    "extdown",
+    "extup",
+    "pmsupdate",
+    "concurrent",
+    "newdevice",
+    "plexpyupdate",
+    "plexpydbcorrupt",
+    "tokenexpired",
+];
+const TAUTULLI_WEBHOOK_MEDIA_TYPES: [&str; 6] =
+    ["movie", "episode", "track", "photo", "clip", "live"];
+const JELLYFIN_WEBHOOK_NOTIFICATIONS: [&str; 22] = [
+    "ItemAdded",
+    "ItemDeleted",
+    "PlaybackStart",
+    "PlaybackProgress",
+    "PlaybackStop",
+    "SubtitleDownloadFailure",
+    "AuthenticationFailure",
+    "AuthenticationSuccess",
+    "SessionStart",
+    "PendingRestart",
+    "TaskCompleted",
+    "UserCreated",
+    "UserDeleted",
+    "UserLockedOut",
+    "UserPasswordChanged",
+    "UserDataSaved",
+    "PluginInstalled",
+    "PluginInstallationCancelled",
+    "PluginInstallationFailed",
+    "PluginUninstalled",
+    "PluginUpdated",
+    "Generic",
+];
+const JELLYFIN_WEBHOOK_ITEM_TYPES: [&str; 10] = [
+    "Movie",
+    "Episode",
+    "Audio",
+    "AudioBook",
+    "MusicVideo",
+    "Video",
+    "TvChannel",
+    "Book",
+    "Photo",
+    "Trailer",
+];
+// Users past this many are counted as `other`.
+const WEBHOOK_USER_LIMIT: usize = 100;
+
+fn allowed_label(value: &str, allowed: &[&str]) -> String {
+    match allowed.contains(&value) {
+        true => value.to_string(),
+        false => "other".to_string(),
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct WebhookMetrics {
+    enabled: Webhooks,
+    users: Arc<Mutex<HashSet<String>>>,
+    plex_events: Family<WebhookEventLabels, Counter>,
+    plex_plays: Family<PlexWebhookLabels, Counter>,
+    plex_scrobbles: Family<PlexWebhookLabels, Counter>,
+    tautulli_events: Family<WebhookEventLabels, Counter>,
+    tautulli_plays: Family<WebhookPlayLabels, Counter>,
+    tautulli_transcodes: Family<WebhookPlayLabels, Counter>,
+    tautulli_errors: Family<WebhookPlayLabels, Counter>,
+    jellyfin_events: Family<WebhookEventLabels, Counter>,
+    jellyfin_plays: Family<WebhookPlayLabels, Counter>,
+    jellyfin_transcodes: Family<WebhookPlayLabels, Counter>,
+}
+impl WebhookMetrics {
+    /// Counters of the webhook listeners enabled in `webhooks`, the default
+    /// has none.
+    pub fn new(webhooks: &Webhooks) -> Self {
+        Self {
+            enabled: webhooks.clone(),
+            ..Default::default()
+        }
+    }
+    // Users seen first keep their name, later ones are `other`.
+    fn user_label(&self, user: &str) -> String {
+        let mut users = self.users.lock().unwrap_or_else(|e| e.into_inner());
+        if users.contains(user) || users.len() < WEBHOOK_USER_LIMIT {
+            users.insert(user.to_string());
+            return user.to_string();
+        }
+        "other".to_string()
+    }
+    pub fn observe_plex(&self, payload: &PlexWebhookPayload) {
+        debug!("Received Plex webhook event {}", payload.event);
+        self.plex_events
+            .get_or_create(&WebhookEventLabels {
+                event: allowed_label(&payload.event, &PLEX_WEBHOOK_EVENTS),
+            })
+            .inc();
+        let labels = PlexWebhookLabels {
+            user: self.user_label(
+                payload
+                    .account
+                    .as_ref()
+                    .map_or("", |account| account.title.as_str()),
+            ),
+            library: payload
+                .metadata
+                .as_ref()
+                .and_then(|metadata| metadata.library_section_title.clone())
+                .unwrap_or_default(),
+        };
+        match payload.event.as_str() {
+            "media.play" => {
+                self.plex_plays.get_or_create(&labels).inc();
+            }
+            "media.scrobble" => {
+                self.plex_scrobbles.get_or_create(&labels).inc();
+            }
+            _ => {}
+        }
+    }
+    pub fn observe_tautulli(&self, payload: &TautulliWebhookPayload) {
+        debug!("Received Tautulli webhook action {}", payload.action);
+        self.tautulli_events
+            .get_or_create(&WebhookEventLabels {
+                event: allowed_label(&payload.action, &TAUTULLI_WEBHOOK_ACTIONS),
+            })
+            .inc();
+        let labels = WebhookPlayLabels {
+            user: self.user_label(&payload.user),
+            media_type: allowed_label(&payload.media_type, &TAUTULLI_WEBHOOK_MEDIA_TYPES),
+        };
+        match payload.action.as_str() {
+            "play" => {
+                self.tautulli_plays.get_or_create(&labels).inc();
+                if payload.transcode_decision.eq_ignore_ascii_case("transcode") {
+                    self.tautulli_transcodes.get_or_create(&labels).inc();
+                }
+            }
+            "error" => {
+                self.tautulli_errors.get_or_create(&labels).inc();
+            }
+            _ => {}
+        }
+    }
+    pub fn observe_jellyfin(&self, payload: &JellyfinWebhookPayload) {
+        debug!(
+            "Received Jellyfin webhook notification {}",
+            payload.notification_type
+        );
+        self.jellyfin_events
+            .get_or_create(&WebhookEventLabels {
+                event: allowed_label(&payload.notification_type, &JELLYFIN_WEBHOOK_NOTIFICATIONS),
+            })
+            .inc();
+        if payload.notification_type == "PlaybackStart" {
+            let labels = WebhookPlayLabels {
+                user: self.user_label(&payload.notification_username),
+                media_type: allowed_label(&payload.item_type, &JELLYFIN_WEBHOOK_ITEM_TYPES),
+            };
+            self.jellyfin_plays.get_or_create(&labels).inc();
+            if payload.play_method.eq_ignore_ascii_case("transcode") {
+                self.jellyfin_transcodes.get_or_create(&labels).inc();
+            }
+        }
+    }
+}
+impl FormatAsPrometheus for WebhookMetrics {
+    fn format_as_prometheus(&self, registry: &mut MetricRegistry) {
+        if self.enabled.plex {
+            registry.register(
+                Some("webhooks"),
+                "plex_webhook_events",
+                "Plex webhook events received, by event",
+                self.plex_events.clone(),
+            );
+            registry.register(
+                Some("webhooks"),
+                "plex_plays",
+                "Plex plays received through webhooks",
+                self.plex_plays.clone(),
+            );
+            registry.register(
+                Some("webhooks"),
+                "plex_scrobbles",
+                "Plex scrobbles received through webhooks",
+                self.plex_scrobbles.clone(),
+            );
+        }
+        if self.enabled.tautulli {
+            registry.register(
+                Some("webhooks"),
+                "tautulli_webhook_events",
+                "Tautulli webhook notifications received, by action",
+                self.tautulli_events.clone(),
+            );
+            registry.register(
+                Some("webhooks"),
+                "tautulli_webhook_plays",
+                "Plays started received through Tautulli webhooks",
+                self.tautulli_plays.clone(),
+            );
+            registry.register(
+                Some("webhooks"),
+                "tautulli_webhook_transcodes",
+                "Transcoded plays started received through Tautulli webhooks",
+                self.tautulli_transcodes.clone(),
+            );
+            registry.register(
+                Some("webhooks"),
+                "tautulli_webhook_playback_errors",
+                "Playback errors received through Tautulli webhooks",
+                self.tautulli_errors.clone(),
+            );
+        }
+        if self.enabled.jellyfin {
+            registry.register(
+                Some("webhooks"),
+                "jellyfin_webhook_events",
+                "Jellyfin webhook notifications received, by notification type",
+                self.jellyfin_events.clone(),
+            );
+            registry.register(
+                Some("webhooks"),
+                "jellyfin_webhook_plays",
+                "Plays started received through Jellyfin webhooks",
+                self.jellyfin_plays.clone(),
+            );
+            registry.register(
+                Some("webhooks"),
+                "jellyfin_webhook_transcodes",
+                "Transcoded plays started received through Jellyfin webhooks",
+                self.jellyfin_transcodes.clone(),
+            );
+        }
+    }
+}
+
+#[derive(Clone, Hash, Eq, PartialEq, EncodeLabelSet, Debug)]
+struct HttpRequestLabels {
+    pub method: String,
+    pub route: String,
+    pub status: u16,
+}
+
+#[derive(Clone, Hash, Eq, PartialEq, EncodeLabelSet, Debug)]
+struct HttpRouteLabels {
+    pub method: String,
+    pub route: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpMetrics {
+    requests: Family<HttpRequestLabels, Counter>,
+    in_flight: Gauge,
+    duration: Family<HttpRouteLabels, Histogram, fn() -> Histogram>,
+}
+impl Default for HttpMetrics {
+    fn default() -> Self {
+        Self {
+            requests: Family::default(),
+            in_flight: Gauge::default(),
+            duration: Family::new_with_constructor(|| {
+                Histogram::new(exponential_buckets(0.005, 2.0, 12))
+            }),
+        }
+    }
+}
+impl HttpMetrics {
+    pub fn start_request(&self) {
+        self.in_flight.inc();
+    }
+    pub fn end_request(&self, method: &str, route: &str, status: u16, seconds: f64) {
+        self.in_flight.dec();
+        self.requests
+            .get_or_create(&HttpRequestLabels {
+                method: method.to_string(),
+                route: route.to_string(),
+                status,
+            })
+            .inc();
+        self.duration
+            .get_or_create(&HttpRouteLabels {
+                method: method.to_string(),
+                route: route.to_string(),
+            })
+            .observe(seconds);
+    }
+}
+impl FormatAsPrometheus for HttpMetrics {
+    fn format_as_prometheus(&self, registry: &mut MetricRegistry) {
+        registry.register(
+            Some("http"),
+            "http_requests",
+            "HTTP requests served by homers",
+            self.requests.clone(),
+        );
+        registry.register(
+            Some("http"),
+            "http_requests_in_flight",
+            "HTTP requests currently being served by homers",
+            self.in_flight.clone(),
+        );
+        registry.register(
+            Some("http"),
+            "http_request_duration_seconds",
+            "Time taken by homers to answer HTTP requests",
+            self.duration.clone(),
+        );
+    }
+}
+
+#[derive(Clone, Hash, Eq, PartialEq, EncodeLabelSet, Debug)]
+struct ProviderRequestLabels {
+    pub provider: String,
+    pub endpoint: String,
 }
 
-pub fn format_metrics(task_result: Vec<TaskResult>) -> anyhow::Result<String> {
-    let mut buffer = String::new();
-    let mut registry = Registry::with_prefix("homers");
-    for task_result in task_result {
-        task_result.format_as_prometheus(&mut registry);
+// Set from `provider_latency_buckets` before the first provider request.
+static PROVIDER_LATENCY_BUCKETS: OnceLock<Vec<f64>> = OnceLock::new();
+type ProviderRequestFamily = Family<ProviderRequestLabels, Histogram, fn() -> Histogram>;
+static PROVIDER_REQUEST_DURATION: Lazy<ProviderRequestFamily> =
+    Lazy::new(|| Family::new_with_constructor(provider_latency_histogram));
+
+fn provider_latency_histogram() -> Histogram {
+    match PROVIDER_LATENCY_BUCKETS.get() {
+        Some(buckets) => Histogram::new(buckets.iter().copied()),
+        None => Histogram::new(exponential_buckets(0.01, 2.0, 12)),
+    }
+}
+
+/// Buckets of `homers_provider_request_duration_seconds`, only the first call counts.
+pub fn set_provider_latency_buckets(mut buckets: Vec<f64>) {
+    buckets.sort_by(f64::total_cmp);
+    buckets.dedup();
+    let _ = PROVIDER_LATENCY_BUCKETS.set(buckets);
+}
+
+pub fn observe_provider_request(provider: &str, endpoint: String, seconds: f64) {
+    PROVIDER_REQUEST_DURATION
+        .get_or_create(&ProviderRequestLabels {
+            provider: provider.to_string(),
+            endpoint,
+        })
+        .observe(seconds);
+}
+
+#[derive(Clone, Hash, Eq, PartialEq, EncodeLabelSet, Debug)]
+struct SkippedItemLabels {
+    pub item: String,
+}
+
+fn format_skipped_items(registry: &mut MetricRegistry) {
+    let skipped_items: Family<SkippedItemLabels, Counter> = registry.family(
+        "skipped_items",
+        "Provider list items that failed to parse and were left out",
+    );
+    for (item, count) in providers::skipped_items() {
+        skipped_items
+            .get_or_create(&SkippedItemLabels {
+                item: item.to_string(),
+            })
+            .inc_by(count);
     }
-    encode(&mut buffer, &registry)?;
-    Ok(buffer)
+}
+
+// One-off registry, for `print` and /probe.
+pub fn format_metrics(
+    task_result: Vec<TaskResult>,
+    webhooks: &WebhookMetrics,
+    http: Option<&HttpMetrics>,
+    format: Format,
+) -> anyhow::Result<String> {
+    MetricRegistry::new(webhooks, http).format(task_result, format)
+}
+
+// Keeps the families of an exposition whose name, without the prefix, passes `keep`.
+fn retain_families(text: &str, keep: impl Fn(&str) -> bool) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut kept = true;
+    for line in text.lines() {
+        if let Some(descriptor) = line.strip_prefix("# HELP ") {
+            kept = descriptor
+                .split(' ')
+                .next()
+                .and_then(|name| name.strip_prefix("homers_"))
+                .is_some_and(&keep);
+        } else if line == "# EOF" {
+            kept = true;
+        }
+        if kept {
+            output.push_str(line);
+            output.push('\n');
+        }
+    }
+    output
+}
+
+// Splits a sample line from its exemplar, the first ` # ` outside of a label value.
+fn split_exemplar(line: &str) -> (&str, Option<&str>) {
+    let mut quoted = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => quoted = !quoted,
+            ' ' if !quoted && line[i..].starts_with(" # ") => {
+                return (&line[..i], Some(&line[i + 3..]));
+            }
+            _ => {}
+        }
+    }
+    (line, None)
+}
+
+// Rewrites the OpenMetrics text in the Prometheus text format 0.0.4, which
+// names counter families after their `_total` sample, calls `unknown`
+// `untyped` and has no units, no exemplars and no `# EOF` terminator.
+fn to_prometheus_text(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut counters = Vec::new();
+    for line in text.lines() {
+        if let Some(family) = line
+            .strip_prefix("# TYPE ")
+            .and_then(|family| family.strip_suffix(" counter"))
+        {
+            counters.push(family);
+        }
+    }
+    for line in text.lines() {
+        let line = match line.strip_prefix("# ") {
+            Some("EOF") => continue,
+            Some(comment) => {
+                let mut parts = comment.splitn(3, ' ');
+                let (kind, name, value) = (
+                    parts.next().unwrap_or_default(),
+                    parts.next().unwrap_or_default(),
+                    parts.next().unwrap_or_default(),
+                );
+                let name = match counters.contains(&name) {
+                    true => format!("{name}_total"),
+                    false => name.to_string(),
+                };
+                match (kind, value) {
+                    ("UNIT", _) => continue,
+                    ("TYPE", "unknown") => format!("# TYPE {name} untyped"),
+                    _ => format!("# {kind} {name} {value}"),
+                }
+            }
+            None => split_exemplar(line).0.to_string(),
+        };
+        output.push_str(&line);
+        output.push('\n');
+    }
+    output
+}
+
+// Rebuilds metric families from the OpenMetrics text so the JSON output
+// carries exactly what the text encoders expose.
+fn parse_exposition(text: &str) -> anyhow::Result<Vec<JsonFamily>> {
+    let mut families: Vec<JsonFamily> = Vec::new();
+    for line in text.lines() {
+        if line == "# EOF" || line.is_empty() {
+            continue;
+        }
+        if let Some(comment) = line.strip_prefix("# ") {
+            let mut parts = comment.splitn(3, ' ');
+            let (kind, name, value) = (
+                parts.next().unwrap_or_default(),
+                parts.next().unwrap_or_default(),
+                parts.next().unwrap_or_default(),
+            );
+            if families.last().map(|family| family.name.as_str()) != Some(name) {
+                families.push(JsonFamily {
+                    name: name.to_string(),
+                    metric_type: "unknown".to_string(),
+                    help: String::new(),
+                    samples: Vec::new(),
+                });
+            }
+            if let Some(family) = families.last_mut() {
+                match kind {
+                    "TYPE" => family.metric_type = value.to_string(),
+                    "HELP" => family.help = value.to_string(),
+                    _ => {}
+                }
+            }
+            continue;
+        }
+        let (series, value) = split_exemplar(line)
+            .0
+            .rsplit_once(' ')
+            .ok_or_else(|| anyhow::anyhow!("Invalid sample {line}"))?;
+        let (name, labels) = match series.split_once('{') {
+            Some((name, labels)) => (name, parse_labels(labels.trim_end_matches('}'))),
+            None => (series, BTreeMap::new()),
+        };
+        let sample = JsonSample {
+            name: name.to_string(),
+            labels,
+            value: value.parse()?,
+        };
+        match families.last_mut() {
+            Some(family) => family.samples.push(sample),
+            None => anyhow::bail!("Sample {line} without metric family"),
+        }
+    }
+    Ok(families)
+}
+
+fn parse_labels(labels: &str) -> BTreeMap<String, String> {
+    let mut parsed = BTreeMap::new();
+    let mut chars = labels.chars();
+    loop {
+        let key: String = chars
+            .by_ref()
+            .take_while(|c| *c != '=')
+            .collect::<String>()
+            .trim_start_matches(',')
+            .to_string();
+        if key.is_empty() || chars.next() != Some('"') {
+            break;
+        }
+        let mut value = String::new();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => break,
+                '\\' => match chars.next() {
+                    Some('n') => value.push('\n'),
+                    Some(escaped) => value.push(escaped),
+                    None => break,
+                },
+                c => value.push(c),
+            }
+        }
+        parsed.insert(key, value);
+    }
+    parsed
 }
 
 impl FormatAsPrometheus for SonarrEpisodeResult {
-    fn format_as_prometheus(&self, registry: &mut Registry) {
+    fn format_as_prometheus(&self, registry: &mut MetricRegistry) {
         debug!("Formatting {self:?} as Prometheus");
-        let sonarr_episode = Family::<SonarrLabels, Gauge<f64, AtomicU64>>::default();
-        registry.register(
+        let sonarr_episode: GaugeFamily<SonarrLabels> = registry.family(
             "sonarr_today_episode",
             format!("Sonarr today episode status"),
-            sonarr_episode.clone(),
         );
         self.episodes.iter().for_each(|ep: &SonarrEpisode| {
             let labels = SonarrLabels {
@@ -180,114 +1285,316 @@ impl FormatAsPrometheus for SonarrEpisodeResult {
     }
 }
 impl FormatAsPrometheus for SonarrMissingResult {
-    fn format_as_prometheus(&self, registry: &mut Registry) {
+    fn format_as_prometheus(&self, registry: &mut MetricRegistry) {
+        debug!("Formatting {self:?} as Prometheus");
+        let sonarr_episode: GaugeFamily<SonarrLabels> = registry.family(
+            "sonarr_missing_episode",
+            format!("Sonarr missing episode status"),
+        );
+        self.episodes.iter().for_each(|ep: &SonarrEpisode| {
+            let labels = SonarrLabels {
+                name: self.name.clone(),
+                sxe: ep.sxe.clone(),
+                season_number: ep.season_number,
+                episode_number: ep.episode_number,
+                title: ep.title.clone(),
+                serie: ep.serie.clone(),
+            };
+            sonarr_episode
+                .get_or_create(&labels)
+                .set(if ep.has_file { 1.0 } else { 0.0 });
+        });
+    }
+}
+
+impl FormatAsPrometheus for TautulliSessionResult {
+    fn format_as_prometheus(&self, registry: &mut MetricRegistry) {
+        debug!("Formatting {self:?} as Prometheus");
+        let tautulli_session: GaugeFamily<TautulliSessionLabels> =
+            registry.family("tautulli_session", format!("Tautulli session status"));
+        let tautulli_session_percentage: GaugeFamily<TautulliSessionPercentageLabels> = registry
+            .family(
+                "tautulli_session_percentage",
+                format!("Tautulli session progress"),
+            );
+        self.sessions.iter().for_each(|session: &SessionSummary| {
+            let labels = TautulliSessionPercentageLabels {
+                name: self.name.clone(),
+                user: session.user.clone(),
+                title: session.title.clone(),
+                state: session.state.clone(),
+                media_type: session.media_type.clone(),
+                season_number: session.season_number.clone(),
+                episode_number: session.episode_number.clone(),
+                quality: session.quality.clone(),
+                quality_profile: session.quality_profile.clone(),
+                video_stream: session.video_stream.clone(),
+                city: session.location.city.clone(),
+            };
+            tautulli_session_percentage
+                .get_or_create(&labels)
+                .set(session.progress.parse::<f64>().unwrap_or(0.0));
+            let labels = TautulliSessionLabels {
+                name: self.name.clone(),
+                user: session.user.clone(),
+                title: session.title.clone(),
+                state: session.state.clone(),
+                media_type: session.media_type.clone(),
+                season_number: session.season_number.clone(),
+                episode_number: session.episode_number.clone(),
+                quality: session.quality.clone(),
+                quality_profile: session.quality_profile.clone(),
+                video_stream: session.video_stream.clone(),
+                city: session.location.city.clone(),
+                longitude: session.location.longitude.clone(),
+                latitude: session.location.latitude.clone(),
+            };
+            tautulli_session.get_or_create(&labels).set(1.0);
+        });
+        if let Some(stats) = &self.stats {
+            let tautulli_bandwidth: GaugeFamily<TautulliBandwidthLabels> = registry.family(
+                "tautulli_bandwidth_bps",
+                "Tautulli streaming bandwidth in bits per second",
+            );
+            [
+                ("total", stats.total_bandwidth),
+                ("lan", stats.lan_bandwidth),
+                ("wan", stats.wan_bandwidth),
+            ]
+            .iter()
+            .for_each(|(location, bandwidth)| {
+                tautulli_bandwidth
+                    .get_or_create(&TautulliBandwidthLabels {
+                        name: self.name.clone(),
+                        location: location.to_string(),
+                    })
+                    .set(*bandwidth as f64 * BITS_PER_KILOBIT);
+            });
+            let tautulli_stream_count: GaugeFamily<TautulliStreamCountLabels> =
+                registry.family("tautulli_stream_count", "Tautulli stream count by decision");
+            [
+                ("direct_play", stats.stream_count_direct_play),
+                ("direct_stream", stats.stream_count_direct_stream),
+                ("transcode", stats.stream_count_transcode),
+            ]
+            .iter()
+            .for_each(|(decision, count)| {
+                tautulli_stream_count
+                    .get_or_create(&TautulliStreamCountLabels {
+                        name: self.name.clone(),
+                        decision: decision.to_string(),
+                    })
+                    .set(*count as f64);
+            });
+        }
+    }
+}
+
+impl FormatAsPrometheus for TautulliLibraryResult {
+    fn format_as_prometheus(&self, registry: &mut MetricRegistry) {
+        debug!("Formatting {self:?} as Prometheus");
+        let tautulli_library: GaugeFamily<TautulliLibraryLabels> =
+            registry.family("tautulli_library", format!("Tautulli library status"));
+        self.libraries.iter().for_each(|library: &TautulliLibrary| {
+            let labels = TautulliLibraryLabels {
+                name: self.name.clone(),
+                section_name: library.section_name.clone(),
+                section_type: library.section_type.clone(),
+                count: library.count.clone(),
+                parent_count: library.parent_count.clone(),
+                child_count: library.child_count.clone(),
+            };
+            tautulli_library
+                .get_or_create(&labels)
+                .set(library.is_active as f64);
+        });
+        let tautulli_library_size: GaugeFamily<TautulliLibrarySizeLabels> = registry.family(
+            "tautulli_library_file_size_bytes",
+            "Tautulli library total file size",
+        );
+        self.file_sizes.iter().for_each(|size| {
+            tautulli_library_size
+                .get_or_create(&TautulliLibrarySizeLabels {
+                    name: self.name.clone(),
+                    section_name: size.section_name.clone(),
+                    section_type: size.section_type.clone(),
+                })
+                .set(size.file_size as f64);
+        });
+    }
+}
+
+impl FormatAsPrometheus for TautulliServerResult {
+    fn format_as_prometheus(&self, registry: &mut MetricRegistry) {
+        debug!("Formatting {self:?} as Prometheus");
+        let info = match &self.info {
+            Some(info) => info,
+            None => return,
+        };
+        let info_label: GaugeFamily<TautulliInfoLabels> =
+            registry.family("tautulli_info", "Tautulli server information");
+        info_label
+            .get_or_create(&TautulliInfoLabels {
+                name: self.name.clone(),
+                version: info.version.clone(),
+                branch: info.branch.clone(),
+            })
+            .set(1.0);
+        if let Some(update_available) = info.update_available {
+            let update: GaugeFamily<InstanceLabels> =
+                registry.family("tautulli_update_available", "Tautulli update available");
+            update
+                .get_or_create(&InstanceLabels {
+                    name: self.name.clone(),
+                })
+                .set(if update_available { 1.0 } else { 0.0 });
+        }
+        if let Some(connected) = info.plex_connected {
+            let plex_connected: GaugeFamily<InstanceLabels> = registry.family(
+                "tautulli_plex_connected",
+                "Tautulli connection to Plex server",
+            );
+            plex_connected
+                .get_or_create(&InstanceLabels {
+                    name: self.name.clone(),
+                })
+                .set(if connected { 1.0 } else { 0.0 });
+        }
+    }
+}
+
+impl FormatAsPrometheus for TautulliNotificationsResult {
+    fn format_as_prometheus(&self, registry: &mut MetricRegistry) {
+        debug!("Formatting {self:?} as Prometheus");
+        let notifications: GaugeFamily<TautulliNotificationLabels> = registry.family(
+            "tautulli_recent_notifications",
+            "Tautulli recent notification deliveries",
+        );
+        let failures: GaugeFamily<TautulliNotificationLabels> = registry.family(
+            "tautulli_recent_notification_failures",
+            "Tautulli recent failed notification deliveries",
+        );
+        self.notifications.iter().for_each(|notification| {
+            let labels = TautulliNotificationLabels {
+                name: self.name.clone(),
+                agent: notification.agent.clone(),
+            };
+            notifications
+                .get_or_create(&labels)
+                .set(notification.sent as f64);
+            failures
+                .get_or_create(&labels)
+                .set(notification.failed as f64);
+        });
+    }
+}
+
+impl FormatAsPrometheus for TautulliPlaysByDateResult {
+    fn format_as_prometheus(&self, registry: &mut MetricRegistry) {
+        debug!("Formatting {self:?} as Prometheus");
+        let daily_plays: GaugeFamily<TautulliDailyPlaysLabels> =
+            registry.family("tautulli_daily_plays", "Tautulli plays per day");
+        self.plays.iter().for_each(|plays| {
+            daily_plays
+                .get_or_create(&TautulliDailyPlaysLabels {
+                    name: self.name.clone(),
+                    date: plays.date.clone(),
+                    media_type: plays.media_type.clone(),
+                })
+                .set(plays.plays as f64);
+        });
+    }
+}
+
+impl FormatAsPrometheus for TautulliHomeStatsResult {
+    fn format_as_prometheus(&self, registry: &mut MetricRegistry) {
         debug!("Formatting {self:?} as Prometheus");
-        let sonarr_episode = Family::<SonarrLabels, Gauge<f64, AtomicU64>>::default();
-        registry.register(
-            "sonarr_missing_episode",
-            format!("Sonarr missing episode status"),
-            sonarr_episode.clone(),
+        let home_stat_plays: GaugeFamily<TautulliHomeStatLabels> =
+            registry.family("tautulli_home_stat_plays", "Tautulli home stats play count");
+        let home_stat_duration: GaugeFamily<TautulliHomeStatLabels> = registry.family(
+            "tautulli_home_stat_duration_seconds",
+            "Tautulli home stats watch duration",
         );
-        self.episodes.iter().for_each(|ep: &SonarrEpisode| {
-            let labels = SonarrLabels {
+        self.stats.iter().for_each(|stat| {
+            let labels = TautulliHomeStatLabels {
                 name: self.name.clone(),
-                sxe: ep.sxe.clone(),
-                season_number: ep.season_number,
-                episode_number: ep.episode_number,
-                title: ep.title.clone(),
-                serie: ep.serie.clone(),
+                stat: stat.stat.clone(),
+                label: stat.label.clone(),
             };
-            sonarr_episode
+            home_stat_plays
                 .get_or_create(&labels)
-                .set(if ep.has_file { 1.0 } else { 0.0 });
+                .set(stat.plays as f64);
+            home_stat_duration
+                .get_or_create(&labels)
+                .set(stat.duration as f64);
         });
     }
 }
 
-impl FormatAsPrometheus for TautulliSessionResult {
-    fn format_as_prometheus(&self, registry: &mut Registry) {
+impl FormatAsPrometheus for TautulliHistoryResult {
+    fn format_as_prometheus(&self, registry: &mut MetricRegistry) {
         debug!("Formatting {self:?} as Prometheus");
-        let tautulli_session = Family::<TautulliSessionLabels, Gauge<f64, AtomicU64>>::default();
-        let tautulli_session_percentage =
-            Family::<TautulliSessionPercentageLabels, Gauge<f64, AtomicU64>>::default();
-        registry.register(
-            "tautulli_session",
-            format!("Tautulli session status"),
-            tautulli_session.clone(),
+        let history_plays: Family<TautulliHistoryLabels, Counter> = registry.counter(
+            "tautulli_history_plays",
+            "Tautulli plays recorded in history",
         );
-        registry.register(
-            "tautulli_session_percentage",
-            format!("Tautulli session progress"),
-            tautulli_session_percentage.clone(),
+        let history_duration: Family<TautulliHistoryLabels, Counter> = registry.counter(
+            "tautulli_history_watch_seconds",
+            "Tautulli watch duration recorded in history",
         );
-        self.sessions.iter().for_each(|session: &SessionSummary| {
-            let labels = TautulliSessionPercentageLabels {
-                user: session.user.clone(),
-                title: session.title.clone(),
-                state: session.state.clone(),
-                media_type: session.media_type.clone(),
-                season_number: session.season_number.clone(),
-                episode_number: session.episode_number.clone(),
-                quality: session.quality.clone(),
-                quality_profile: session.quality_profile.clone(),
-                video_stream: session.video_stream.clone(),
-                city: session.location.city.clone(),
+        // Rows up to the cursor were counted by an earlier collection.
+        let cursor = registry.history_cursors.get(&self.name).copied();
+        let mut last = cursor;
+        for play in &self.plays {
+            if cursor.is_some_and(|cursor| play.id <= cursor) {
+                continue;
+            }
+            let labels = TautulliHistoryLabels {
+                name: self.name.clone(),
+                user: play.user.clone(),
+                media_type: play.media_type.clone(),
             };
-            tautulli_session_percentage
+            history_plays.get_or_create(&labels).inc();
+            history_duration
                 .get_or_create(&labels)
-                .set(session.progress.parse::<f64>().unwrap_or(0.0));
-            let labels = TautulliSessionLabels {
-                user: session.user.clone(),
-                title: session.title.clone(),
-                state: session.state.clone(),
-                media_type: session.media_type.clone(),
-                season_number: session.season_number.clone(),
-                episode_number: session.episode_number.clone(),
-                quality: session.quality.clone(),
-                quality_profile: session.quality_profile.clone(),
-                video_stream: session.video_stream.clone(),
-                city: session.location.city.clone(),
-                longitude: session.location.longitude.clone(),
-                latitude: session.location.latitude.clone(),
-            };
-            tautulli_session.get_or_create(&labels).set(1.0);
-        });
+                .inc_by(play.duration);
+            last = last.max(Some(play.id));
+        }
+        if let Some(last) = last.filter(|last| Some(*last) != cursor) {
+            registry.history_cursors.insert(self.name.clone(), last);
+            providers::tautulli::set_history_cursor(&self.name, last);
+        }
     }
 }
 
-impl FormatAsPrometheus for TautulliLibraryResult {
-    fn format_as_prometheus(&self, registry: &mut Registry) {
+impl FormatAsPrometheus for TautulliUserWatchTimeResult {
+    fn format_as_prometheus(&self, registry: &mut MetricRegistry) {
         debug!("Formatting {self:?} as Prometheus");
-        let tautulli_library = Family::<TautulliLibraryLabels, Gauge<f64, AtomicU64>>::default();
-        registry.register(
-            "tautulli_library",
-            format!("Tautulli library status"),
-            tautulli_library.clone(),
+        let watch_time: GaugeFamily<TautulliWatchTimeLabels> = registry.family(
+            "tautulli_user_watch_time_seconds",
+            "Tautulli user watch time over the last days",
         );
-        self.libraries.iter().for_each(|library: &TautulliLibrary| {
-            let labels = TautulliLibraryLabels {
-                section_name: library.section_name.clone(),
-                section_type: library.section_type.clone(),
-                count: library.count.clone(),
-                parent_count: library.parent_count.clone(),
-                child_count: library.child_count.clone(),
+        let plays: GaugeFamily<TautulliWatchTimeLabels> = registry.family(
+            "tautulli_user_plays",
+            "Tautulli user plays over the last days",
+        );
+        self.watch_time.iter().for_each(|stat| {
+            let labels = TautulliWatchTimeLabels {
+                name: self.name.clone(),
+                user: stat.user.clone(),
+                days: stat.days,
             };
-            tautulli_library
-                .get_or_create(&labels)
-                .set(library.is_active as f64);
+            watch_time.get_or_create(&labels).set(stat.duration as f64);
+            plays.get_or_create(&labels).set(stat.plays as f64);
         });
     }
 }
 
 impl FormatAsPrometheus for RadarrMovieResult {
-    fn format_as_prometheus(&self, registry: &mut Registry) {
+    fn format_as_prometheus(&self, registry: &mut MetricRegistry) {
         debug!("Formatting {self:?} as Prometheus");
-        let radarr_movie = Family::<RadarrLabels, Gauge<f64, AtomicU64>>::default();
-        registry.register(
-            "radarr_movie",
-            format!("Radarr movie status"),
-            radarr_movie.clone(),
-        );
+        let radarr_movie: GaugeFamily<RadarrLabels> =
+            registry.family("radarr_movie", format!("Radarr movie status"));
         self.movies.iter().for_each(|movie: &RadarrMovie| {
             let labels = RadarrLabels {
                 name: self.name.clone(),
@@ -304,99 +1611,208 @@ impl FormatAsPrometheus for RadarrMovieResult {
 }
 
 impl FormatAsPrometheus for OverseerrRequestResult {
-    fn format_as_prometheus(&self, registry: &mut Registry) {
+    fn format_as_prometheus(&self, registry: &mut MetricRegistry) {
         debug!("Formatting {self:?} as Prometheus");
-        let overseerr_request = Family::<OverseerrLabels, Gauge<f64, AtomicU64>>::default();
-        registry.register(
-            format!("{}_requests", self.kind),
-            format!("{} requests status", self.kind),
-            overseerr_request.clone(),
-        );
-
-        self.requests.iter().for_each(|request: &OverseerrRequest| {
-            let labels = OverseerrLabels {
-                media_type: request.media_type.clone(),
-                requested_by: request.requested_by.to_string(),
-                request_status: request.status.to_string(),
-                media_status: request.media_status.to_string(),
-                media_title: request.media_title.clone(),
-                requested_at: request.requested_at.clone(),
+        if self.aggregate {
+            let overseerr_aggregate: GaugeFamily<OverseerrAggregateLabels> = registry.family(
+                format!("{}_requests_aggregated", self.kind),
+                format!("{} requests by status, media type and user", self.kind),
+            );
+            self.requests.iter().for_each(|request: &OverseerrRequest| {
+                let labels = OverseerrAggregateLabels {
+                    name: self.name.clone(),
+                    media_type: request.media_type.clone(),
+                    requested_by: request.requested_by.to_string(),
+                    request_status: request.status.to_string(),
+                    media_status: request.media_status.to_string(),
+                };
+                overseerr_aggregate.get_or_create(&labels).inc();
+            });
+        } else {
+            let overseerr_request: GaugeFamily<OverseerrLabels> = registry.family(
+                format!("{}_requests", self.kind),
+                format!("{} requests status", self.kind),
+            );
+            self.requests.iter().for_each(|request: &OverseerrRequest| {
+                let labels = OverseerrLabels {
+                    name: self.name.clone(),
+                    media_type: request.media_type.clone(),
+                    requested_by: request.requested_by.to_string(),
+                    request_status: request.status.to_string(),
+                    media_status: request.media_status.to_string(),
+                    media_title: request.media_title.clone(),
+                    requested_at: request.requested_at.clone(),
+                };
+                overseerr_request
+                    .get_or_create(&labels)
+                    .set(request.status.as_f64());
+            });
+        }
+        if let Some(counts) = &self.counts {
+            let status_count: GaugeFamily<OverseerrStatusCountLabels> = registry.family(
+                format!("{}_request_count", self.kind),
+                format!("{} request count by status", self.kind),
+            );
+            let media_type_count: GaugeFamily<OverseerrMediaTypeCountLabels> = registry.family(
+                format!("{}_request_media_type_count", self.kind),
+                format!("{} request count by media type", self.kind),
+            );
+            [
+                ("pending", counts.pending),
+                ("approved", counts.approved),
+                ("declined", counts.declined),
+                ("processing", counts.processing),
+                ("available", counts.available),
+            ]
+            .iter()
+            .for_each(|(status, count)| {
+                status_count
+                    .get_or_create(&OverseerrStatusCountLabels {
+                        name: self.name.clone(),
+                        status: status.to_string(),
+                    })
+                    .set(*count as f64);
+            });
+            [("movie", counts.movie), ("tv", counts.tv)]
+                .iter()
+                .for_each(|(media_type, count)| {
+                    media_type_count
+                        .get_or_create(&OverseerrMediaTypeCountLabels {
+                            name: self.name.clone(),
+                            media_type: media_type.to_string(),
+                        })
+                        .set(*count as f64);
+                });
+        }
+    }
+}
+
+impl FormatAsPrometheus for OverseerrJobsResult {
+    fn format_as_prometheus(&self, registry: &mut MetricRegistry) {
+        debug!("Formatting {self:?} as Prometheus");
+        let job_running: GaugeFamily<OverseerrJobLabels> = registry.family(
+            format!("{}_job_running", self.kind),
+            format!("{} job currently running", self.kind),
+        );
+        let job_next_execution: GaugeFamily<OverseerrJobLabels> = registry.family(
+            format!("{}_job_next_execution_timestamp_seconds", self.kind),
+            format!("{} job next execution time", self.kind),
+        );
+        self.jobs.iter().for_each(|job| {
+            let labels = OverseerrJobLabels {
+                name: self.name.clone(),
+                job: job.id.clone(),
+                job_name: job.name.clone(),
+                job_type: job.job_type.clone(),
             };
-            overseerr_request
+            job_running
                 .get_or_create(&labels)
-                .set(request.status.as_f64());
+                .set(if job.running { 1.0 } else { 0.0 });
+            if let Some(next_execution) = job.next_execution {
+                job_next_execution
+                    .get_or_create(&labels)
+                    .set(next_execution as f64);
+            }
         });
     }
 }
 
 impl FormatAsPrometheus for SessionResult {
-    fn format_as_prometheus(&self, registry: &mut Registry) {
+    fn format_as_prometheus(&self, registry: &mut MetricRegistry) {
         debug!("Formatting {self:?} as Prometheus");
-        let sessions_labels = Family::<SessionLabels, Gauge<f64, AtomicU64>>::default();
-        let sessions_percentage = Family::<SessionLabels, Gauge<f64, AtomicU64>>::default();
-        let session_bandwidth = Family::<SessionBandwidth, Gauge<f64, AtomicU64>>::default();
+        let (prefix, title) = match self.kind.as_str() {
+            "plex" => ("plex_", "Plex sessions"),
+            "jellyfin" => ("jellyfin_", "Jellyfin sessions"),
+            _ => ("", "Sessions"),
+        };
+        let sessions_labels: GaugeFamily<SessionLabels> =
+            registry.family(format!("{prefix}sessions"), format!("{title} status"));
+        let sessions_percentage: GaugeFamily<SessionLabels> = registry.family(
+            format!("{prefix}sessions_percentage"),
+            format!("{title} percentage status"),
+        );
+        let sessions_duration: GaugeFamily<SessionLabels> = registry.family(
+            format!("{prefix}session_duration_seconds"),
+            format!("{title} media runtime in seconds"),
+        );
+        let sessions_remaining: GaugeFamily<SessionLabels> = registry.family(
+            format!("{prefix}session_remaining_seconds"),
+            format!("{title} playback time left in seconds"),
+        );
+        let progress: Family<ProgressLabels, Histogram, fn() -> Histogram> = registry
+            .histogram_family(
+                format!("{prefix}session_progress_percent"),
+                format!("{title} by progress, in buckets of 10%"),
+                || Histogram::new(linear_buckets(10.0, 10.0, 10)),
+            );
+        let plays: Family<PlayLabels, CounterWithExemplar<PlayExemplar>> = registry.counter(
+            format!("{prefix}plays_observed"),
+            format!("{title} seen starting between two scrapes"),
+        );
+        let watch_seconds: Family<WatchLabels, Counter<f64, AtomicU64>> = registry.counter(
+            "watch_seconds",
+            "Seconds of playback seen between two scrapes, by user, media type and library",
+        );
+        let instance = format!("{}/{}", self.kind, self.name);
+        let changes = registry.observe_sessions(instance, &self.sessions);
+        for session in changes.started {
+            plays
+                .get_or_create(&PlayLabels {
+                    name: self.name.clone(),
+                    user: session.user.clone(),
+                    media_type: session.media_type.to_string(),
+                })
+                .inc_by(
+                    1,
+                    (!session.id.is_empty()).then(|| PlayExemplar {
+                        session_id: session.id.clone(),
+                    }),
+                );
+        }
+        for (session, seconds) in changes.watched {
+            watch_seconds
+                .get_or_create(&WatchLabels {
+                    provider: self.kind.clone(),
+                    name: self.name.clone(),
+                    user: session.user.clone(),
+                    media_type: session.media_type.to_string(),
+                    library: session.library.clone(),
+                })
+                .inc_by(seconds);
+        }
+        let sessions_by_play_method: GaugeFamily<PlayMethodLabels> = match self.kind.as_str() {
+            "jellyfin" => registry.family(
+                "jellyfin_sessions_by_play_method",
+                "Jellyfin sessions by play method",
+            ),
+            _ => Family::default(),
+        };
         let mut inactive_users = self.users.clone();
         let mut wan_bandwidth = 0.0;
         let mut lan_bandwidth = 0.0;
-        match self.kind.as_str() {
-            "plex" => {
-                registry.register(
-                    "plex_sessions",
-                    format!("Plex sessions status"),
-                    sessions_labels.clone(),
-                );
-                registry.register(
-                    "plex_sessions_percentage",
-                    format!("Plex sessions percentage status"),
-                    sessions_percentage.clone(),
-                );
-                registry.register(
-                    "plex_session_bandwidth",
-                    format!("Plex session bandwidth"),
-                    session_bandwidth.clone(),
-                );
-                session_bandwidth
-                    .get_or_create(&SessionBandwidth {
-                        name: self.name.clone(),
-                        location: "LAN".to_string(),
-                    })
-                    .set(lan_bandwidth);
-                session_bandwidth
-                    .get_or_create(&SessionBandwidth {
+        let session_bandwidth: GaugeFamily<SessionBandwidth> = match self.kind.as_str() {
+            "plex" => registry.family(
+                "plex_session_bandwidth_bps",
+                "Plex session bandwidth in bits per second",
+            ),
+            _ => Family::default(),
+        };
+        if self.kind == "jellyfin" {
+            [
+                StreamDecision::DirectPlay,
+                StreamDecision::DirectStream,
+                StreamDecision::Transcode,
+            ]
+            .iter()
+            .filter_map(StreamDecision::play_method)
+            .for_each(|method| {
+                sessions_by_play_method
+                    .get_or_create(&PlayMethodLabels {
                         name: self.name.clone(),
-                        location: "WAN".to_string(),
+                        method: method.to_string(),
                     })
-                    .set(wan_bandwidth);
-            }
-            "jellyfin" => {
-                registry.register(
-                    "jellyfin_sessions",
-                    format!("Jellyfin sessions status"),
-                    sessions_labels.clone(),
-                );
-                registry.register(
-                    "jellyfin_sessions_percentage",
-                    format!("Jellyfin sessions percentage status"),
-                    sessions_percentage.clone(),
-                );
-            }
-            _ => {
-                registry.register(
-                    "sessions",
-                    format!("Sessions status"),
-                    sessions_labels.clone(),
-                );
-                registry.register(
-                    "sessions_percentage",
-                    format!("Sessions percentage status"),
-                    sessions_percentage.clone(),
-                );
-                registry.register(
-                    "session_bandwidth",
-                    format!("Session bandwidth"),
-                    session_bandwidth.clone(),
-                );
-            }
+                    .set(0.0);
+            });
         }
         self.sessions.iter().for_each(|session: &Session| {
             match session.bandwidth.location {
@@ -405,6 +1821,14 @@ impl FormatAsPrometheus for SessionResult {
                 BandwidthLocation::Unknown => {}
             };
             inactive_users.retain(|user| user.name != session.user);
+            if let Some(method) = session.stream_decision.play_method() {
+                sessions_by_play_method
+                    .get_or_create(&PlayMethodLabels {
+                        name: self.name.clone(),
+                        method: method.to_string(),
+                    })
+                    .inc();
+            }
             let session_labels = SessionLabels {
                 name: self.name.clone(),
                 title: session.title.clone(),
@@ -417,8 +1841,14 @@ impl FormatAsPrometheus for SessionResult {
                 secure: session.secure as i8,
                 address: session.address.clone(),
                 public_address: session.location.ip_address.clone(),
+                hostname: session.hostname.clone(),
                 season_number: session.season_number.clone(),
                 episode_number: session.episode_number.clone(),
+                show: session.show_title.clone(),
+                season_title: session.season_title.clone(),
+                episode_title: session.episode_title.clone(),
+                artist: session.artist.clone(),
+                album: session.album.clone(),
                 media_type: session.media_type.to_string(),
                 quality: session.quality.to_string(),
                 city: session.location.city.clone(),
@@ -428,9 +1858,31 @@ impl FormatAsPrometheus for SessionResult {
 
             sessions_percentage
                 .get_or_create(&session_labels)
-                .set(session.progress as f64);
+                .set(session.progress);
+            // Photos and live TV have no runtime.
+            if session.duration > 0.0 {
+                progress
+                    .get_or_create(&ProgressLabels {
+                        name: self.name.clone(),
+                    })
+                    .observe(session.progress);
+                sessions_duration
+                    .get_or_create(&session_labels)
+                    .set(session.duration);
+                sessions_remaining
+                    .get_or_create(&session_labels)
+                    .set((session.duration - session.offset).max(0.0));
+            }
             sessions_labels.get_or_create(&session_labels).set(1.0);
         });
+        for (location, bandwidth) in [("LAN", lan_bandwidth), ("WAN", wan_bandwidth)] {
+            session_bandwidth
+                .get_or_create(&SessionBandwidth {
+                    name: self.name.clone(),
+                    location: location.to_string(),
+                })
+                .set(bandwidth * BITS_PER_KILOBIT);
+        }
         inactive_users.iter().for_each(|user| {
             sessions_labels
                 .get_or_create(&SessionLabels {
@@ -446,8 +1898,14 @@ impl FormatAsPrometheus for SessionResult {
                     address: "".to_string(),
                     media_type: "".to_string(),
                     public_address: "".to_string(),
+                    hostname: None,
                     season_number: None,
                     episode_number: None,
+                    show: None,
+                    season_title: None,
+                    episode_title: None,
+                    artist: None,
+                    album: None,
                     quality: "".to_string(),
                     city: "".to_string(),
                     longitude: "".to_string(),
@@ -458,67 +1916,335 @@ impl FormatAsPrometheus for SessionResult {
     }
 }
 
+#[derive(Clone, Hash, Eq, PartialEq, EncodeLabelSet, Debug)]
+struct CollectionLabels {
+    pub provider: String,
+    pub name: String,
+    pub task: String,
+    pub reason: String,
+}
+
+impl FormatAsPrometheus for CollectionResult {
+    fn format_as_prometheus(&self, registry: &mut MetricRegistry) {
+        debug!("Formatting {self:?} as Prometheus");
+        let success: GaugeFamily<CollectionLabels> = registry.family(
+            "collection_success",
+            "Whether the task got its data from the provider, reason tells why it did not",
+        );
+        let errors: Family<CollectionLabels, Counter> = registry.counter(
+            "provider_errors",
+            "Tasks that did not get their data from the provider, by reason",
+        );
+        let stale: GaugeFamily<CollectionLabels> = registry.family(
+            "collection_stale",
+            "Whether the task is served from the result cache of a previous run",
+        );
+        for outcome in &self.outcomes {
+            let labels = CollectionLabels {
+                provider: outcome.provider.clone(),
+                name: outcome.name.clone(),
+                task: outcome.task.clone(),
+                reason: outcome.failure.unwrap_or_default().to_string(),
+            };
+            success
+                .get_or_create(&labels)
+                .set(if outcome.failure.is_none() { 1.0 } else { 0.0 });
+            if outcome.failure.is_some() {
+                errors.get_or_create(&labels).inc();
+            }
+            stale
+                .get_or_create(&labels)
+                .set(if outcome.stale { 1.0 } else { 0.0 });
+        }
+    }
+}
+
+#[derive(Clone, Hash, Eq, PartialEq, EncodeLabelSet, Debug)]
+struct HistoryLibraryLabels {
+    pub provider: String,
+    pub name: String,
+    pub library: String,
+}
+
+#[derive(Clone, Hash, Eq, PartialEq, EncodeLabelSet, Debug)]
+struct HistoryLabels {
+    pub provider: String,
+    pub name: String,
+}
+
+impl FormatAsPrometheus for HistoryResult {
+    fn format_as_prometheus(&self, registry: &mut MetricRegistry) {
+        debug!("Formatting {self:?} as Prometheus");
+        let library_items_added: GaugeFamily<HistoryLibraryLabels> = registry.family(
+            "library_items_added_24h",
+            "Change of the library item count over the last 24 hours",
+        );
+        let requests_added_today: GaugeFamily<HistoryLabels> =
+            registry.family("requests_added_today", "Requests made since midnight");
+        let sessions_peak: GaugeFamily<HistoryLabels> = registry.family(
+            "sessions_peak_24h",
+            "Highest session count seen over the last 24 hours",
+        );
+        for value in &self.library_items_added {
+            library_items_added
+                .get_or_create(&HistoryLibraryLabels {
+                    provider: value.provider.clone(),
+                    name: value.name.clone(),
+                    library: value.library.clone(),
+                })
+                .set(value.value);
+        }
+        for (family, values) in [
+            (&requests_added_today, &self.requests_added_today),
+            (&sessions_peak, &self.sessions_peak),
+        ] {
+            for value in values {
+                family
+                    .get_or_create(&HistoryLabels {
+                        provider: value.provider.clone(),
+                        name: value.name.clone(),
+                    })
+                    .set(value.value);
+            }
+        }
+    }
+}
+
+impl FormatAsPrometheus for PlexAccountResult {
+    fn format_as_prometheus(&self, registry: &mut MetricRegistry) {
+        debug!("Formatting {self:?} as Prometheus");
+        let remote_access_enabled: GaugeFamily<PlexCount> =
+            registry.family("plex_remote_access_enabled", "Plex remote access enabled");
+        let remote_access_reachable: GaugeFamily<PlexCount> = registry.family(
+            "plex_remote_access_reachable",
+            "Plex remote access reachable from plex.tv",
+        );
+        let token_valid: GaugeFamily<PlexCount> = registry.family(
+            "plex_token_valid",
+            "Plex token accepted and server signed in to plex.tv",
+        );
+        let Some(remote_access) = &self.remote_access else {
+            return;
+        };
+        let labels = PlexCount {
+            name: self.name.clone(),
+        };
+        if let Some(enabled) = remote_access.enabled {
+            remote_access_enabled
+                .get_or_create(&labels)
+                .set(if enabled { 1.0 } else { 0.0 });
+        }
+        if let Some(reachable) = remote_access.reachable {
+            remote_access_reachable
+                .get_or_create(&labels)
+                .set(if reachable { 1.0 } else { 0.0 });
+        }
+        token_valid
+            .get_or_create(&labels)
+            .set(if remote_access.token_valid { 1.0 } else { 0.0 });
+    }
+}
+
+impl FormatAsPrometheus for JellyfinDevicesResult {
+    fn format_as_prometheus(&self, registry: &mut MetricRegistry) {
+        debug!("Formatting {self:?} as Prometheus");
+        let devices: GaugeFamily<JellyfinClientLabels> =
+            registry.family("jellyfin_devices", "Jellyfin registered devices");
+        let connected: GaugeFamily<JellyfinClientLabels> = registry.family(
+            "jellyfin_connected_sessions",
+            "Jellyfin connected sessions including idle ones",
+        );
+        self.devices.iter().for_each(|device| {
+            devices
+                .get_or_create(&JellyfinClientLabels {
+                    name: self.name.clone(),
+                    client: device.client.clone(),
+                    platform: device.platform.clone(),
+                })
+                .inc();
+        });
+        self.connected.iter().for_each(|session| {
+            connected
+                .get_or_create(&JellyfinClientLabels {
+                    name: self.name.clone(),
+                    client: session.client.clone(),
+                    platform: session.platform.clone(),
+                })
+                .inc();
+        });
+    }
+}
+
+impl FormatAsPrometheus for JellyfinPluginsResult {
+    fn format_as_prometheus(&self, registry: &mut MetricRegistry) {
+        debug!("Formatting {self:?} as Prometheus");
+        let plugin_info: GaugeFamily<JellyfinPluginLabels> =
+            registry.family("jellyfin_plugin_info", "Jellyfin installed plugins");
+        let plugin_update: GaugeFamily<JellyfinPluginUpdateLabels> = registry.family(
+            "jellyfin_plugin_update_available",
+            "Jellyfin plugin has a newer version available",
+        );
+        self.plugins.iter().for_each(|plugin| {
+            plugin_info
+                .get_or_create(&JellyfinPluginLabels {
+                    name: self.name.clone(),
+                    plugin: plugin.name.clone(),
+                    version: plugin.version.clone(),
+                    status: plugin.status.clone(),
+                })
+                .set(1.0);
+            plugin_update
+                .get_or_create(&JellyfinPluginUpdateLabels {
+                    name: self.name.clone(),
+                    plugin: plugin.name.clone(),
+                })
+                .set(if plugin.update_available { 1.0 } else { 0.0 });
+        });
+    }
+}
+
+impl FormatAsPrometheus for JellyfinInfoResult {
+    fn format_as_prometheus(&self, registry: &mut MetricRegistry) {
+        debug!("Formatting {self:?} as Prometheus");
+        let info = match &self.info {
+            Some(info) => info,
+            None => return,
+        };
+        let info_label: GaugeFamily<JellyfinInfoLabels> =
+            registry.family("jellyfin_info", "Jellyfin server information");
+        let startup_wizard: GaugeFamily<PlexCount> = registry.family(
+            "jellyfin_startup_wizard_completed",
+            "Jellyfin startup wizard completed",
+        );
+        info_label
+            .get_or_create(&JellyfinInfoLabels {
+                name: self.name.clone(),
+                version: info.version.clone(),
+                product: info.product.clone(),
+                operating_system: info.operating_system.clone(),
+            })
+            .set(1.0);
+        let labels = PlexCount {
+            name: self.name.clone(),
+        };
+        startup_wizard
+            .get_or_create(&labels)
+            .set(if info.startup_wizard_completed {
+                1.0
+            } else {
+                0.0
+            });
+        if let Some(pending) = info.restart_pending {
+            let restart_pending: GaugeFamily<PlexCount> = registry.family(
+                "jellyfin_restart_pending",
+                "Jellyfin server restart pending",
+            );
+            restart_pending
+                .get_or_create(&labels)
+                .set(if pending { 1.0 } else { 0.0 });
+        }
+    }
+}
+
+impl FormatAsPrometheus for JellyfinRecentlyAddedResult {
+    fn format_as_prometheus(&self, registry: &mut MetricRegistry) {
+        debug!("Formatting {self:?} as Prometheus");
+        let recently_added: GaugeFamily<MediaTypeLabels> =
+            registry.family("jellyfin_recently_added", "Jellyfin items recently added");
+        let last_added: GaugeFamily<MediaTypeLabels> = registry.family(
+            "jellyfin_last_added_timestamp_seconds",
+            "Jellyfin most recent item added timestamp",
+        );
+        self.recently_added.iter().for_each(|added| {
+            let labels = MediaTypeLabels {
+                name: self.name.clone(),
+                media_type: added.media_type.clone(),
+            };
+            recently_added
+                .get_or_create(&labels)
+                .set(added.count as f64);
+            if let Some(timestamp) = added.last_added {
+                last_added.get_or_create(&labels).set(timestamp as f64);
+            }
+        });
+    }
+}
+
+impl FormatAsPrometheus for JellyfinStorageResult {
+    fn format_as_prometheus(&self, registry: &mut MetricRegistry) {
+        debug!("Formatting {self:?} as Prometheus");
+        let free_bytes: GaugeFamily<StoragePathLabels> = registry.family(
+            "jellyfin_storage_free_bytes",
+            "Jellyfin library path free space",
+        );
+        let used_bytes: GaugeFamily<StoragePathLabels> = registry.family(
+            "jellyfin_storage_used_bytes",
+            "Jellyfin library path used space",
+        );
+        self.paths.iter().for_each(|path| {
+            let labels = StoragePathLabels {
+                name: self.name.clone(),
+                library_name: path.library_name.clone(),
+                path: path.path.clone(),
+            };
+            free_bytes
+                .get_or_create(&labels)
+                .set(path.free_bytes as f64);
+            used_bytes
+                .get_or_create(&labels)
+                .set(path.used_bytes as f64);
+        });
+    }
+}
+
 impl FormatAsPrometheus for LibraryResult {
-    fn format_as_prometheus(&self, registry: &mut Registry) {
+    fn format_as_prometheus(&self, registry: &mut MetricRegistry) {
         debug!("Formatting {self:?} as Prometheus");
-        let movie_count_label = Family::<EmptyLabel, Gauge<f64, AtomicU64>>::default();
-        let show_count_label = Family::<EmptyLabel, Gauge<f64, AtomicU64>>::default();
-        let season_count_label = Family::<EmptyLabel, Gauge<f64, AtomicU64>>::default();
-        let episode_count_label = Family::<EmptyLabel, Gauge<f64, AtomicU64>>::default();
-        let show_library_label = Family::<PlexShowLabels, Gauge<f64, AtomicU64>>::default();
-        let library_label = Family::<PlexLibraryLabels, Gauge<f64, AtomicU64>>::default();
+        let (kind, title) = match self.kind.as_str() {
+            "plex" => ("plex", "Plex"),
+            "jellyfin" => ("jellyfin", "Jellyfin"),
+            _ => return,
+        };
+        let movie_count_label: GaugeFamily<InstanceLabels> = registry.family(
+            format!("{kind}_movie_count"),
+            format!("{title} movie count"),
+        );
+        let show_count_label: GaugeFamily<InstanceLabels> =
+            registry.family(format!("{kind}_show_count"), format!("{title} show count"));
+        let season_count_label: GaugeFamily<InstanceLabels> = registry.family(
+            format!("{kind}_season_count"),
+            format!("{title} season count"),
+        );
+        let episode_count_label: GaugeFamily<InstanceLabels> = registry.family(
+            format!("{kind}_episode_count"),
+            format!("{title} episode count"),
+        );
+        let show_library_label: GaugeFamily<PlexShowLabels> = registry.family(
+            format!("{kind}_show_library"),
+            format!("{title} show library"),
+        );
+        let library_label: GaugeFamily<PlexLibraryLabels> =
+            registry.family(format!("{kind}_library"), format!("{title} library"));
+        let album_count_label: GaugeFamily<InstanceLabels> = registry.family(
+            format!("{kind}_album_count"),
+            format!("{title} album count"),
+        );
+        let artist_count_label: GaugeFamily<InstanceLabels> = registry.family(
+            format!("{kind}_artist_count"),
+            format!("{title} artist count"),
+        );
+        let song_count_label: GaugeFamily<InstanceLabels> =
+            registry.family(format!("{kind}_song_count"), format!("{title} song count"));
+        let book_count_label: GaugeFamily<InstanceLabels> =
+            registry.family(format!("{kind}_book_count"), format!("{title} book count"));
         let mut movie_count = 0;
         let mut episode_count = 0;
         let mut season_count = 0;
         let mut show_count = 0;
-        match self.kind.as_str() {
-            "plex" => {
-                registry.register(
-                    "plex_movie_count",
-                    "Plex movie count",
-                    movie_count_label.clone(),
-                );
-                registry.register(
-                    "plex_show_count",
-                    "Plex show count",
-                    show_count_label.clone(),
-                );
-                registry.register(
-                    "plex_season_count",
-                    "Plex season count",
-                    season_count_label.clone(),
-                );
-                registry.register(
-                    "plex_episode_count",
-                    "Plex episode count",
-                    episode_count_label.clone(),
-                );
-                registry.register(
-                    "plex_show_library",
-                    "Plex show library",
-                    show_library_label.clone(),
-                );
-                registry.register("plex_library", "Plex library", library_label.clone());
-            }
-            "jellyfin" => {
-                registry.register(
-                    "jellyfin_movie_count",
-                    "Jellyfin movie count",
-                    movie_count_label.clone(),
-                );
-                registry.register(
-                    "jellyfin_show_count",
-                    "Jellyfin show count",
-                    show_count_label.clone(),
-                );
-                registry.register(
-                    "jellyfin_episode_count",
-                    "Jellyfin episode count",
-                    episode_count_label.clone(),
-                );
-            }
-            _ => {}
-        }
+        let mut album_count = 0;
+        let mut artist_count = 0;
+        let mut song_count = 0;
+        let mut book_count = 0;
         self.libraries.iter().for_each(|lib: &LibraryCount| {
             let library_labels = PlexLibraryLabels {
                 name: self.name.clone(),
@@ -546,6 +2272,20 @@ impl FormatAsPrometheus for LibraryResult {
                     season_count += lib.child_count.unwrap_or(0);
                     show_count += lib.count
                 }
+                LibraryMediaType::Music => {
+                    album_count += lib.count;
+                    artist_count += lib.child_count.unwrap_or(0);
+                    song_count += lib.grand_child_count.unwrap_or(0);
+                    library_label
+                        .get_or_create(&library_labels)
+                        .set(lib.count as f64);
+                }
+                LibraryMediaType::Book => {
+                    book_count += lib.count;
+                    library_label
+                        .get_or_create(&library_labels)
+                        .set(lib.count as f64);
+                }
                 _ => {
                     library_label
                         .get_or_create(&library_labels)
@@ -553,17 +2293,204 @@ impl FormatAsPrometheus for LibraryResult {
                 }
             };
         });
+        let instance = InstanceLabels {
+            name: self.name.clone(),
+        };
         movie_count_label
-            .get_or_create(&EmptyLabel {})
+            .get_or_create(&instance)
             .set(movie_count as f64);
         show_count_label
-            .get_or_create(&EmptyLabel {})
+            .get_or_create(&instance)
             .set(show_count as f64);
         season_count_label
-            .get_or_create(&EmptyLabel {})
+            .get_or_create(&instance)
             .set(season_count as f64);
         episode_count_label
-            .get_or_create(&EmptyLabel {})
+            .get_or_create(&instance)
             .set(episode_count as f64);
+        album_count_label
+            .get_or_create(&instance)
+            .set(album_count as f64);
+        artist_count_label
+            .get_or_create(&instance)
+            .set(artist_count as f64);
+        song_count_label
+            .get_or_create(&instance)
+            .set(song_count as f64);
+        book_count_label
+            .get_or_create(&instance)
+            .set(book_count as f64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn parse_labels_reads_every_pair() {
+        assert_eq!(
+            parse_labels(r#"name="main",library="Movies""#),
+            labels(&[("name", "main"), ("library", "Movies")])
+        );
+        assert_eq!(parse_labels(""), BTreeMap::new());
+    }
+
+    #[test]
+    fn parse_labels_unescapes_values() {
+        assert_eq!(
+            parse_labels(r#"title="Say \"hi\"",path="C:\\Media",note="a\nb,c=d""#),
+            labels(&[
+                ("title", r#"Say "hi""#),
+                ("path", r"C:\Media"),
+                ("note", "a\nb,c=d"),
+            ])
+        );
+    }
+
+    #[test]
+    fn to_prometheus_text_renames_counters() {
+        let text = "\
+# HELP homers_plex_plays_observed Plays observed.
+# TYPE homers_plex_plays_observed counter
+homers_plex_plays_observed_total{name=\"main\"} 3 # {session=\"a # b\"} 1.0 1700000000.0
+homers_plex_plays_observed_created{name=\"main\"} 1700000000.0
+# HELP homers_uptime Uptime.
+# TYPE homers_uptime gauge
+# UNIT homers_uptime seconds
+homers_uptime 12.0
+# HELP homers_build Build.
+# TYPE homers_build unknown
+homers_build{version=\"1.0 # beta\"} 1
+# EOF
+";
+        assert_eq!(
+            to_prometheus_text(text),
+            "\
+# HELP homers_plex_plays_observed_total Plays observed.
+# TYPE homers_plex_plays_observed_total counter
+homers_plex_plays_observed_total{name=\"main\"} 3
+homers_plex_plays_observed_created{name=\"main\"} 1700000000.0
+# HELP homers_uptime Uptime.
+# TYPE homers_uptime gauge
+homers_uptime 12.0
+# HELP homers_build Build.
+# TYPE homers_build untyped
+homers_build{version=\"1.0 # beta\"} 1
+"
+        );
+    }
+
+    fn history(name: &str, ids: &[i64]) -> TaskResult {
+        TaskResult::TautulliHistory(TautulliHistoryResult {
+            name: name.to_string(),
+            plays: ids
+                .iter()
+                .map(|id| providers::tautulli::HistoryPlay {
+                    id: *id,
+                    user: "alice".to_string(),
+                    media_type: "movie".to_string(),
+                    duration: 60,
+                })
+                .collect(),
+        })
+    }
+
+    fn sample(text: &str, name: &str) -> Option<String> {
+        text.lines()
+            .find(|line| line.starts_with(name))
+            .and_then(|line| line.rsplit(' ').next())
+            .map(str::to_string)
+    }
+
+    #[test]
+    fn history_rows_are_counted_once() {
+        let mut registry = MetricRegistry::new(&WebhookMetrics::default(), None);
+        for _ in 0..2 {
+            let text = registry
+                .format(vec![history("once", &[3, 2, 1])], Format::Prometheus)
+                .unwrap();
+            assert_eq!(
+                sample(&text, "homers_tautulli_history_plays_total{").as_deref(),
+                Some("3")
+            );
+            assert_eq!(
+                sample(&text, "homers_tautulli_history_watch_seconds_total{").as_deref(),
+                Some("180")
+            );
+        }
+        // Only the row above the cursor is new.
+        let text = registry
+            .format(vec![history("once", &[4, 3, 2])], Format::Prometheus)
+            .unwrap();
+        assert_eq!(
+            sample(&text, "homers_tautulli_history_plays_total{").as_deref(),
+            Some("4")
+        );
+    }
+
+    #[test]
+    fn history_cursors_are_by_instance() {
+        let mut registry = MetricRegistry::new(&WebhookMetrics::default(), None);
+        registry
+            .format(vec![history("first", &[10])], Format::Prometheus)
+            .unwrap();
+        let text = registry
+            .format(vec![history("second", &[5])], Format::Prometheus)
+            .unwrap();
+        assert!(text.contains(
+            "homers_tautulli_history_plays_total{name=\"second\",user=\"alice\",media_type=\"movie\"} 1"
+        ));
+    }
+
+    #[test]
+    fn library_totals_are_labelled_by_instance() {
+        let library = |name: &str, media_type, count| LibraryCount {
+            name: name.to_string(),
+            media_type,
+            count,
+            child_count: Some(2),
+            grand_child_count: Some(20),
+        };
+        let result = |name: &str, libraries| {
+            TaskResult::PlexLibrary(LibraryResult {
+                name: name.to_string(),
+                kind: "plex".to_string(),
+                libraries,
+            })
+        };
+        let text = format_metrics(
+            vec![
+                result(
+                    "home",
+                    vec![
+                        library("Movies", LibraryMediaType::Movie, 3),
+                        library("Shows", LibraryMediaType::Show, 4),
+                    ],
+                ),
+                result("cabin", vec![library("Movies", LibraryMediaType::Movie, 5)]),
+            ],
+            &WebhookMetrics::default(),
+            None,
+            Format::Prometheus,
+        )
+        .unwrap();
+        for sample in [
+            "homers_plex_movie_count{name=\"home\"} 3",
+            "homers_plex_movie_count{name=\"cabin\"} 5",
+            "homers_plex_show_count{name=\"home\"} 4",
+            "homers_plex_season_count{name=\"home\"} 2",
+            "homers_plex_episode_count{name=\"home\"} 20",
+            "homers_plex_show_count{name=\"cabin\"} 0",
+        ] {
+            assert!(text.contains(sample), "{sample} missing from {text}");
+        }
     }
 }
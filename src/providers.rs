@@ -1,3 +1,4 @@
+pub mod geoip;
 pub mod jellyfin;
 pub mod overseerr;
 pub mod plex;
@@ -7,6 +8,349 @@ pub mod structs;
 pub mod tautulli;
 pub mod unifi;
 
+use async_trait::async_trait;
+use log::warn;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::reporting;
+use crate::tasks::{TaskKind, TaskResult};
+use std::cell::Cell;
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// Items dropped from list responses since startup, by item type.
+static SKIPPED_ITEMS: Mutex<BTreeMap<&'static str, u64>> = Mutex::new(BTreeMap::new());
+
+const DEFAULT_TIMEOUT_SECONDS: u64 = 10;
+const DEFAULT_CONNECT_TIMEOUT_SECONDS: u64 = 5;
+
+// HTTP client settings shared by every provider block.
+#[derive(Debug, Deserialize, Clone, Serialize, Default)]
+pub struct ClientOptions {
+    pub proxy_url: Option<String>,
+    pub no_proxy: Option<String>,
+    pub tls: Option<ClientTls>,
+    pub headers: Option<HashMap<String, String>>,
+    // Seconds, for each request to the provider.
+    pub timeout: Option<u64>,
+    pub connect_timeout: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize, Default)]
+pub struct ClientTls {
+    #[serde(default)]
+    pub insecure_skip_verify: bool,
+    pub ca_file: Option<PathBuf>,
+}
+impl ClientOptions {
+    pub fn apply(
+        &self,
+        builder: reqwest::ClientBuilder,
+    ) -> Result<reqwest::ClientBuilder, ProviderError> {
+        // Without them a dead host or a DNS blackhole holds the task until the
+        // scrape times out, if it ever does.
+        let builder = builder
+            .timeout(Duration::from_secs(
+                self.timeout.unwrap_or(DEFAULT_TIMEOUT_SECONDS),
+            ))
+            .connect_timeout(Duration::from_secs(
+                self.connect_timeout
+                    .unwrap_or(DEFAULT_CONNECT_TIMEOUT_SECONDS),
+            ));
+        let builder = match &self.proxy_url {
+            Some(proxy_url) => {
+                let no_proxy = self
+                    .no_proxy
+                    .as_deref()
+                    .and_then(reqwest::NoProxy::from_string);
+                builder.proxy(reqwest::Proxy::all(proxy_url)?.no_proxy(no_proxy))
+            }
+            None => builder,
+        };
+        let builder = match &self.tls {
+            Some(tls) => {
+                let mut builder = builder.danger_accept_invalid_certs(tls.insecure_skip_verify);
+                if let Some(ca_file) = &tls.ca_file {
+                    let pem = std::fs::read(ca_file).map_err(|e| {
+                        ProviderError::new(
+                            Provider::Reqwest,
+                            ProviderErrorKind::GetError,
+                            &format!("Failed to read CA file {ca_file:?}: {e}"),
+                        )
+                    })?;
+                    for certificate in reqwest::Certificate::from_pem_bundle(&pem)? {
+                        builder = builder.add_root_certificate(certificate);
+                    }
+                }
+                builder
+            }
+            None => builder,
+        };
+        let builder = match &self.headers {
+            // Added on top of the provider headers, a header set here wins.
+            Some(headers) => {
+                let mut map = reqwest::header::HeaderMap::new();
+                for (name, value) in headers {
+                    let name = reqwest::header::HeaderName::from_bytes(name.as_bytes())
+                        .map_err(|e| header_error(name, e))?;
+                    let value = reqwest::header::HeaderValue::from_str(value)
+                        .map_err(|e| header_error(name.as_str(), e))?;
+                    map.insert(name, value);
+                }
+                builder.default_headers(map)
+            }
+            None => builder,
+        };
+        Ok(builder)
+    }
+}
+
+// Path of a request with the ids masked, and the Tautulli command, e.g.
+// "/library/sections/{id}/all" or "/api/v2?cmd=get_activity".
+fn endpoint(url: &reqwest::Url) -> String {
+    let mut endpoint = String::new();
+    for segment in url.path_segments().into_iter().flatten() {
+        let id = segment.chars().any(|c| c.is_ascii_digit())
+            && segment.chars().all(|c| c.is_ascii_hexdigit() || c == '-');
+        endpoint.push('/');
+        endpoint.push_str(if id { "{id}" } else { segment });
+    }
+    if let Some((_, command)) = url.query_pairs().find(|(key, _)| key == "cmd") {
+        endpoint.push_str(&format!("?cmd={command}"));
+    }
+    endpoint
+}
+
+/// `send` that records the latency of the request by provider and endpoint,
+/// failed requests included.
+pub trait SendTimed {
+    fn send_timed(
+        self,
+        provider: Provider,
+    ) -> impl Future<Output = reqwest::Result<reqwest::Response>> + Send;
+}
+impl SendTimed for reqwest::RequestBuilder {
+    async fn send_timed(self, provider: Provider) -> reqwest::Result<reqwest::Response> {
+        let (client, request) = self.build_split();
+        let request = request?;
+        let endpoint = endpoint(request.url());
+        let start = Instant::now();
+        let response = client.execute(request).await;
+        crate::prometheus::observe_provider_request(
+            &provider.to_string().to_lowercase(),
+            endpoint,
+            start.elapsed().as_secs_f64(),
+        );
+        response
+    }
+}
+
+// Used by `homers check` to test that a provider answers with the configured credentials.
+pub async fn check_endpoint(
+    provider: Provider,
+    client: &reqwest::Client,
+    url: &str,
+) -> Result<(), ProviderError> {
+    let response = match client.get(url).send().await {
+        Ok(response) => response,
+        Err(e) => {
+            return Err(ProviderError::new(
+                provider,
+                ProviderErrorKind::GetError,
+                &format!("{}", e),
+            ));
+        }
+    };
+    match response.error_for_status() {
+        Ok(_) => Ok(()),
+        Err(e) => Err(ProviderError::new(
+            provider,
+            ProviderErrorKind::GetError,
+            &format!("{}", e),
+        )),
+    }
+}
+
+// Used by `homers debug` to dump the raw payload of any endpoint, JSON is pretty printed.
+pub async fn get_raw(
+    provider: Provider,
+    client: &reqwest::Client,
+    url: &str,
+) -> Result<String, ProviderError> {
+    let response = match client.get(url).send().await {
+        Ok(response) => response,
+        Err(e) => {
+            return Err(ProviderError::new(
+                provider,
+                ProviderErrorKind::GetError,
+                &format!("{}", e),
+            ));
+        }
+    };
+    let response = match response.error_for_status() {
+        Ok(response) => response,
+        Err(e) => {
+            return Err(ProviderError::new(
+                provider,
+                ProviderErrorKind::GetError,
+                &format!("{}", e),
+            ));
+        }
+    };
+    let body = match response.text().await {
+        Ok(body) => body,
+        Err(e) => {
+            return Err(ProviderError::new(
+                provider,
+                ProviderErrorKind::ParseError,
+                &format!("{}", e),
+            ));
+        }
+    };
+    match serde_json::from_str::<serde_json::Value>(&body) {
+        Ok(json) => Ok(serde_json::to_string_pretty(&json).unwrap_or(body)),
+        Err(_) => Ok(body),
+    }
+}
+
+#[derive(Debug)]
+pub enum JsonError {
+    Body(reqwest::Error),
+    Parse(serde_json::Error),
+}
+impl std::fmt::Display for JsonError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JsonError::Body(e) => write!(f, "Failed to read response body: {}", e),
+            JsonError::Parse(e) => write!(f, "Failed to parse response: {}", e),
+        }
+    }
+}
+impl std::error::Error for JsonError {}
+impl JsonError {
+    pub fn reason(&self) -> &'static str {
+        match self {
+            JsonError::Body(_) => "request",
+            JsonError::Parse(_) => "parse",
+        }
+    }
+}
+impl From<JsonError> for ProviderError {
+    fn from(e: JsonError) -> ProviderError {
+        ProviderError::new(
+            Provider::Reqwest,
+            ProviderErrorKind::ParseError,
+            &format!("{:?}", e),
+        )
+    }
+}
+
+// Same as `response.json()`, but the body is kept to report schema mismatches.
+pub async fn parse_json<T: DeserializeOwned>(
+    provider: Provider,
+    response: reqwest::Response,
+) -> Result<T, JsonError> {
+    let url = response.url().clone();
+    let body = response.text().await.map_err(JsonError::Body)?;
+    serde_json::from_str(&body).map_err(|e| {
+        reporting::report_parse_error(&provider, &url, &body, &e);
+        JsonError::Parse(e)
+    })
+}
+
+tokio::task_local! {
+    // First failure of the task being collected, see `record_failure`.
+    static FAILURE: Cell<Option<&'static str>>;
+}
+
+/// Runs a collection task, along with the reason of its first failure if any.
+pub async fn track_failure<F: Future>(future: F) -> (F::Output, Option<&'static str>) {
+    FAILURE
+        .scope(Cell::new(None), async {
+            let output = future.await;
+            (output, FAILURE.with(Cell::get))
+        })
+        .await
+}
+
+/// Marks the task being collected as failed. The provider methods log their
+/// errors and return empty data, which would otherwise look like no data.
+pub fn record_failure(reason: &'static str) {
+    let _ = FAILURE.try_with(|failure| {
+        if failure.get().is_none() {
+            failure.set(Some(reason));
+        }
+    });
+}
+
+/// Number of list items that failed to deserialize since startup, by item type.
+pub fn skipped_items() -> Vec<(&'static str, u64)> {
+    match SKIPPED_ITEMS.lock() {
+        Ok(skipped) => skipped
+            .iter()
+            .map(|(item, count)| (*item, *count))
+            .collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+// e.g. "tautulli::Session" for the Tautulli sessions.
+fn item_name<T>() -> &'static str {
+    let name = std::any::type_name::<T>();
+    name.strip_prefix("homers::providers::structs::")
+        .unwrap_or(name)
+}
+
+// Converts the items one by one, an item that does not match the schema is
+// logged and counted instead of failing the whole list.
+fn from_values<T: DeserializeOwned>(values: Vec<serde_json::Value>) -> Vec<T> {
+    values
+        .into_iter()
+        .filter_map(|value| match serde_json::from_value(value) {
+            Ok(item) => Some(item),
+            Err(e) => {
+                let item = item_name::<T>();
+                warn!("Skipping {item} that failed to parse: {e}");
+                if let Ok(mut skipped) = SKIPPED_ITEMS.lock() {
+                    *skipped.entry(item).or_default() += 1;
+                }
+                None
+            }
+        })
+        .collect()
+}
+
+/// `deserialize_with` for list fields, see `from_values`. A null list is empty.
+pub fn deserialize_items<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: DeserializeOwned,
+{
+    let values = Option::<Vec<serde_json::Value>>::deserialize(deserializer)?;
+    Ok(from_values(values.unwrap_or_default()))
+}
+
+// Same as `parse_json` for endpoints returning a JSON array, see `from_values`.
+pub async fn parse_json_list<T: DeserializeOwned>(
+    provider: Provider,
+    response: reqwest::Response,
+) -> Result<Vec<T>, JsonError> {
+    let values: Vec<serde_json::Value> = parse_json(provider, response).await?;
+    Ok(from_values(values))
+}
+
+fn header_error(name: &str, e: impl std::fmt::Debug) -> ProviderError {
+    ProviderError::new(
+        Provider::Reqwest,
+        ProviderErrorKind::HeaderError,
+        &format!("Invalid header {name}: {e:?}"),
+    )
+}
+
 #[derive(Debug)]
 pub enum ProviderErrorKind {
     GetError,
@@ -40,6 +384,25 @@ impl std::fmt::Display for Provider {
     }
 }
 
+/// Implemented by every provider client. `Task` goes through it for everything,
+/// a new provider only adds its client and `get_tasks` the block building it.
+#[async_trait]
+pub trait MetricProvider: Send + Sync + std::fmt::Debug {
+    // Provider label of the metrics, e.g. `sonarr`.
+    fn provider(&self) -> &'static str;
+    fn name(&self) -> &str;
+    fn address(&self) -> &str;
+    // A single authenticated request, for `check --live` and /ready.
+    async fn check(&self) -> Result<(), ProviderError>;
+    async fn version(&self) -> Result<String, ProviderError>;
+    // Raw payload of an endpoint, a status endpoint by default.
+    async fn debug(&self, endpoint: Option<&str>) -> Result<String, ProviderError>;
+    // Tasks enabled in the block, each one is collected on its own.
+    fn tasks(&self) -> Vec<TaskKind>;
+    // Results of `task`, one of `tasks`.
+    async fn collect(&self, task: TaskKind) -> Result<Vec<TaskResult>, ProviderError>;
+}
+
 #[derive(Debug)]
 pub struct ProviderError {
     provider: Provider,
@@ -54,6 +417,14 @@ impl ProviderError {
             message: message.to_string(),
         }
     }
+    // Value of the reason label of homers_collection_success.
+    pub fn reason(&self) -> &'static str {
+        match self.kind {
+            ProviderErrorKind::GetError => "request",
+            ProviderErrorKind::HeaderError => "header",
+            ProviderErrorKind::ParseError => "parse",
+        }
+    }
 }
 impl std::fmt::Display for ProviderError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -90,3 +461,48 @@ impl From<reqwest::Error> for ProviderError {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Item {
+        id: i64,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Items {
+        #[serde(default, deserialize_with = "deserialize_items")]
+        items: Vec<Item>,
+    }
+
+    fn skipped() -> u64 {
+        skipped_items()
+            .into_iter()
+            .find(|(item, _)| *item == item_name::<Item>())
+            .map(|(_, count)| count)
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn bad_items_are_skipped_and_counted() {
+        let before = skipped();
+        let list: Items = serde_json::from_value(json!({
+            "items": [{"id": 1}, {"id": "two"}, {}, {"id": 3}],
+        }))
+        .unwrap();
+        assert_eq!(list.items, [Item { id: 1 }, Item { id: 3 }]);
+        assert_eq!(skipped() - before, 2);
+    }
+
+    #[test]
+    fn null_or_missing_lists_are_empty() {
+        let list: Items = serde_json::from_value(json!({"items": null})).unwrap();
+        assert!(list.items.is_empty());
+        let list: Items = serde_json::from_value(json!({})).unwrap();
+        assert!(list.items.is_empty());
+        assert!(serde_json::from_value::<Items>(json!({"items": {"id": 1}})).is_err());
+    }
+}
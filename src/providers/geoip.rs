@@ -0,0 +1,250 @@
+use ipnet::IpNet;
+use log::debug;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+
+use crate::config::GeoipOptions;
+use crate::providers::structs::{is_lan_address, parse_ip, Location};
+
+// A scrape waits at most this long for the locations, including the time
+// spent waiting for one of the concurrent lookups to finish.
+const LOOKUP_TIMEOUT: Duration = Duration::from_secs(1);
+const CONCURRENT_LOOKUPS: usize = 4;
+
+#[derive(Debug, Deserialize, Clone, Copy, Serialize, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum GeoipService {
+    #[default]
+    IpApi,
+    IpApiCo,
+    IpWhois,
+    FreeGeoIp,
+}
+
+impl GeoipService {
+    // Paid tiers of ip-api.com and ipwhois are served from another host.
+    fn url(&self, ip: &str, api_key: Option<&str>) -> String {
+        match (self, api_key) {
+            (GeoipService::IpApi, None) => format!("http://ip-api.com/json/{ip}"),
+            (GeoipService::IpApi, Some(key)) => {
+                format!("https://pro.ip-api.com/json/{ip}?key={key}")
+            }
+            (GeoipService::IpApiCo, None) => format!("https://ipapi.co/{ip}/json/"),
+            (GeoipService::IpApiCo, Some(key)) => format!("https://ipapi.co/{ip}/json/?key={key}"),
+            (GeoipService::IpWhois, None) => format!("https://ipwho.is/{ip}"),
+            (GeoipService::IpWhois, Some(key)) => format!("https://ipwhois.pro/{ip}?key={key}"),
+            (GeoipService::FreeGeoIp, None) => format!("https://api.freegeoip.app/json/{ip}"),
+            (GeoipService::FreeGeoIp, Some(key)) => {
+                format!("https://api.freegeoip.app/json/{ip}?apikey={key}")
+            }
+        }
+    }
+    // Names of the city, country, latitude and longitude fields.
+    fn fields(&self) -> [&'static str; 4] {
+        match self {
+            GeoipService::IpApi => ["city", "country", "lat", "lon"],
+            GeoipService::IpApiCo => ["city", "country_name", "latitude", "longitude"],
+            GeoipService::IpWhois => ["city", "country", "latitude", "longitude"],
+            GeoipService::FreeGeoIp => ["city", "country_name", "latitude", "longitude"],
+        }
+    }
+}
+
+impl std::fmt::Display for GeoipService {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GeoipService::IpApi => write!(f, "ipapi"),
+            GeoipService::IpApiCo => write!(f, "ipapico"),
+            GeoipService::IpWhois => write!(f, "ipwhois"),
+            GeoipService::FreeGeoIp => write!(f, "freegeoip"),
+        }
+    }
+}
+
+// Results by address, with the time they were resolved at.
+type Cache<T> = Arc<Mutex<HashMap<String, (Instant, T)>>>;
+
+/// Resolves the public address of a session to a city and coordinates.
+///
+/// Clones share the same cache, so an address seen by Plex, Jellyfin and
+/// Tautulli is only resolved once per `cache_ttl`.
+#[derive(Debug, Clone)]
+pub struct Geolocator {
+    enabled: bool,
+    service: GeoipService,
+    api_key: Option<String>,
+    lan_networks: Vec<IpNet>,
+    lan_label: String,
+    cache_ttl: Duration,
+    cache: Cache<Location>,
+    lookups: Arc<Semaphore>,
+    client: reqwest::Client,
+    reverse_dns: bool,
+    hostnames: Cache<Option<String>>,
+}
+
+// Shared address space of carrier-grade NAT (RFC 6598), not routable either.
+fn is_cgnat_address(address: &str) -> bool {
+    match parse_ip(address) {
+        Some(std::net::IpAddr::V4(ip)) => ip.octets()[0] == 100 && (ip.octets()[1] & 0xc0) == 64,
+        _ => false,
+    }
+}
+
+impl Default for Geolocator {
+    fn default() -> Self {
+        Geolocator::new(&GeoipOptions::default())
+    }
+}
+
+impl Geolocator {
+    pub fn new(options: &GeoipOptions) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(LOOKUP_TIMEOUT)
+            .build()
+            .unwrap_or_default();
+        Geolocator {
+            enabled: options.enabled,
+            service: options.service,
+            api_key: options.api_key.clone().filter(|key| !key.is_empty()),
+            lan_networks: options.lan_networks.clone(),
+            lan_label: options.lan_label(),
+            cache_ttl: options.cache_ttl(),
+            cache: Arc::new(Mutex::new(HashMap::new())),
+            lookups: Arc::new(Semaphore::new(CONCURRENT_LOOKUPS)),
+            client,
+            reverse_dns: options.reverse_dns,
+            hostnames: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+    fn cached(&self, ip: &str) -> Option<Location> {
+        let cache = self.cache.lock().ok()?;
+        match cache.get(ip) {
+            Some((resolved_at, location)) if resolved_at.elapsed() < self.cache_ttl => {
+                Some(location.clone())
+            }
+            _ => None,
+        }
+    }
+    fn store(&self, ip: &str, location: &Location) {
+        if let Ok(mut cache) = self.cache.lock() {
+            cache.retain(|_, (resolved_at, _)| resolved_at.elapsed() < self.cache_ttl);
+            cache.insert(ip.to_string(), (Instant::now(), location.clone()));
+        }
+    }
+    async fn lookup(&self, ip: &str) -> anyhow::Result<Location> {
+        let _permit = self.lookups.acquire().await?;
+        let url = self.service.url(ip, self.api_key.as_deref());
+        // The URL may carry the API key, keep it out of the logs.
+        let body: Value = self
+            .client
+            .get(url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| e.without_url())?
+            .json()
+            .await
+            .map_err(|e| e.without_url())?;
+        // Coordinates are numbers for most services, strings for some.
+        let field = |name: &str| match &body[name] {
+            Value::String(value) => Some(value.clone()),
+            Value::Number(value) => Some(value.to_string()),
+            _ => None,
+        };
+        let [city, country, latitude, longitude] = self.service.fields();
+        match (
+            field(city),
+            field(country),
+            field(latitude),
+            field(longitude),
+        ) {
+            (Some(city), Some(country), Some(latitude), Some(longitude)) => Ok(Location {
+                city,
+                country,
+                ip_address: ip.to_string(),
+                latitude,
+                longitude,
+            }),
+            _ => anyhow::bail!("unexpected {} response: {body}", self.service),
+        }
+    }
+    // Lookup failures are reported as "Unknown" and retried on the next scrape,
+    // disabled lookups and missing addresses leave the location empty and
+    // private addresses are labelled without asking the service.
+    pub async fn locate(&self, ip: &str) -> Location {
+        if !self.enabled || parse_ip(ip).is_none() {
+            return Location::empty(ip);
+        }
+        if is_lan_address(ip, &self.lan_networks) || is_cgnat_address(ip) {
+            return Location {
+                city: self.lan_label.clone(),
+                country: self.lan_label.clone(),
+                ..Location::empty(ip)
+            };
+        }
+        if let Some(location) = self.cached(ip) {
+            return location;
+        }
+        let result = match tokio::time::timeout(LOOKUP_TIMEOUT, self.lookup(ip)).await {
+            Ok(result) => result,
+            Err(_) => Err(anyhow::anyhow!("no answer after {LOOKUP_TIMEOUT:?}")),
+        };
+        match result {
+            Ok(location) => {
+                self.store(ip, &location);
+                location
+            }
+            Err(e) => {
+                debug!("Failed to locate {ip} with {}: {e}", self.service);
+                Location {
+                    city: "Unknown".to_string(),
+                    country: "Unknown".to_string(),
+                    ip_address: ip.to_string(),
+                    latitude: "0.0".to_string(),
+                    longitude: "0.0".to_string(),
+                }
+            }
+        }
+    }
+    // Addresses without a PTR record are cached too, most LAN devices have none.
+    pub async fn hostname(&self, address: &str) -> Option<String> {
+        if !self.reverse_dns {
+            return None;
+        }
+        let ip = parse_ip(address)?;
+        if let Ok(hostnames) = self.hostnames.lock() {
+            if let Some((resolved_at, hostname)) = hostnames.get(address) {
+                if resolved_at.elapsed() < self.cache_ttl {
+                    return hostname.clone();
+                }
+            }
+        }
+        // getnameinfo blocks, the thread is left to finish on timeout.
+        let hostname = match tokio::time::timeout(
+            LOOKUP_TIMEOUT,
+            tokio::task::spawn_blocking(move || dns_lookup::lookup_addr(&ip)),
+        )
+        .await
+        {
+            Ok(Ok(Ok(hostname))) => Some(hostname),
+            Ok(Ok(Err(e))) => {
+                debug!("No hostname for {address}: {e}");
+                None
+            }
+            _ => {
+                debug!("No hostname for {address} after {LOOKUP_TIMEOUT:?}");
+                return None;
+            }
+        };
+        if let Ok(mut hostnames) = self.hostnames.lock() {
+            hostnames.retain(|_, (resolved_at, _)| resolved_at.elapsed() < self.cache_ttl);
+            hostnames.insert(address.to_string(), (Instant::now(), hostname.clone()));
+        }
+        hostname
+    }
+}
@@ -1,13 +1,78 @@
-use crate::providers::structs::AsyncFrom;
-use log::error;
+use crate::providers::geoip::Geolocator;
+use crate::providers::structs::is_lan_address;
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use futures::future::join_all;
+use ipnet::IpNet;
+use log::{debug, error};
+use once_cell::sync::Lazy;
 use reqwest::header;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Instant;
+use tracing::instrument;
 
+pub use crate::providers::structs::jellyfin::WebhookPayload;
 use crate::providers::structs::jellyfin::{
-    JellyfinLibraryCounts, SessionResponse, User as JellyfinUser,
+    DevicesResponse, ItemsResponse, JellyfinLibraryCounts, LibraryInfos, Package, Plugin,
+    PublicSystemInfo, SessionResponse, StorageInfo, SystemInfo, User as JellyfinUser,
+    VirtualFolder,
 };
 use crate::providers::structs::{LibraryCount, Session, User};
-use crate::providers::{Provider, ProviderError, ProviderErrorKind};
+use crate::providers::{
+    check_endpoint, get_raw, parse_json, parse_json_list, record_failure, ClientOptions,
+    MetricProvider, Provider, ProviderError, ProviderErrorKind, SendTimed,
+};
+use crate::tasks::{
+    enabled, JellyfinDevicesResult, JellyfinInfoResult, JellyfinPluginsResult,
+    JellyfinRecentlyAddedResult, JellyfinStorageResult, LibraryResult, SessionResult, TaskKind,
+    TaskResult,
+};
+
+const SESSIONS: TaskKind = TaskKind {
+    name: "jellyfin_session",
+    flag: Some("sessions"),
+};
+const LIBRARY: TaskKind = TaskKind {
+    name: "jellyfin_library",
+    flag: Some("library"),
+};
+const DEVICES: TaskKind = TaskKind {
+    name: "jellyfin_devices",
+    flag: Some("devices"),
+};
+const PLUGINS: TaskKind = TaskKind {
+    name: "jellyfin_plugins",
+    flag: Some("plugins"),
+};
+const INFO: TaskKind = TaskKind {
+    name: "jellyfin_info",
+    flag: Some("info"),
+};
+const RECENTLY_ADDED: TaskKind = TaskKind {
+    name: "jellyfin_recently_added",
+    flag: Some("recently_added"),
+};
+const STORAGE: TaskKind = TaskKind {
+    name: "jellyfin_storage",
+    flag: Some("storage"),
+};
+
+// Item types counted by `recently_added`, as Jellyfin names them.
+const RECENTLY_ADDED_TYPES: [&str; 6] =
+    ["Movie", "Series", "Episode", "MusicAlbum", "Audio", "Book"];
+const RECENTLY_ADDED_PAGE_SIZE: usize = 500;
+// /Packages makes Jellyfin fetch every plugin repository manifest, the
+// packages are only refreshed every six hours.
+const PACKAGES_TTL_SECONDS: u64 = 6 * 60 * 60;
+
+// Packages of an instance with the time they were fetched.
+type FetchedPackages = (Instant, Vec<Package>);
+
+// By instance address.
+static PACKAGES: Lazy<Mutex<HashMap<String, FetchedPackages>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
 
 #[derive(Debug, Deserialize, Clone, Serialize)]
 pub struct Jellyfin {
@@ -16,12 +81,105 @@ pub struct Jellyfin {
     pub address: String,
     #[serde(rename = "apikey")]
     pub api_key: String,
+    #[serde(default)]
+    pub lan_networks: Vec<IpNet>,
+    pub recently_added_hours: Option<i64>,
+    pub sessions: Option<bool>,
+    pub library: Option<bool>,
+    pub devices: Option<bool>,
+    pub plugins: Option<bool>,
+    pub info: Option<bool>,
+    pub recently_added: Option<bool>,
+    pub storage: Option<bool>,
+    #[serde(flatten)]
+    pub client_options: ClientOptions,
     #[serde(skip)]
     client: reqwest::Client,
+    #[serde(skip)]
+    pub geoip: Geolocator,
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize)]
+pub struct JellyfinClient {
+    pub client: String,
+    pub platform: String,
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize)]
+pub struct JellyfinPlugin {
+    pub name: String,
+    pub version: String,
+    pub status: String,
+    pub update_available: bool,
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize)]
+pub struct JellyfinServerInfo {
+    pub version: String,
+    pub product: String,
+    pub operating_system: String,
+    pub startup_wizard_completed: bool,
+    pub restart_pending: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize)]
+pub struct JellyfinRecentlyAdded {
+    pub media_type: String,
+    pub count: i64,
+    pub last_added: Option<i64>,
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize)]
+pub struct JellyfinStoragePath {
+    pub library_name: String,
+    pub path: String,
+    pub free_bytes: i64,
+    pub used_bytes: i64,
+}
+
+// Operating system of a client from its name, e.g. "Jellyfin Android" or
+// "Swiftfin iOS". Device names are left out, they are one series per device.
+fn client_platform(client: &str) -> String {
+    let client = client.to_lowercase();
+    let platform = [
+        ("android", "Android"),
+        ("ios", "iOS"),
+        ("swiftfin", "iOS"),
+        ("infuse", "iOS"),
+        ("tvos", "tvOS"),
+        ("roku", "Roku"),
+        ("tizen", "Tizen"),
+        ("samsung", "Tizen"),
+        ("webos", "webOS"),
+        ("kodi", "Kodi"),
+        ("xbox", "Xbox"),
+        ("media player", "Desktop"),
+        ("theater", "Desktop"),
+        ("web", "Web"),
+        ("dlna", "DLNA"),
+    ]
+    .into_iter()
+    .find(|(needle, _)| client.contains(needle))
+    .map_or("Other", |(_, platform)| platform);
+    platform.to_string()
+}
+
+fn parse_version(version: &str) -> Vec<u64> {
+    version
+        .split('.')
+        .map(|part| part.parse::<u64>().unwrap_or(0))
+        .collect()
 }
 
 impl Jellyfin {
-    pub fn new(name: &str, address: &str, api_key: &str) -> Result<Jellyfin, ProviderError> {
+    pub fn new(
+        name: &str,
+        address: &str,
+        api_key: &str,
+        lan_networks: Vec<IpNet>,
+        recently_added_hours: i64,
+        options: &ClientOptions,
+    ) -> Result<Jellyfin, ProviderError> {
         let mut headers = header::HeaderMap::new();
         let header_str = format!("MediaBrowser Token=\"{}\"", api_key);
         let mut header_api_key = match header::HeaderValue::from_str(&header_str) {
@@ -36,20 +194,32 @@ impl Jellyfin {
         };
         header_api_key.set_sensitive(true);
         headers.insert(header::AUTHORIZATION, header_api_key);
-        let client = reqwest::Client::builder()
-            .default_headers(headers)
+        let client = options
+            .apply(reqwest::Client::builder().default_headers(headers))?
             .build()?;
         Ok(Jellyfin {
             name: name.to_string(),
             address: address.to_string(),
             api_key: api_key.to_string(),
+            lan_networks,
+            recently_added_hours: Some(recently_added_hours),
+            sessions: None,
+            library: None,
+            devices: None,
+            plugins: None,
+            info: None,
+            recently_added: None,
+            storage: None,
+            client_options: options.clone(),
             client,
+            geoip: Geolocator::default(),
         })
     }
 
-    async fn get_sessions(&self) -> Result<Vec<Session>, ProviderError> {
+    #[instrument(skip_all)]
+    async fn fetch_sessions(&self) -> Result<Vec<SessionResponse>, ProviderError> {
         let url = format!("{}/Sessions", self.address);
-        let response = match self.client.get(&url).send().await {
+        let response = match self.client.get(&url).send_timed(Provider::Jellyfin).await {
             Ok(response) => response,
             Err(e) => {
                 return Err(ProviderError::new(
@@ -59,8 +229,68 @@ impl Jellyfin {
                 ));
             }
         };
-        let sessions: Vec<SessionResponse> = match response.json().await {
+        let sessions: Vec<SessionResponse> =
+            match parse_json_list(Provider::Jellyfin, response).await {
+                Ok(sessions) => sessions,
+                Err(e) => {
+                    return Err(ProviderError::new(
+                        Provider::Jellyfin,
+                        ProviderErrorKind::ParseError,
+                        &format!("{:?}", e),
+                    ));
+                }
+            };
+        Ok(sessions)
+    }
+    async fn get_sessions(&self) -> Result<Vec<Session>, ProviderError> {
+        let sessions = self.fetch_sessions().await?;
+        let mut jelly_sessions: Vec<Session> = Vec::new();
+        let locations = join_all(
+            sessions
+                .iter()
+                .map(|session| self.geoip.locate(&session.remote_end_point)),
+        )
+        .await;
+        let hostnames = join_all(
+            sessions
+                .iter()
+                .map(|session| self.geoip.hostname(&session.remote_end_point)),
+        )
+        .await;
+        for ((session, location), hostname) in sessions.into_iter().zip(locations).zip(hostnames) {
+            let mut session = Session::from(session);
+            session.location = location;
+            session.hostname = hostname;
+            session.local = is_lan_address(&session.address, &self.lan_networks);
+            jelly_sessions.push(session);
+        }
+        Ok(jelly_sessions)
+    }
+    pub async fn get_current_sessions(&self) -> Vec<Session> {
+        match self.get_sessions().await {
             Ok(sessions) => sessions,
+            Err(e) => {
+                error!("Failed to get sessions: {}", e);
+                record_failure(e.reason());
+                Vec::new()
+            }
+        }
+    }
+    #[instrument(skip_all)]
+    async fn get_devices(&self) -> Result<DevicesResponse, ProviderError> {
+        let url = format!("{}/Devices", self.address);
+        let response = match self.client.get(&url).send_timed(Provider::Jellyfin).await {
+            Ok(response) => response,
+            Err(e) => {
+                return Err(ProviderError::new(
+                    Provider::Jellyfin,
+                    ProviderErrorKind::GetError,
+                    &format!("{:?}", e),
+                ));
+            }
+        };
+        let devices: DevicesResponse = match parse_json(Provider::Jellyfin, response).await {
+            Ok(devices) => devices,
             Err(e) => {
                 return Err(ProviderError::new(
                     Provider::Jellyfin,
@@ -69,43 +299,414 @@ impl Jellyfin {
                 ));
             }
         };
-        let mut jelly_sessions: Vec<Session> = Vec::new();
-        for session in sessions {
-            let session = Session::from_async(session).await;
-            jelly_sessions.push(session);
+        Ok(devices)
+    }
+    pub async fn get_registered_devices(&self) -> Vec<JellyfinClient> {
+        match self.get_devices().await {
+            Ok(devices) => devices
+                .items
+                .into_iter()
+                .map(|device| JellyfinClient {
+                    platform: client_platform(&device.app_name),
+                    client: device.app_name,
+                })
+                .collect(),
+            Err(e) => {
+                error!("Failed to get devices: {}", e);
+                record_failure(e.reason());
+                Vec::new()
+            }
         }
-        Ok(jelly_sessions)
     }
-    pub async fn get_current_sessions(&self) -> Vec<Session> {
-        match self.get_sessions().await {
-            Ok(sessions) => sessions,
+    pub async fn get_connected_clients(&self) -> Vec<JellyfinClient> {
+        match self.fetch_sessions().await {
+            Ok(sessions) => sessions
+                .into_iter()
+                .map(|session| JellyfinClient {
+                    platform: client_platform(&session.client),
+                    client: session.client,
+                })
+                .collect(),
             Err(e) => {
                 error!("Failed to get sessions: {}", e);
+                record_failure(e.reason());
                 Vec::new()
             }
         }
     }
+    // One page of items, newest first.
+    #[instrument(skip_all)]
+    async fn get_latest_items(&self, start_index: usize) -> Result<ItemsResponse, ProviderError> {
+        let url = format!("{}/Items", self.address);
+        let start_index = start_index.to_string();
+        let item_types = RECENTLY_ADDED_TYPES.join(",");
+        let limit = RECENTLY_ADDED_PAGE_SIZE.to_string();
+        let params = [
+            ("Recursive", "true"),
+            ("SortBy", "DateCreated"),
+            ("SortOrder", "Descending"),
+            ("Fields", "DateCreated"),
+            ("IncludeItemTypes", &item_types),
+            ("StartIndex", &start_index),
+            ("Limit", &limit),
+        ];
+        let response = match self
+            .client
+            .get(&url)
+            .query(&params)
+            .send_timed(Provider::Jellyfin)
+            .await
+        {
+            Ok(response) => response,
+            Err(e) => {
+                return Err(ProviderError::new(
+                    Provider::Jellyfin,
+                    ProviderErrorKind::GetError,
+                    &format!("{:?}", e),
+                ));
+            }
+        };
+        let items: ItemsResponse = match parse_json(Provider::Jellyfin, response).await {
+            Ok(items) => items,
+            Err(e) => {
+                return Err(ProviderError::new(
+                    Provider::Jellyfin,
+                    ProviderErrorKind::ParseError,
+                    &format!("{:?}", e),
+                ));
+            }
+        };
+        Ok(items)
+    }
+    // Pages through the items until the first one older than the window,
+    // every type is reported even when nothing was added.
+    pub async fn get_recently_added(&self) -> Vec<JellyfinRecentlyAdded> {
+        let since = Utc::now() - Duration::hours(self.recently_added_hours.unwrap_or(24));
+        let mut recently_added: Vec<JellyfinRecentlyAdded> = RECENTLY_ADDED_TYPES
+            .iter()
+            .map(|media_type| JellyfinRecentlyAdded {
+                media_type: media_type.to_string(),
+                count: 0,
+                last_added: None,
+            })
+            .collect();
+        let mut start_index = 0;
+        loop {
+            let page = match self.get_latest_items(start_index).await {
+                Ok(page) => page,
+                Err(e) => {
+                    error!("Failed to get latest items: {}", e);
+                    record_failure(e.reason());
+                    return Vec::new();
+                }
+            };
+            let count = page.items.len();
+            let mut older = false;
+            for item in page.items {
+                let created = match item
+                    .date_created
+                    .as_deref()
+                    .and_then(|date| DateTime::parse_from_rfc3339(date).ok())
+                {
+                    Some(created) => created.with_timezone(&Utc),
+                    None => continue,
+                };
+                let Some(entry) = recently_added
+                    .iter_mut()
+                    .find(|entry| entry.media_type == item.type_field)
+                else {
+                    continue;
+                };
+                if created >= since {
+                    entry.count += 1;
+                } else {
+                    older = true;
+                }
+                if entry.last_added.unwrap_or(0) < created.timestamp() {
+                    entry.last_added = Some(created.timestamp());
+                }
+            }
+            start_index += count;
+            if older
+                || count < RECENTLY_ADDED_PAGE_SIZE
+                || start_index as i64 >= page.total_record_count
+            {
+                break;
+            }
+        }
+        recently_added
+    }
+    #[instrument(skip_all)]
+    async fn get_plugins(&self) -> Result<Vec<Plugin>, ProviderError> {
+        let url = format!("{}/Plugins", self.address);
+        let response = match self.client.get(&url).send_timed(Provider::Jellyfin).await {
+            Ok(response) => response,
+            Err(e) => {
+                return Err(ProviderError::new(
+                    Provider::Jellyfin,
+                    ProviderErrorKind::GetError,
+                    &format!("{:?}", e),
+                ));
+            }
+        };
+        let plugins: Vec<Plugin> = match parse_json_list(Provider::Jellyfin, response).await {
+            Ok(plugins) => plugins,
+            Err(e) => {
+                return Err(ProviderError::new(
+                    Provider::Jellyfin,
+                    ProviderErrorKind::ParseError,
+                    &format!("{:?}", e),
+                ));
+            }
+        };
+        Ok(plugins)
+    }
+    #[instrument(skip_all)]
+    async fn get_packages(&self) -> Result<Vec<Package>, ProviderError> {
+        let url = format!("{}/Packages", self.address);
+        let response = match self.client.get(&url).send_timed(Provider::Jellyfin).await {
+            Ok(response) => response,
+            Err(e) => {
+                return Err(ProviderError::new(
+                    Provider::Jellyfin,
+                    ProviderErrorKind::GetError,
+                    &format!("{:?}", e),
+                ));
+            }
+        };
+        let packages: Vec<Package> = match parse_json_list(Provider::Jellyfin, response).await {
+            Ok(packages) => packages,
+            Err(e) => {
+                return Err(ProviderError::new(
+                    Provider::Jellyfin,
+                    ProviderErrorKind::ParseError,
+                    &format!("{:?}", e),
+                ));
+            }
+        };
+        Ok(packages)
+    }
+    async fn get_cached_packages(&self) -> Result<Vec<Package>, ProviderError> {
+        let cached = PACKAGES.lock().ok().and_then(|packages| {
+            packages
+                .get(&self.address)
+                .filter(|(fetched, _)| fetched.elapsed().as_secs() < PACKAGES_TTL_SECONDS)
+                .map(|(_, packages)| packages.clone())
+        });
+        if let Some(packages) = cached {
+            return Ok(packages);
+        }
+        let packages = self.get_packages().await?;
+        if let Ok(mut cache) = PACKAGES.lock() {
+            cache.insert(self.address.clone(), (Instant::now(), packages.clone()));
+        }
+        Ok(packages)
+    }
+    pub async fn get_installed_plugins(&self) -> Vec<JellyfinPlugin> {
+        let plugins = match self.get_plugins().await {
+            Ok(plugins) => plugins,
+            Err(e) => {
+                error!("Failed to get plugins: {}", e);
+                record_failure(e.reason());
+                return Vec::new();
+            }
+        };
+        let packages = match self.get_cached_packages().await {
+            Ok(packages) => packages,
+            Err(e) => {
+                error!("Failed to get packages: {}", e);
+                record_failure(e.reason());
+                Vec::new()
+            }
+        };
+        plugins
+            .into_iter()
+            .map(|plugin| {
+                let installed = parse_version(&plugin.version);
+                let update_available = packages
+                    .iter()
+                    .find(|package| {
+                        package.guid.replace('-', "").to_lowercase()
+                            == plugin.id.replace('-', "").to_lowercase()
+                            || package.name == plugin.name
+                    })
+                    .map(|package| {
+                        package
+                            .versions
+                            .iter()
+                            .any(|available| parse_version(&available.version) > installed)
+                    })
+                    .unwrap_or(false);
+                JellyfinPlugin {
+                    name: plugin.name,
+                    version: plugin.version,
+                    status: plugin.status.unwrap_or_else(|| "Unknown".to_string()),
+                    update_available,
+                }
+            })
+            .collect()
+    }
+    #[instrument(skip_all)]
+    async fn get_public_system_info(&self) -> Result<PublicSystemInfo, ProviderError> {
+        let url = format!("{}/System/Info/Public", self.address);
+        let response = match self.client.get(&url).send_timed(Provider::Jellyfin).await {
+            Ok(response) => response,
+            Err(e) => {
+                return Err(ProviderError::new(
+                    Provider::Jellyfin,
+                    ProviderErrorKind::GetError,
+                    &format!("{:?}", e),
+                ));
+            }
+        };
+        let info: PublicSystemInfo = match parse_json(Provider::Jellyfin, response).await {
+            Ok(info) => info,
+            Err(e) => {
+                return Err(ProviderError::new(
+                    Provider::Jellyfin,
+                    ProviderErrorKind::ParseError,
+                    &format!("{:?}", e),
+                ));
+            }
+        };
+        Ok(info)
+    }
+    #[instrument(skip_all)]
+    async fn get_system_info(&self) -> Result<SystemInfo, ProviderError> {
+        let url = format!("{}/System/Info", self.address);
+        let response = match self.client.get(&url).send_timed(Provider::Jellyfin).await {
+            Ok(response) => response,
+            Err(e) => {
+                return Err(ProviderError::new(
+                    Provider::Jellyfin,
+                    ProviderErrorKind::GetError,
+                    &format!("{:?}", e),
+                ));
+            }
+        };
+        let info: SystemInfo = match parse_json(Provider::Jellyfin, response).await {
+            Ok(info) => info,
+            Err(e) => {
+                return Err(ProviderError::new(
+                    Provider::Jellyfin,
+                    ProviderErrorKind::ParseError,
+                    &format!("{:?}", e),
+                ));
+            }
+        };
+        Ok(info)
+    }
+    pub async fn get_server_info(&self) -> Option<JellyfinServerInfo> {
+        let public_info = match self.get_public_system_info().await {
+            Ok(info) => info,
+            Err(e) => {
+                error!("Failed to get public system info: {}", e);
+                record_failure(e.reason());
+                return None;
+            }
+        };
+        let system_info = match self.get_system_info().await {
+            Ok(info) => Some(info),
+            Err(e) => {
+                error!("Failed to get system info: {}", e);
+                record_failure(e.reason());
+                None
+            }
+        };
+        let operating_system = public_info
+            .operating_system
+            .or_else(|| {
+                system_info
+                    .as_ref()
+                    .and_then(|info| info.operating_system.clone())
+            })
+            .unwrap_or_else(|| "Unknown".to_string());
+        Some(JellyfinServerInfo {
+            version: public_info.version,
+            product: public_info
+                .product_name
+                .unwrap_or_else(|| "Jellyfin Server".to_string()),
+            operating_system,
+            startup_wizard_completed: public_info.startup_wizard_completed.unwrap_or(true),
+            restart_pending: system_info.map(|info| info.has_pending_restart),
+        })
+    }
+    #[instrument(skip_all)]
+    async fn get_storage_info(&self) -> Result<StorageInfo, ProviderError> {
+        let url = format!("{}/System/Info/Storage", self.address);
+        let response = match self.client.get(&url).send_timed(Provider::Jellyfin).await {
+            Ok(response) => response,
+            Err(e) => {
+                return Err(ProviderError::new(
+                    Provider::Jellyfin,
+                    ProviderErrorKind::GetError,
+                    &format!("{:?}", e),
+                ));
+            }
+        };
+        let storage: StorageInfo = match parse_json(Provider::Jellyfin, response).await {
+            Ok(storage) => storage,
+            Err(e) => {
+                return Err(ProviderError::new(
+                    Provider::Jellyfin,
+                    ProviderErrorKind::ParseError,
+                    &format!("{:?}", e),
+                ));
+            }
+        };
+        Ok(storage)
+    }
+    pub async fn get_storage_paths(&self) -> Vec<JellyfinStoragePath> {
+        let storage = match self.get_storage_info().await {
+            Ok(storage) => storage,
+            Err(e) => {
+                error!("Failed to get storage info: {}", e);
+                record_failure(e.reason());
+                return Vec::new();
+            }
+        };
+        storage
+            .libraries
+            .into_iter()
+            .flat_map(|library| {
+                let library_name = library.name;
+                library
+                    .folders
+                    .into_iter()
+                    .map(move |folder| JellyfinStoragePath {
+                        library_name: library_name.clone(),
+                        path: folder.path,
+                        free_bytes: folder.free_space,
+                        used_bytes: folder.used_space,
+                    })
+            })
+            .collect()
+    }
+    #[instrument(skip_all)]
     pub async fn get_users(&self) -> Vec<User> {
         let url = format!("{}/Users", self.address);
-        let response = match self.client.get(&url).send().await {
+        let response = match self.client.get(&url).send_timed(Provider::Jellyfin).await {
             Ok(response) => response,
             Err(e) => {
                 error!("Failed to get users: {}", e);
+                record_failure("request");
                 return Vec::new();
             }
         };
-        let users: Vec<JellyfinUser> = match response.json().await {
+        let users: Vec<JellyfinUser> = match parse_json_list(Provider::Jellyfin, response).await {
             Ok(users) => users,
             Err(e) => {
                 error!("Failed to parse users: {}", e);
+                record_failure(e.reason());
                 return Vec::new();
             }
         };
         users.into_iter().map(User::from).collect()
     }
+    #[instrument(skip_all)]
     async fn get_library_counts(&self) -> Result<JellyfinLibraryCounts, ProviderError> {
         let url = format!("{}/Items/Counts", self.address);
-        let response = match self.client.get(&url).send().await {
+        let response = match self.client.get(&url).send_timed(Provider::Jellyfin).await {
             Ok(response) => response,
             Err(e) => {
                 return Err(ProviderError::new(
@@ -115,8 +716,35 @@ impl Jellyfin {
                 ));
             }
         };
-        let library_counts: JellyfinLibraryCounts = match response.json().await {
-            Ok(library_counts) => library_counts,
+        let library_counts: JellyfinLibraryCounts =
+            match parse_json(Provider::Jellyfin, response).await {
+                Ok(library_counts) => library_counts,
+                Err(e) => {
+                    return Err(ProviderError::new(
+                        Provider::Jellyfin,
+                        ProviderErrorKind::ParseError,
+                        &format!("{:?}", e),
+                    ));
+                }
+            };
+        Ok(library_counts)
+    }
+    #[instrument(skip_all)]
+    async fn get_virtual_folders(&self) -> Result<Vec<VirtualFolder>, ProviderError> {
+        let url = format!("{}/Library/VirtualFolders", self.address);
+        let response = match self.client.get(&url).send_timed(Provider::Jellyfin).await {
+            Ok(response) => response,
+            Err(e) => {
+                return Err(ProviderError::new(
+                    Provider::Jellyfin,
+                    ProviderErrorKind::GetError,
+                    &format!("{:?}", e),
+                ));
+            }
+        };
+        let folders: Vec<VirtualFolder> = match parse_json_list(Provider::Jellyfin, response).await
+        {
+            Ok(folders) => folders,
             Err(e) => {
                 return Err(ProviderError::new(
                     Provider::Jellyfin,
@@ -125,14 +753,118 @@ impl Jellyfin {
                 ));
             }
         };
-        Ok(library_counts)
+        Ok(folders)
+    }
+    #[instrument(skip_all)]
+    async fn get_item_count(
+        &self,
+        endpoint: &str,
+        parent_id: &str,
+        item_types: Option<&str>,
+    ) -> Result<i64, ProviderError> {
+        let url = format!("{}/{}", self.address, endpoint);
+        let mut params = vec![
+            ("ParentId", parent_id),
+            ("Recursive", "true"),
+            ("Limit", "0"),
+        ];
+        match item_types {
+            Some(item_types) => params.push(("IncludeItemTypes", item_types)),
+            None => params.push(("IsFolder", "false")),
+        }
+        let response = match self
+            .client
+            .get(&url)
+            .query(&params)
+            .send_timed(Provider::Jellyfin)
+            .await
+        {
+            Ok(response) => response,
+            Err(e) => {
+                return Err(ProviderError::new(
+                    Provider::Jellyfin,
+                    ProviderErrorKind::GetError,
+                    &format!("{:?}", e),
+                ));
+            }
+        };
+        let items: ItemsResponse = match parse_json(Provider::Jellyfin, response).await {
+            Ok(items) => items,
+            Err(e) => {
+                return Err(ProviderError::new(
+                    Provider::Jellyfin,
+                    ProviderErrorKind::ParseError,
+                    &format!("{:?}", e),
+                ));
+            }
+        };
+        Ok(items.total_record_count)
+    }
+    async fn get_library_infos(&self) -> Result<Vec<LibraryInfos>, ProviderError> {
+        let folders = self.get_virtual_folders().await?;
+        let mut library_infos = Vec::new();
+        for folder in folders {
+            let id = folder.item_id.as_str();
+            debug!("Counting items of Jellyfin library {}", folder.name);
+            let infos = match folder.collection_type.as_deref() {
+                Some("movies") => LibraryInfos {
+                    name: folder.name.clone(),
+                    library_type: "Movie".to_string(),
+                    count: self.get_item_count("Items", id, Some("Movie")).await?,
+                    child_count: None,
+                    grand_child_count: None,
+                },
+                Some("tvshows") => LibraryInfos {
+                    name: folder.name.clone(),
+                    library_type: "Shows".to_string(),
+                    count: self.get_item_count("Items", id, Some("Series")).await?,
+                    child_count: Some(self.get_item_count("Items", id, Some("Season")).await?),
+                    grand_child_count: Some(
+                        self.get_item_count("Items", id, Some("Episode")).await?,
+                    ),
+                },
+                Some("music") => LibraryInfos {
+                    name: folder.name.clone(),
+                    library_type: "Music".to_string(),
+                    count: self.get_item_count("Items", id, Some("MusicAlbum")).await?,
+                    child_count: Some(self.get_item_count("Artists", id, None).await?),
+                    grand_child_count: Some(self.get_item_count("Items", id, Some("Audio")).await?),
+                },
+                Some("books") => LibraryInfos {
+                    name: folder.name.clone(),
+                    library_type: "Book".to_string(),
+                    count: self.get_item_count("Items", id, Some("Book")).await?,
+                    child_count: None,
+                    grand_child_count: None,
+                },
+                collection_type => LibraryInfos {
+                    name: folder.name.clone(),
+                    library_type: collection_type.unwrap_or("Unknown").to_string(),
+                    count: self.get_item_count("Items", id, None).await?,
+                    child_count: None,
+                    grand_child_count: None,
+                },
+            };
+            library_infos.push(infos);
+        }
+        Ok(library_infos)
     }
     pub async fn get_library(&self) -> Vec<LibraryCount> {
-        let library_infos = match self.get_library_counts().await {
-            Ok(library_counts) => library_counts.into(),
+        let library_infos = match self.get_library_infos().await {
+            Ok(library_infos) => library_infos,
             Err(e) => {
-                error!("Failed to get library counts: {}", e);
-                Vec::new()
+                error!(
+                    "Failed to get per library counts, falling back to global counts: {}",
+                    e
+                );
+                match self.get_library_counts().await {
+                    Ok(library_counts) => library_counts.into(),
+                    Err(e) => {
+                        error!("Failed to get library counts: {}", e);
+                        record_failure(e.reason());
+                        Vec::new()
+                    }
+                }
             }
         };
         library_infos
@@ -141,3 +873,97 @@ impl Jellyfin {
             .collect()
     }
 }
+
+#[async_trait]
+impl MetricProvider for Jellyfin {
+    fn provider(&self) -> &'static str {
+        "jellyfin"
+    }
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn address(&self) -> &str {
+        &self.address
+    }
+    async fn check(&self) -> Result<(), ProviderError> {
+        let url = format!("{}/System/Info", self.address);
+        check_endpoint(Provider::Jellyfin, &self.client, &url).await
+    }
+    async fn version(&self) -> Result<String, ProviderError> {
+        Ok(self.get_public_system_info().await?.version)
+    }
+    async fn debug(&self, endpoint: Option<&str>) -> Result<String, ProviderError> {
+        let url = format!("{}/{}", self.address, endpoint.unwrap_or("System/Info"));
+        get_raw(Provider::Jellyfin, &self.client, &url).await
+    }
+    fn tasks(&self) -> Vec<TaskKind> {
+        let mut tasks = Vec::new();
+        if enabled(self.sessions) {
+            tasks.push(SESSIONS);
+        }
+        if enabled(self.library) {
+            tasks.push(LIBRARY);
+        }
+        if enabled(self.devices) {
+            tasks.push(DEVICES);
+        }
+        if enabled(self.plugins) {
+            tasks.push(PLUGINS);
+        }
+        if enabled(self.info) {
+            tasks.push(INFO);
+        }
+        if enabled(self.recently_added) {
+            tasks.push(RECENTLY_ADDED);
+        }
+        if enabled(self.storage) {
+            tasks.push(STORAGE);
+        }
+        tasks
+    }
+    async fn collect(&self, task: TaskKind) -> Result<Vec<TaskResult>, ProviderError> {
+        let name = self.name.clone();
+        let result = match task {
+            SESSIONS => {
+                let sessions = self.get_current_sessions().await;
+                TaskResult::JellyfinSession(SessionResult {
+                    name,
+                    kind: "jellyfin".to_string(),
+                    users: self.get_users().await,
+                    sessions,
+                })
+            }
+            LIBRARY => TaskResult::JellyfinLibrary(LibraryResult {
+                name,
+                kind: "jellyfin".to_string(),
+                libraries: self.get_library().await,
+            }),
+            DEVICES => {
+                let devices = self.get_registered_devices().await;
+                TaskResult::JellyfinDevices(JellyfinDevicesResult {
+                    name,
+                    devices,
+                    connected: self.get_connected_clients().await,
+                })
+            }
+            PLUGINS => TaskResult::JellyfinPlugins(JellyfinPluginsResult {
+                name,
+                plugins: self.get_installed_plugins().await,
+            }),
+            INFO => TaskResult::JellyfinInfo(JellyfinInfoResult {
+                name,
+                info: self.get_server_info().await,
+            }),
+            RECENTLY_ADDED => TaskResult::JellyfinRecentlyAdded(JellyfinRecentlyAddedResult {
+                name,
+                recently_added: self.get_recently_added().await,
+            }),
+            STORAGE => TaskResult::JellyfinStorage(JellyfinStorageResult {
+                name,
+                paths: self.get_storage_paths().await,
+            }),
+            _ => return Ok(Vec::new()),
+        };
+        Ok(vec![result])
+    }
+}
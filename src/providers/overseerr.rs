@@ -1,10 +1,47 @@
 use anyhow::Context;
+use async_trait::async_trait;
+use chrono::DateTime;
+use futures::StreamExt;
 use log::error;
+use once_cell::sync::Lazy;
 use reqwest::header;
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use tracing::instrument;
 
 use crate::providers::structs::overseerr;
-use crate::providers::{Provider, ProviderError, ProviderErrorKind};
+use crate::providers::{
+    check_endpoint, get_raw, parse_json, parse_json_list, record_failure, ClientOptions,
+    MetricProvider, Provider, ProviderError, ProviderErrorKind, SendTimed,
+};
+use crate::tasks::{enabled, OverseerrJobsResult, OverseerrRequestResult, TaskKind, TaskResult};
+
+const OVERSEERR: TaskKind = TaskKind {
+    name: "overseerr",
+    flag: Some("request_metrics"),
+};
+const JELLYSEERR: TaskKind = TaskKind {
+    name: "jellyseerr",
+    flag: Some("request_metrics"),
+};
+const OVERSEERR_JOBS: TaskKind = TaskKind {
+    name: "overseerr_jobs",
+    flag: Some("jobs"),
+};
+const JELLYSEERR_JOBS: TaskKind = TaskKind {
+    name: "jellyseerr_jobs",
+    flag: Some("jobs"),
+};
+
+const PAGE_SIZE: i64 = 100;
+// Media lookups in flight at once when resolving request titles.
+const TITLE_CONCURRENCY: usize = 8;
+
+// Titles by media type and TMDB id, they do not change so each one is only
+// looked up once.
+static MEDIA_TITLES: Lazy<Mutex<HashMap<(String, i64), String>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
 
 #[derive(Debug, Deserialize, Clone, Serialize)]
 pub struct OverseerrRequest {
@@ -17,14 +54,47 @@ pub struct OverseerrRequest {
     pub requested_at: String,
 }
 
+#[derive(Debug, Deserialize, Clone, Serialize)]
+pub struct OverseerrRequestCount {
+    pub movie: i64,
+    pub tv: i64,
+    pub pending: i64,
+    pub approved: i64,
+    pub declined: i64,
+    pub processing: i64,
+    pub available: i64,
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize)]
+pub struct OverseerrJob {
+    pub id: String,
+    pub name: String,
+    pub job_type: String,
+    pub running: bool,
+    pub next_execution: Option<i64>,
+}
+
 #[derive(Debug, Deserialize, Clone, Serialize)]
 pub struct Overseerr {
+    #[serde(skip)]
+    pub name: String,
     pub address: String,
     #[serde(rename = "apikey")]
     pub api_key: String,
     pub requests: Option<i64>,
+    #[serde(default)]
+    pub all_requests: bool,
+    #[serde(default)]
+    pub aggregate: bool,
+    pub request_metrics: Option<bool>,
+    pub jobs: Option<bool>,
+    #[serde(flatten)]
+    pub client_options: ClientOptions,
     #[serde(skip)]
     client: reqwest::Client,
+    // Jellyseerr shares the Overseerr API, only the labels differ.
+    #[serde(skip)]
+    pub jellyseerr: bool,
 }
 
 #[derive(Debug, Deserialize, Clone, Serialize)]
@@ -129,7 +199,14 @@ impl MediaStatus {
 }
 
 impl Overseerr {
-    pub fn new(address: &str, api_key: &str, requests: i64) -> Result<Overseerr, ProviderError> {
+    pub fn new(
+        name: &str,
+        address: &str,
+        api_key: &str,
+        requests: Option<i64>,
+        aggregate: bool,
+        options: &ClientOptions,
+    ) -> Result<Overseerr, ProviderError> {
         let mut headers = header::HeaderMap::new();
         let mut header_api_key = header::HeaderValue::from_str(api_key).unwrap();
         header_api_key.set_sensitive(true);
@@ -138,26 +215,140 @@ impl Overseerr {
             "Content-Type",
             header::HeaderValue::from_static("application/json"),
         );
-        let client = reqwest::Client::builder()
-            .default_headers(headers)
+        let client = options
+            .apply(reqwest::Client::builder().default_headers(headers))?
             .build()?;
         Ok(Overseerr {
+            name: name.to_string(),
             address: address.to_string(),
             api_key: api_key.to_string(),
-            requests: Some(requests),
+            requests,
+            all_requests: requests.is_none(),
+            aggregate,
+            request_metrics: None,
+            jobs: None,
+            jellyseerr: false,
+            client_options: options.clone(),
             client,
         })
     }
+    #[instrument(skip_all)]
     async fn get_requests(&self) -> Result<Vec<overseerr::Result>, ProviderError> {
         let url = format!("{}/api/v1/request", self.address);
-        let response = match self
-            .client
-            .get(&url)
-            .query(&[("sort", "added")])
-            .query(&[("take", self.requests.unwrap().to_string())])
-            .send()
-            .await
+        let mut results: Vec<overseerr::Result> = Vec::new();
+        loop {
+            let take = match self.requests {
+                Some(limit) => (limit - results.len() as i64).min(PAGE_SIZE),
+                None => PAGE_SIZE,
+            };
+            if take <= 0 {
+                break;
+            }
+            let response = match self
+                .client
+                .get(&url)
+                .query(&[("sort", "added")])
+                .query(&[("take", take.to_string())])
+                .query(&[("skip", results.len().to_string())])
+                .send_timed(Provider::Overseerr)
+                .await
+            {
+                Ok(response) => response,
+                Err(e) => {
+                    return Err(ProviderError::new(
+                        Provider::Overseerr,
+                        ProviderErrorKind::GetError,
+                        &format!("{:?}", e),
+                    ));
+                }
+            };
+            let requests =
+                match parse_json::<overseerr::Request>(Provider::Overseerr, response).await {
+                    Ok(requests) => requests,
+                    Err(e) => {
+                        return Err(ProviderError::new(
+                            Provider::Overseerr,
+                            ProviderErrorKind::ParseError,
+                            &format!("{:?}", e),
+                        ));
+                    }
+                };
+            let fetched = requests.results.len();
+            results.extend(requests.results);
+            if fetched == 0 || results.len() as i64 >= requests.page_info.results {
+                break;
+            }
+        }
+        Ok(results)
+    }
+    pub async fn get_overseerr_requests(&self) -> Vec<OverseerrRequest> {
+        let requests = match self.get_requests().await {
+            Ok(requests) => requests,
+            Err(e) => {
+                error!("Failed to get overseerr requests: {:?}", e);
+                record_failure(e.reason());
+                Vec::new()
+            }
+        };
+        // Titles are not exported in aggregate mode, each media is looked up
+        // once even when it was requested several times.
+        let media: HashSet<(String, i64)> = match self.aggregate {
+            true => HashSet::new(),
+            false => requests
+                .iter()
+                .map(|request| (request.media.media_type.clone(), request.media.tmdb_id))
+                .collect(),
+        };
+        let titles: HashMap<(String, i64), String> = futures::stream::iter(media)
+            .map(|key| async move {
+                let title = self.cached_media_title(&key.0, key.1).await;
+                (key, title)
+            })
+            .buffer_unordered(TITLE_CONCURRENCY)
+            .collect()
+            .await;
+        requests
+            .into_iter()
+            .map(|request| OverseerrRequest {
+                media_title: titles
+                    .get(&(request.media.media_type.clone(), request.media.tmdb_id))
+                    .cloned()
+                    .unwrap_or_else(|| "Unknown".to_string()),
+                media_type: request.media.media_type.clone(),
+                media_id: request.media.id,
+                status: request.status.into(),
+                requested_by: self.get_username(&request).to_string(),
+                media_status: request.media.status.into(),
+                requested_at: request.created_at,
+            })
+            .collect()
+    }
+    async fn cached_media_title(&self, media_type: &str, tmdb_id: i64) -> String {
+        let key = (media_type.to_string(), tmdb_id);
+        if let Some(title) = MEDIA_TITLES
+            .lock()
+            .ok()
+            .and_then(|titles| titles.get(&key).cloned())
         {
+            return title;
+        }
+        match self.get_media_title(media_type, tmdb_id).await {
+            Ok(title) => {
+                if let Ok(mut titles) = MEDIA_TITLES.lock() {
+                    titles.insert(key, title.clone());
+                }
+                title
+            }
+            Err(e) => {
+                error!("Failed to get media title: {:?}", e);
+                "Unknown".to_string()
+            }
+        }
+    }
+    #[instrument(skip_all)]
+    async fn get_request_count(&self) -> Result<overseerr::RequestCount, ProviderError> {
+        let url = format!("{}/api/v1/request/count", self.address);
+        let response = match self.client.get(&url).send_timed(Provider::Overseerr).await {
             Ok(response) => response,
             Err(e) => {
                 return Err(ProviderError::new(
@@ -167,59 +358,77 @@ impl Overseerr {
                 ));
             }
         };
-        let requests = match response.json::<overseerr::Request>().await {
-            Ok(requests) => requests,
+        match parse_json::<overseerr::RequestCount>(Provider::Overseerr, response).await {
+            Ok(count) => Ok(count),
+            Err(e) => Err(ProviderError::new(
+                Provider::Overseerr,
+                ProviderErrorKind::ParseError,
+                &format!("{:?}", e),
+            )),
+        }
+    }
+    pub async fn get_overseerr_request_count(&self) -> Option<OverseerrRequestCount> {
+        match self.get_request_count().await {
+            Ok(count) => Some(OverseerrRequestCount {
+                movie: count.movie,
+                tv: count.tv,
+                pending: count.pending,
+                approved: count.approved,
+                declined: count.declined,
+                processing: count.processing,
+                available: count.available,
+            }),
+            Err(e) => {
+                error!("Failed to get overseerr request count: {:?}", e);
+                record_failure(e.reason());
+                None
+            }
+        }
+    }
+    #[instrument(skip_all)]
+    async fn get_jobs(&self) -> Result<Vec<overseerr::Job>, ProviderError> {
+        let url = format!("{}/api/v1/settings/jobs", self.address);
+        let response = match self.client.get(&url).send_timed(Provider::Overseerr).await {
+            Ok(response) => response,
             Err(e) => {
                 return Err(ProviderError::new(
                     Provider::Overseerr,
-                    ProviderErrorKind::ParseError,
+                    ProviderErrorKind::GetError,
                     &format!("{:?}", e),
                 ));
             }
         };
-        Ok(requests.results)
-        //Ok(Vec::new())
+        match parse_json_list::<overseerr::Job>(Provider::Overseerr, response).await {
+            Ok(jobs) => Ok(jobs),
+            Err(e) => Err(ProviderError::new(
+                Provider::Overseerr,
+                ProviderErrorKind::ParseError,
+                &format!("{:?}", e),
+            )),
+        }
     }
-    pub async fn get_overseerr_requests(&self) -> Vec<OverseerrRequest> {
-        let requests = match self.get_requests().await {
-            Ok(requests) => requests,
+    pub async fn get_overseerr_jobs(&self) -> Vec<OverseerrJob> {
+        let jobs = match self.get_jobs().await {
+            Ok(jobs) => jobs,
             Err(e) => {
-                error!("Failed to get overseerr requests: {:?}", e);
-                Vec::new()
+                error!("Failed to get overseerr jobs: {:?}", e);
+                record_failure(e.reason());
+                return Vec::new();
             }
         };
-        let futures_requests = requests.into_iter().map(|request| {
-            let self_ref = self.clone(); // Assuming `self` implements `Clone`, so we can move it into the future.
-            async move {
-                // Fetch media title asynchronously
-                let media_title = match self_ref
-                    .get_media_title(&request.media.media_type, request.media.tmdb_id)
-                    .await
-                {
-                    Ok(title) => title,
-                    Err(e) => {
-                        error!("Failed to get media title: {:?}", e);
-                        "Unknown".to_string()
-                    }
-                };
-
-                // Construct the OverseerrRequest
-                OverseerrRequest {
-                    media_type: request.media.media_type.clone(),
-                    media_id: request.media.id,
-                    status: request.status.into(),
-                    requested_by: self_ref.get_username(&request).to_string(),
-                    media_status: request.media.status.into(),
-                    media_title,
-                    requested_at: request.created_at,
-                }
-            }
-        });
-        let overseerr_requests: Vec<OverseerrRequest> = futures::future::join_all(futures_requests)
-            .await
-            .into_iter()
-            .collect();
-        overseerr_requests
+        jobs.into_iter()
+            .map(|job| OverseerrJob {
+                id: job.id,
+                name: job.name,
+                job_type: job.type_field,
+                running: job.running,
+                next_execution: job
+                    .next_execution_time
+                    .as_deref()
+                    .and_then(|date| DateTime::parse_from_rfc3339(date).ok())
+                    .map(|date| date.timestamp()),
+            })
+            .collect()
     }
     fn get_username<'a>(&self, request: &'a overseerr::Result) -> &'a str {
         match &request.requested_by.username {
@@ -230,13 +439,14 @@ impl Overseerr {
             },
         }
     }
+    #[instrument(skip_all)]
     async fn get_media_title(
         &self,
         media_type: &str,
         media_id: i64,
     ) -> Result<String, ProviderError> {
         let url = format!("{}/api/v1/{}/{}", self.address, media_type, media_id);
-        let response = match self.client.get(&url).send().await {
+        let response = match self.client.get(&url).send_timed(Provider::Overseerr).await {
             Ok(response) => response,
             Err(e) => {
                 return Err(ProviderError::new(
@@ -248,37 +458,123 @@ impl Overseerr {
         };
         match media_type {
             "movie" => {
-                let movie: overseerr::Movie =
-                    match response.json().await.context("Failed to parse movie") {
-                        Ok(movie) => movie,
-                        Err(e) => {
-                            return Err(ProviderError::new(
-                                Provider::Overseerr,
-                                ProviderErrorKind::ParseError,
-                                &format!("{:?}", e),
-                            ));
-                        }
-                    };
+                let movie: overseerr::Movie = match parse_json(Provider::Overseerr, response)
+                    .await
+                    .context("Failed to parse movie")
+                {
+                    Ok(movie) => movie,
+                    Err(e) => {
+                        return Err(ProviderError::new(
+                            Provider::Overseerr,
+                            ProviderErrorKind::ParseError,
+                            &format!("{:?}", e),
+                        ));
+                    }
+                };
                 match movie.original_title {
                     Some(title) => Ok(title),
                     None => Ok("Unknown".to_string()),
                 }
             }
             "tv" => {
-                let show: overseerr::Tv =
-                    match response.json().await.context("Failed to parse show") {
-                        Ok(show) => show,
-                        Err(e) => {
-                            return Err(ProviderError::new(
-                                Provider::Overseerr,
-                                ProviderErrorKind::ParseError,
-                                &format!("{:?}", e),
-                            ));
-                        }
-                    };
+                let show: overseerr::Tv = match parse_json(Provider::Overseerr, response)
+                    .await
+                    .context("Failed to parse show")
+                {
+                    Ok(show) => show,
+                    Err(e) => {
+                        return Err(ProviderError::new(
+                            Provider::Overseerr,
+                            ProviderErrorKind::ParseError,
+                            &format!("{:?}", e),
+                        ));
+                    }
+                };
                 Ok(show.name)
             }
             _ => Ok("Unknown".to_string()),
         }
     }
 }
+
+#[async_trait]
+impl MetricProvider for Overseerr {
+    fn provider(&self) -> &'static str {
+        if self.jellyseerr {
+            "jellyseerr"
+        } else {
+            "overseerr"
+        }
+    }
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn address(&self) -> &str {
+        &self.address
+    }
+    async fn check(&self) -> Result<(), ProviderError> {
+        let url = format!("{}/api/v1/auth/me", self.address);
+        check_endpoint(Provider::Overseerr, &self.client, &url).await
+    }
+    async fn version(&self) -> Result<String, ProviderError> {
+        let url = format!("{}/api/v1/status", self.address);
+        let response = self
+            .client
+            .get(&url)
+            .send_timed(Provider::Overseerr)
+            .await?
+            .error_for_status()?;
+        let status: overseerr::Status = parse_json(Provider::Overseerr, response).await?;
+        Ok(status.version)
+    }
+    async fn debug(&self, endpoint: Option<&str>) -> Result<String, ProviderError> {
+        let url = format!("{}/api/v1/{}", self.address, endpoint.unwrap_or("status"));
+        get_raw(Provider::Overseerr, &self.client, &url).await
+    }
+    fn tasks(&self) -> Vec<TaskKind> {
+        let mut tasks = Vec::new();
+        match (self.jellyseerr, enabled(self.request_metrics)) {
+            (false, true) => tasks.push(OVERSEERR),
+            (true, true) => tasks.push(JELLYSEERR),
+            _ => {}
+        }
+        match (self.jellyseerr, enabled(self.jobs)) {
+            (false, true) => tasks.push(OVERSEERR_JOBS),
+            (true, true) => tasks.push(JELLYSEERR_JOBS),
+            _ => {}
+        }
+        tasks
+    }
+    async fn collect(&self, task: TaskKind) -> Result<Vec<TaskResult>, ProviderError> {
+        let result = match task {
+            OVERSEERR | JELLYSEERR => {
+                let result = OverseerrRequestResult {
+                    name: self.name.clone(),
+                    kind: self.provider().to_string(),
+                    aggregate: self.aggregate,
+                    requests: self.get_overseerr_requests().await,
+                    counts: self.get_overseerr_request_count().await,
+                };
+                if task == JELLYSEERR {
+                    TaskResult::Jellyseerr(result)
+                } else {
+                    TaskResult::Overseerr(result)
+                }
+            }
+            OVERSEERR_JOBS | JELLYSEERR_JOBS => {
+                let result = OverseerrJobsResult {
+                    name: self.name.clone(),
+                    kind: self.provider().to_string(),
+                    jobs: self.get_overseerr_jobs().await,
+                };
+                if task == JELLYSEERR_JOBS {
+                    TaskResult::JellyseerrJobs(result)
+                } else {
+                    TaskResult::OverseerrJobs(result)
+                }
+            }
+            _ => return Ok(Vec::new()),
+        };
+        Ok(vec![result])
+    }
+}
@@ -1,13 +1,37 @@
-use crate::providers::structs::AsyncFrom;
+use async_trait::async_trait;
+use futures::future::join_all;
 use log::{debug, error, info};
 use reqwest;
 use reqwest::header;
 use serde::{Deserialize, Serialize};
+use tracing::instrument;
 
-pub use crate::providers::structs::plex::{LibraryInfos, MediaContainer};
-use crate::providers::structs::plex::{Metadata, PlexResponse, StatUser};
+use crate::providers::geoip::Geolocator;
+use crate::providers::structs::plex::{
+    Identity, Metadata, MyPlexAccount, MyPlexResponse, PlexResponse, StatUser,
+};
+pub use crate::providers::structs::plex::{LibraryInfos, MediaContainer, WebhookPayload};
 use crate::providers::structs::{LibraryCount, Session, User};
-use crate::providers::{Provider, ProviderError, ProviderErrorKind};
+use crate::providers::{
+    check_endpoint, get_raw, parse_json, record_failure, ClientOptions, MetricProvider, Provider,
+    ProviderError, ProviderErrorKind, SendTimed,
+};
+use crate::tasks::{
+    enabled, LibraryResult, PlexAccountResult, SessionResult, TaskKind, TaskResult,
+};
+
+const SESSIONS: TaskKind = TaskKind {
+    name: "plex_session",
+    flag: Some("sessions"),
+};
+const LIBRARY: TaskKind = TaskKind {
+    name: "plex_library",
+    flag: Some("library"),
+};
+const ACCOUNTS: TaskKind = TaskKind {
+    name: "plex_account",
+    flag: Some("accounts"),
+};
 
 #[derive(Debug, Deserialize, Clone, Serialize)]
 pub struct PlexViews {
@@ -15,26 +39,45 @@ pub struct PlexViews {
     pub movies_viewed: i64,
 }
 
+#[derive(Debug, Deserialize, Clone, Serialize)]
+pub struct PlexRemoteAccess {
+    // Unknown when plex.tv rejected the token.
+    pub enabled: Option<bool>,
+    pub reachable: Option<bool>,
+    pub token_valid: bool,
+}
+
 #[derive(Debug, Deserialize, Clone, Serialize)]
 pub struct Plex {
     #[serde(skip)]
     pub name: String,
     pub address: String,
     pub token: String,
+    pub sessions: Option<bool>,
+    pub library: Option<bool>,
+    pub accounts: Option<bool>,
+    #[serde(flatten)]
+    pub client_options: ClientOptions,
     #[serde(skip)]
     pub client: reqwest::Client,
+    #[serde(skip)]
+    pub geoip: Geolocator,
 }
 impl Plex {
-    pub fn _default() -> Plex {
-        match Plex::new("default", "http://localhost:32400", "123456789") {
-            Ok(plex) => plex,
-            Err(e) => {
-                eprintln!("Failed to create default Plex struct: {}", e);
-                std::process::exit(1);
-            }
-        }
+    pub fn _default() -> anyhow::Result<Plex> {
+        Plex::new(
+            "default",
+            "http://localhost:32400",
+            "123456789",
+            &ClientOptions::default(),
+        )
     }
-    pub fn new(name: &str, address: &str, token: &str) -> anyhow::Result<Plex> {
+    pub fn new(
+        name: &str,
+        address: &str,
+        token: &str,
+        options: &ClientOptions,
+    ) -> anyhow::Result<Plex> {
         let mut headers = header::HeaderMap::new();
         let mut header_token = header::HeaderValue::from_str(&token)?;
         let header_container_size = header::HeaderValue::from_static("1000");
@@ -45,28 +88,34 @@ impl Plex {
             header::ACCEPT,
             header::HeaderValue::from_static("application/json"),
         );
-        let client = reqwest::Client::builder()
-            .default_headers(headers)
+        let client = options
+            .apply(reqwest::Client::builder().default_headers(headers))?
             .build()?;
         Ok(Plex {
             name: name.to_string(),
             address: address.to_string(),
             token: token.to_string(),
+            sessions: None,
+            library: None,
+            accounts: None,
+            client_options: options.clone(),
             client,
+            geoip: Geolocator::default(),
         })
     }
     async fn _get_history(&self) -> Result<PlexResponse, ProviderError> {
         let url = format!("{}/status/sessions/history/all", self.address);
         debug!("Requesting history from {}", url);
-        let response = self.client.get(&url).send().await?;
-        let history = response.json::<PlexResponse>().await?;
+        let response = self.client.get(&url).send_timed(Provider::Plex).await?;
+        let history = parse_json::<PlexResponse>(Provider::Plex, response).await?;
         Ok(history)
     }
 
+    #[instrument(skip_all)]
     async fn get_sessions(&self) -> Result<PlexResponse, ProviderError> {
         let url = format!("{}/status/sessions", self.address);
         debug!("Requesting session from {}", url);
-        let response = match self.client.get(&url).send().await {
+        let response = match self.client.get(&url).send_timed(Provider::Plex).await {
             Ok(response) => response,
             Err(e) => {
                 return Err(ProviderError::new(
@@ -76,7 +125,7 @@ impl Plex {
                 ));
             }
         };
-        let session = match response.json::<PlexResponse>().await {
+        let session = match parse_json::<PlexResponse>(Provider::Plex, response).await {
             Ok(session) => session,
             Err(e) => {
                 return Err(ProviderError::new(
@@ -88,19 +137,21 @@ impl Plex {
         };
         Ok(session)
     }
+    #[instrument(skip_all)]
     async fn get_all_libraries(&self) -> Result<PlexResponse, ProviderError> {
         let url = format!("{}/library/sections", self.address);
         debug!("Requesting libraries from {}", url);
-        let response = self.client.get(&url).send().await?;
-        let libraries = response.json::<PlexResponse>().await?;
+        let response = self.client.get(&url).send_timed(Provider::Plex).await?;
+        let libraries = parse_json::<PlexResponse>(Provider::Plex, response).await?;
         Ok(libraries)
     }
 
+    #[instrument(skip_all)]
     async fn get_library_items(&self, library_id: &str) -> Result<PlexResponse, ProviderError> {
         let url = format!("{}/library/sections/{}/all", self.address, library_id);
         debug!("Requesting library items from {}", url);
-        let response = self.client.get(&url).send().await?;
-        let library_items = response.json::<PlexResponse>().await?;
+        let response = self.client.get(&url).send_timed(Provider::Plex).await?;
+        let library_items = parse_json::<PlexResponse>(Provider::Plex, response).await?;
         Ok(library_items)
     }
 
@@ -109,6 +160,7 @@ impl Plex {
             Ok(libraries) => libraries,
             Err(e) => {
                 error!("Failed to get libraries: {}", e);
+                record_failure(e.reason());
                 return Vec::new();
             }
         };
@@ -117,6 +169,7 @@ impl Plex {
             MediaContainer::LibraryContainer(libraries_container) => libraries_container,
             _ => {
                 error!("Media container received does not match library container");
+                record_failure("parse");
                 return Vec::new();
             }
         };
@@ -125,6 +178,7 @@ impl Plex {
                 Ok(library_item) => library_item,
                 Err(e) => {
                     error!("Failed to get library items: {}", e);
+                    record_failure(e.reason());
                     return Vec::new();
                 }
             };
@@ -134,6 +188,7 @@ impl Plex {
                 }
                 _ => {
                     error!("Media container received does not match library items container");
+                    record_failure("parse");
                     return Vec::new();
                 }
             };
@@ -149,6 +204,7 @@ impl Plex {
                                 }
                                 _ => {
                                     error!("Metadata received does not match library metadata");
+                                    record_failure("parse");
                                 }
                             }
                             (child_acc, leaf_acc)
@@ -179,29 +235,49 @@ impl Plex {
             Ok(sessions) => sessions,
             Err(e) => {
                 error!("Failed to get sessions: {}", e);
+                record_failure(e.reason());
                 return Vec::new();
             }
         };
-        let mut current_sessions: Vec<Session> = Vec::new();
+        let mut current_sessions: Vec<(String, Session)> = Vec::new();
         let activity_container = match sessions.media_container {
             MediaContainer::ActivityContainer(activity_container) => activity_container,
             _ => {
                 error!("Media container received does not match activity container");
+                record_failure("parse");
                 return Vec::new();
             }
         };
         for item in activity_container.metadata.into_iter() {
             match item {
                 Metadata::SessionMetadata(meta) => {
-                    let session = Session::from_async(meta).await;
-                    current_sessions.push(session);
+                    let ip = meta.player.remote_public_address.clone();
+                    current_sessions.push((ip, Session::from(meta)));
                 }
                 _ => {
                     error!("Metadata received does not match session metadata");
+                    record_failure("parse");
                 }
             }
         }
+        let locations =
+            join_all(current_sessions.iter().map(|(ip, _)| self.geoip.locate(ip))).await;
+        let hostnames = join_all(
+            current_sessions
+                .iter()
+                .map(|(_, session)| self.geoip.hostname(&session.address)),
+        )
+        .await;
         current_sessions
+            .into_iter()
+            .zip(locations)
+            .zip(hostnames)
+            .map(|(((_, mut session), location), hostname)| {
+                session.location = location;
+                session.hostname = hostname;
+                session
+            })
+            .collect()
     }
 
     pub async fn _get_views(&self) -> PlexViews {
@@ -209,6 +285,7 @@ impl Plex {
             Ok(history) => history,
             Err(e) => {
                 error!("Failed to get history: {}", e);
+                record_failure(e.reason());
                 return PlexViews {
                     episodes_viewed: 0,
                     movies_viewed: 0,
@@ -221,6 +298,7 @@ impl Plex {
             MediaContainer::ActivityContainer(activity_container) => activity_container,
             _ => {
                 error!("Media container received does not match activity container");
+                record_failure("parse");
                 return PlexViews {
                     episodes_viewed: 0,
                     movies_viewed: 0,
@@ -240,6 +318,7 @@ impl Plex {
                 }
                 _ => {
                     error!("Metadata received does not match history metadata");
+                    record_failure("parse");
                 }
             });
         PlexViews {
@@ -247,10 +326,11 @@ impl Plex {
             movies_viewed,
         }
     }
+    #[instrument(skip_all)]
     pub async fn get_statistics(&self) -> Result<PlexResponse, ProviderError> {
         let url = format!("{}/statistics/bandwidth?timespan=0", self.address);
         debug!("Requesting statistics from {}", url);
-        let response = match self.client.get(&url).send().await {
+        let response = match self.client.get(&url).send_timed(Provider::Plex).await {
             Ok(response) => response,
             Err(e) => {
                 return Err(ProviderError::new(
@@ -260,7 +340,7 @@ impl Plex {
                 ));
             }
         };
-        let statistics = match response.json::<PlexResponse>().await {
+        let statistics = match parse_json::<PlexResponse>(Provider::Plex, response).await {
             Ok(statistics) => statistics,
             Err(e) => {
                 return Err(ProviderError::new(
@@ -277,6 +357,7 @@ impl Plex {
             Ok(statistics) => statistics,
             Err(e) => {
                 error!("Failed to get statistics: {}", e);
+                record_failure(e.reason());
                 return Vec::new();
             }
         };
@@ -288,6 +369,7 @@ impl Plex {
             }
             _ => {
                 error!("Media container received does not match statistics container");
+                record_failure("parse");
                 return Vec::new();
             }
         };
@@ -297,4 +379,128 @@ impl Plex {
             .map(|item| <StatUser as Into<User>>::into(item))
             .collect()
     }
+    #[instrument(skip_all)]
+    async fn get_myplex_account(&self) -> Result<Option<MyPlexAccount>, ProviderError> {
+        let url = format!("{}/myplex/account", self.address);
+        debug!("Requesting myplex account from {}", url);
+        let response = match self.client.get(&url).send_timed(Provider::Plex).await {
+            Ok(response) => response,
+            Err(e) => {
+                return Err(ProviderError::new(
+                    Provider::Plex,
+                    ProviderErrorKind::GetError,
+                    &format!("{:?}", e),
+                ));
+            }
+        };
+        if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+            return Ok(None);
+        }
+        let account = match parse_json::<MyPlexResponse>(Provider::Plex, response).await {
+            Ok(account) => account,
+            Err(e) => {
+                return Err(ProviderError::new(
+                    Provider::Plex,
+                    ProviderErrorKind::ParseError,
+                    &format!("{:?}", e),
+                ));
+            }
+        };
+        Ok(Some(account.my_plex))
+    }
+    // Nothing is known when plex.tv could not be asked.
+    pub async fn get_remote_access(&self) -> Option<PlexRemoteAccess> {
+        let account = match self.get_myplex_account().await {
+            Ok(Some(account)) => account,
+            Ok(None) => {
+                error!("Plex token was rejected by {}", self.address);
+                record_failure("auth");
+                return Some(PlexRemoteAccess {
+                    enabled: None,
+                    reachable: None,
+                    token_valid: false,
+                });
+            }
+            Err(e) => {
+                error!("Failed to get myplex account: {}", e);
+                record_failure(e.reason());
+                return None;
+            }
+        };
+        let enabled = !matches!(account.mapping_state.as_str(), "" | "unknown");
+        Some(PlexRemoteAccess {
+            enabled: Some(enabled),
+            reachable: Some(account.mapping_state == "mapped" && account.mapping_error.is_empty()),
+            token_valid: account.sign_in_state == "ok",
+        })
+    }
+}
+
+#[async_trait]
+impl MetricProvider for Plex {
+    fn provider(&self) -> &'static str {
+        "plex"
+    }
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn address(&self) -> &str {
+        &self.address
+    }
+    async fn check(&self) -> Result<(), ProviderError> {
+        let url = format!("{}/library/sections", self.address);
+        check_endpoint(Provider::Plex, &self.client, &url).await
+    }
+    async fn version(&self) -> Result<String, ProviderError> {
+        let url = format!("{}/identity", self.address);
+        let response = self
+            .client
+            .get(&url)
+            .send_timed(Provider::Plex)
+            .await?
+            .error_for_status()?;
+        let identity: Identity = parse_json(Provider::Plex, response).await?;
+        Ok(identity.media_container.version)
+    }
+    async fn debug(&self, endpoint: Option<&str>) -> Result<String, ProviderError> {
+        let url = format!("{}/{}", self.address, endpoint.unwrap_or("identity"));
+        get_raw(Provider::Plex, &self.client, &url).await
+    }
+    fn tasks(&self) -> Vec<TaskKind> {
+        let mut tasks = Vec::new();
+        if enabled(self.sessions) {
+            tasks.push(SESSIONS);
+        }
+        if enabled(self.library) {
+            tasks.push(LIBRARY);
+        }
+        if enabled(self.accounts) {
+            tasks.push(ACCOUNTS);
+        }
+        tasks
+    }
+    async fn collect(&self, task: TaskKind) -> Result<Vec<TaskResult>, ProviderError> {
+        let result = match task {
+            SESSIONS => {
+                let sessions = self.get_current_sessions().await;
+                TaskResult::PlexSession(SessionResult {
+                    name: self.name.clone(),
+                    kind: "plex".to_string(),
+                    users: self.get_users().await,
+                    sessions,
+                })
+            }
+            LIBRARY => TaskResult::PlexLibrary(LibraryResult {
+                name: self.name.clone(),
+                kind: "plex".to_string(),
+                libraries: self.get_all_library_size().await,
+            }),
+            ACCOUNTS => TaskResult::PlexAccount(PlexAccountResult {
+                name: self.name.clone(),
+                remote_access: self.get_remote_access().await,
+            }),
+            _ => return Ok(Vec::new()),
+        };
+        Ok(vec![result])
+    }
 }
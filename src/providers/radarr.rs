@@ -1,9 +1,20 @@
+use async_trait::async_trait;
 use log::error;
 use reqwest::header;
 use serde::{Deserialize, Serialize};
+use tracing::instrument;
 
-use crate::providers::structs::radarr::Movie;
-use crate::providers::{Provider, ProviderError, ProviderErrorKind};
+use crate::providers::structs::radarr::{self, Movie};
+use crate::providers::{
+    check_endpoint, get_raw, parse_json, parse_json_list, record_failure, ClientOptions,
+    MetricProvider, Provider, ProviderError, ProviderErrorKind, SendTimed,
+};
+use crate::tasks::{RadarrMovieResult, TaskKind, TaskResult};
+
+const MOVIES: TaskKind = TaskKind {
+    name: "radarr",
+    flag: None,
+};
 
 #[derive(Debug, Deserialize, Clone, Serialize)]
 pub struct RadarrMovie {
@@ -32,11 +43,18 @@ pub struct Radarr {
     pub address: String,
     #[serde(rename = "apikey")]
     pub api_key: String,
+    #[serde(flatten)]
+    pub client_options: ClientOptions,
     #[serde(skip)]
     client: reqwest::Client,
 }
 impl Radarr {
-    pub fn new(name: &str, address: &str, api_key: &str) -> Result<Radarr, ProviderError> {
+    pub fn new(
+        name: &str,
+        address: &str,
+        api_key: &str,
+        options: &ClientOptions,
+    ) -> Result<Radarr, ProviderError> {
         let mut headers = header::HeaderMap::new();
         let mut header_api_key = match header::HeaderValue::from_str(&api_key) {
             Ok(header_api_key) => header_api_key,
@@ -54,19 +72,21 @@ impl Radarr {
             "Accept",
             header::HeaderValue::from_static("application/json"),
         );
-        let client = reqwest::Client::builder()
-            .default_headers(headers)
+        let client = options
+            .apply(reqwest::Client::builder().default_headers(headers))?
             .build()?;
         Ok(Radarr {
             name: name.to_string(),
             address: format!("{}/api/v3", address),
             api_key: api_key.to_string(),
+            client_options: options.clone(),
             client,
         })
     }
+    #[instrument(skip_all)]
     async fn get_movies(&self) -> Result<Vec<Movie>, ProviderError> {
         let url = format!("{}/movie", self.address);
-        let response = match self.client.get(&url).send().await {
+        let response = match self.client.get(&url).send_timed(Provider::Radarr).await {
             Ok(response) => response,
             Err(e) => {
                 return Err(ProviderError::new(
@@ -76,7 +96,7 @@ impl Radarr {
                 ));
             }
         };
-        let movies: Vec<Movie> = match response.json().await {
+        let movies: Vec<Movie> = match parse_json_list(Provider::Radarr, response).await {
             Ok(movies) => movies,
             Err(e) => {
                 return Err(ProviderError::new(
@@ -93,6 +113,7 @@ impl Radarr {
             Ok(movies) => movies,
             Err(e) => {
                 error!("Failed to get radarr movies: {:?}", e);
+                record_failure(e.reason());
                 Vec::new()
             }
         };
@@ -116,3 +137,47 @@ impl Radarr {
         }
     }
 }
+
+#[async_trait]
+impl MetricProvider for Radarr {
+    fn provider(&self) -> &'static str {
+        "radarr"
+    }
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn address(&self) -> &str {
+        &self.address
+    }
+    async fn check(&self) -> Result<(), ProviderError> {
+        let url = format!("{}/system/status", self.address);
+        check_endpoint(Provider::Radarr, &self.client, &url).await
+    }
+    async fn version(&self) -> Result<String, ProviderError> {
+        let url = format!("{}/system/status", self.address);
+        let response = self
+            .client
+            .get(&url)
+            .send_timed(Provider::Radarr)
+            .await?
+            .error_for_status()?;
+        let status: radarr::Status = parse_json(Provider::Radarr, response).await?;
+        Ok(status.version)
+    }
+    async fn debug(&self, endpoint: Option<&str>) -> Result<String, ProviderError> {
+        let url = format!("{}/{}", self.address, endpoint.unwrap_or("system/status"));
+        get_raw(Provider::Radarr, &self.client, &url).await
+    }
+    fn tasks(&self) -> Vec<TaskKind> {
+        vec![MOVIES]
+    }
+    async fn collect(&self, task: TaskKind) -> Result<Vec<TaskResult>, ProviderError> {
+        if task != MOVIES {
+            return Ok(Vec::new());
+        }
+        Ok(vec![TaskResult::Radarr(RadarrMovieResult {
+            name: self.name.clone(),
+            movies: self.get_radarr_movies().await,
+        })])
+    }
+}
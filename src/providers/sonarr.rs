@@ -1,10 +1,27 @@
+use async_trait::async_trait;
 use chrono::{format::strftime::StrftimeItems, Duration, Local};
 use log::{debug, error};
-use reqwest::header;
+use reqwest::{header, StatusCode};
 use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tracing::instrument;
 
 use crate::providers::structs::sonarr;
-use crate::providers::{Provider, ProviderError, ProviderErrorKind};
+use crate::providers::{
+    get_raw, parse_json, parse_json_list, record_failure, ClientOptions, MetricProvider, Provider,
+    ProviderError, ProviderErrorKind, SendTimed,
+};
+use crate::tasks::{enabled, SonarrEpisodeResult, SonarrMissingResult, TaskKind, TaskResult};
+
+const TODAY: TaskKind = TaskKind {
+    name: "sonarr_today",
+    flag: Some("today"),
+};
+const MISSING: TaskKind = TaskKind {
+    name: "sonarr_missing",
+    flag: Some("missing"),
+};
 
 #[derive(Debug, Deserialize, Clone, Serialize)]
 pub struct Sonarr {
@@ -13,8 +30,15 @@ pub struct Sonarr {
     pub address: String,
     #[serde(rename = "apikey")]
     pub api_key: String,
+    pub today: Option<bool>,
+    pub missing: Option<bool>,
+    #[serde(flatten)]
+    pub client_options: ClientOptions,
     #[serde(skip)]
     client: reqwest::Client,
+    // Set by `version` for Sonarr v2, which serves its API under /api.
+    #[serde(skip)]
+    legacy_api: Arc<AtomicBool>,
 }
 
 #[derive(Debug, Deserialize, Clone, Serialize)]
@@ -39,7 +63,12 @@ impl std::fmt::Display for SonarrEpisode {
 }
 
 impl Sonarr {
-    pub fn new(name: &str, address: &str, api_key: &str) -> Result<Sonarr, ProviderError> {
+    pub fn new(
+        name: &str,
+        address: &str,
+        api_key: &str,
+        options: &ClientOptions,
+    ) -> Result<Sonarr, ProviderError> {
         let mut headers = header::HeaderMap::new();
         let mut header_api_key = match header::HeaderValue::from_str(api_key) {
             Ok(header_api_key) => header_api_key,
@@ -53,18 +82,23 @@ impl Sonarr {
         };
         header_api_key.set_sensitive(true);
         headers.insert("X-Api-Key", header_api_key);
-        let client = reqwest::Client::builder()
-            .default_headers(headers)
+        let client = options
+            .apply(reqwest::Client::builder().default_headers(headers))?
             .build()?;
         Ok(Sonarr {
             name: name.to_string(),
             address: address.to_string(),
             api_key: api_key.to_string(),
+            today: None,
+            missing: None,
+            client_options: options.clone(),
             client,
+            legacy_api: Arc::default(),
         })
     }
+    #[instrument(skip_all)]
     async fn get_last_seven_days_calendars(&self) -> Result<Vec<sonarr::Calendar>, ProviderError> {
-        let url = format!("{}/api/v3/calendar", self.address);
+        let url = format!("{}/{}/calendar", self.address, self.api());
         let local_datetime = Local::now();
         let date_end = local_datetime.date_naive();
         let date_start = date_end - Duration::days(7);
@@ -78,7 +112,13 @@ impl Sonarr {
             ("includeSeries", &true.to_string()),
         ];
         debug!("Params: {:?}", params);
-        let response = match self.client.get(&url).query(&params).send().await {
+        let response = match self
+            .client
+            .get(&url)
+            .query(&params)
+            .send_timed(Provider::Sonarr)
+            .await
+        {
             Ok(response) => response,
             Err(e) => {
                 return Err(ProviderError::new(
@@ -88,7 +128,8 @@ impl Sonarr {
                 ));
             }
         };
-        let calendars = match response.json::<Vec<sonarr::Calendar>>().await {
+        let calendars = match parse_json_list::<sonarr::Calendar>(Provider::Sonarr, response).await
+        {
             Ok(calendars) => calendars,
             Err(e) => {
                 return Err(ProviderError::new(
@@ -100,8 +141,9 @@ impl Sonarr {
         };
         Ok(calendars)
     }
+    #[instrument(skip_all)]
     async fn get_today_calendars(&self) -> Result<Vec<sonarr::Calendar>, ProviderError> {
-        let url = format!("{}/api/v3/calendar", self.address);
+        let url = format!("{}/{}/calendar", self.address, self.api());
         let local_datetime = Local::now();
 
         // Extract the date component
@@ -119,7 +161,13 @@ impl Sonarr {
             ("end", &formatted_date_end),
             ("includeSeries", &true.to_string()),
         ];
-        let response = match self.client.get(url).query(&params).send().await {
+        let response = match self
+            .client
+            .get(url)
+            .query(&params)
+            .send_timed(Provider::Sonarr)
+            .await
+        {
             Ok(response) => response,
             Err(e) => {
                 return Err(ProviderError::new(
@@ -129,7 +177,8 @@ impl Sonarr {
                 ));
             }
         };
-        let calendars = match response.json::<Vec<sonarr::Calendar>>().await {
+        let calendars = match parse_json_list::<sonarr::Calendar>(Provider::Sonarr, response).await
+        {
             Ok(calendars) => calendars,
             Err(e) => {
                 return Err(ProviderError::new(
@@ -147,6 +196,7 @@ impl Sonarr {
             Ok(calendars) => calendars,
             Err(e) => {
                 error!("Failed to get today's shows: {}", e);
+                record_failure(e.reason());
                 return Vec::new();
             }
         };
@@ -175,6 +225,7 @@ impl Sonarr {
             Ok(calendars) => calendars,
             Err(e) => {
                 error!("Failed to get today's shows: {}", e);
+                record_failure(e.reason());
                 return Vec::new();
             }
         };
@@ -201,24 +252,87 @@ impl Sonarr {
             .collect()
     }
 
-    async fn _get_status(&self) -> sonarr::Status {
-        let url = format!("{}/api/v3/system/status", self.address);
-        let response = self
-            .client
-            .get(url)
-            .send()
-            .await
-            .expect("Failed to get sonarr status");
-        response.json().await.unwrap()
+    fn api(&self) -> &'static str {
+        if self.legacy_api.load(Ordering::Relaxed) {
+            "api"
+        } else {
+            "api/v3"
+        }
     }
-    async fn _debug(&self, uri: &str) -> String {
-        let url = format!("{}/api/v3/{}", self.address, uri);
-        let response = self
-            .client
-            .get(url)
-            .send()
-            .await
-            .expect("Failed to get sonarr status");
-        response.text().await.unwrap()
+    // None when the API is not served under this path.
+    async fn get_status(&self, api: &str) -> Result<Option<sonarr::Status>, ProviderError> {
+        let url = format!("{}/{}/system/status", self.address, api);
+        let response = self.client.get(url).send_timed(Provider::Sonarr).await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let response = response.error_for_status()?;
+        Ok(Some(parse_json(Provider::Sonarr, response).await?))
+    }
+}
+
+#[async_trait]
+impl MetricProvider for Sonarr {
+    fn provider(&self) -> &'static str {
+        "sonarr"
+    }
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn address(&self) -> &str {
+        &self.address
+    }
+    async fn check(&self) -> Result<(), ProviderError> {
+        self.version().await.map(|_| ())
+    }
+    async fn version(&self) -> Result<String, ProviderError> {
+        if let Some(status) = self.get_status("api/v3").await? {
+            self.legacy_api.store(false, Ordering::Relaxed);
+            return Ok(status.version);
+        }
+        match self.get_status("api").await? {
+            Some(status) => {
+                self.legacy_api.store(true, Ordering::Relaxed);
+                Ok(status.version)
+            }
+            None => Err(ProviderError::new(
+                Provider::Sonarr,
+                ProviderErrorKind::GetError,
+                "no system status under /api/v3 or /api",
+            )),
+        }
+    }
+    async fn debug(&self, endpoint: Option<&str>) -> Result<String, ProviderError> {
+        let url = format!(
+            "{}/{}/{}",
+            self.address,
+            self.api(),
+            endpoint.unwrap_or("system/status")
+        );
+        get_raw(Provider::Sonarr, &self.client, &url).await
+    }
+    fn tasks(&self) -> Vec<TaskKind> {
+        let mut tasks = Vec::new();
+        if enabled(self.today) {
+            tasks.push(TODAY);
+        }
+        if enabled(self.missing) {
+            tasks.push(MISSING);
+        }
+        tasks
+    }
+    async fn collect(&self, task: TaskKind) -> Result<Vec<TaskResult>, ProviderError> {
+        let result = match task {
+            TODAY => TaskResult::SonarrToday(SonarrEpisodeResult {
+                name: self.name.clone(),
+                episodes: self.get_today_shows().await,
+            }),
+            MISSING => TaskResult::SonarrMissing(SonarrMissingResult {
+                name: self.name.clone(),
+                episodes: self.get_last_week_missing_shows().await,
+            }),
+            _ => return Ok(Vec::new()),
+        };
+        Ok(vec![result])
     }
 }
@@ -1,7 +1,11 @@
-use async_trait::async_trait;
-use ipgeolocate::{Locator, Service};
+use ipnet::IpNet;
 use serde::{Deserialize, Serialize};
 use std::fmt::Display;
+use std::net::{IpAddr, SocketAddr};
+// Provider payloads are deserialized with `#[serde(default)]` so that a field
+// missing after an upgrade only empties that field instead of failing the
+// whole response. Only envelopes and the variants of untagged enums, which are
+// told apart by the fields they have, keep required fields.
 pub mod jellyfin;
 pub mod overseerr;
 pub mod plex;
@@ -9,28 +13,28 @@ pub mod radarr;
 pub mod sonarr;
 pub mod tautulli;
 
-#[async_trait]
-pub trait AsyncFrom<T>: Sized {
-    async fn from_async(value: T) -> Self;
+pub fn parse_ip(address: &str) -> Option<IpAddr> {
+    address
+        .parse::<IpAddr>()
+        .ok()
+        .or_else(|| address.parse::<SocketAddr>().ok().map(|addr| addr.ip()))
 }
 
-async fn get_ip_info(ip: &str) -> Location {
-    let service = Service::IpApi;
-    match Locator::get(ip, service).await {
-        Ok(location) => Location {
-            city: location.city,
-            country: location.country,
-            ip_address: ip.to_string(),
-            latitude: location.latitude,
-            longitude: location.longitude,
-        },
-        Err(_) => Location {
-            city: "Unknown".to_string(),
-            country: "Unknown".to_string(),
-            ip_address: ip.to_string(),
-            latitude: "0.0".to_string(),
-            longitude: "0.0".to_string(),
-        },
+pub fn is_lan_address(address: &str, lan_networks: &[IpNet]) -> bool {
+    let ip = match parse_ip(address) {
+        Some(ip) => ip,
+        None => return false,
+    };
+    if lan_networks.iter().any(|network| network.contains(&ip)) {
+        return true;
+    }
+    match ip {
+        IpAddr::V4(ip) => ip.is_private() || ip.is_loopback() || ip.is_link_local(),
+        IpAddr::V6(ip) => {
+            ip.is_loopback()
+                || (ip.segments()[0] & 0xfe00) == 0xfc00
+                || (ip.segments()[0] & 0xffc0) == 0xfe80
+        }
     }
 }
 
@@ -58,16 +62,33 @@ impl From<plex::StatUser> for User {
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Session {
+    // Identifier given by the provider to the playback, empty when unknown.
+    #[serde(default)]
+    pub id: String,
     pub title: String,
     pub user: String,
     pub stream_decision: StreamDecision,
     pub media_type: String,
     pub state: String,
     pub progress: f64,
+    // Runtime of the media and position of the playback, in seconds, 0 when unknown.
+    #[serde(default)]
+    pub duration: f64,
+    #[serde(default)]
+    pub offset: f64,
     pub quality: String,
     pub season_number: Option<String>,
     pub episode_number: Option<String>,
+    pub show_title: Option<String>,
+    pub season_title: Option<String>,
+    pub episode_title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    // Library of the media, empty when the provider does not tell.
+    #[serde(default)]
+    pub library: String,
     pub address: String,
+    pub hostname: Option<String>,
     pub location: Location,
     pub local: bool,
     pub secure: bool,
@@ -75,15 +96,32 @@ pub struct Session {
     pub platform: String,
     pub bandwidth: Bandwidth,
 }
-#[async_trait]
-impl AsyncFrom<jellyfin::SessionResponse> for Session {
-    async fn from_async(session: jellyfin::SessionResponse) -> Self {
+// The location is left empty, providers fill it with their `Geolocator`.
+impl From<jellyfin::SessionResponse> for Session {
+    fn from(session: jellyfin::SessionResponse) -> Self {
         let mut title = "".to_string();
         let mut media_type = "Unknown".to_string();
         let mut quality = "".to_string();
+        let mut show_title = None;
+        let mut season_title = None;
+        let mut episode_title = None;
+        let mut artist = None;
+        let mut album = None;
         match &session.now_playing_item {
             Some(item) => {
                 title = item.name.clone();
+                match item.type_field.as_str() {
+                    "Episode" => {
+                        show_title = item.series_name.clone();
+                        season_title = item.season_name.clone();
+                        episode_title = Some(item.name.clone());
+                    }
+                    "Audio" => {
+                        artist = item.album_artist.clone();
+                        album = item.album.clone();
+                    }
+                    _ => {}
+                }
                 media_type = item.type_field.clone();
                 let media_stream = &item
                     .media_streams
@@ -96,10 +134,18 @@ impl AsyncFrom<jellyfin::SessionResponse> for Session {
             }
             None => (),
         };
+        // Jellyfin ticks are 100 nanoseconds.
+        let duration = match &session.now_playing_item {
+            Some(item) => item.run_time_ticks as f64 / 10_000_000.0,
+            None => 0.0,
+        };
+        let offset = session.play_state.position_ticks.unwrap_or_default() as f64 / 10_000_000.0;
         let progress = match &session.play_state.position_ticks {
             Some(position) => match &session.now_playing_item {
-                Some(item) => (*position as f64 / item.run_time_ticks as f64) * 100.0,
-                None => 0.0,
+                Some(item) if item.run_time_ticks > 0 => {
+                    (*position as f64 / item.run_time_ticks as f64) * 100.0
+                }
+                _ => 0.0,
             },
             None => 0.0,
         };
@@ -116,7 +162,7 @@ impl AsyncFrom<jellyfin::SessionResponse> for Session {
             },
             None => "Idle",
         };
-        let location = get_ip_info(&session.remote_end_point).await;
+        let location = Location::empty(&session.remote_end_point);
         let stream_decision = match &session.play_state.play_method {
             Some(method) => match method.as_str() {
                 "DirectPlay" => StreamDecision::DirectPlay,
@@ -136,16 +182,26 @@ impl AsyncFrom<jellyfin::SessionResponse> for Session {
         };
 
         Session {
+            id: session.id,
             title: title.to_string(),
             user: session.user_name,
             stream_decision,
             media_type,
             state: state.to_string(),
             progress,
+            duration,
+            offset,
             quality,
             season_number: None,
             episode_number: None,
+            show_title,
+            season_title,
+            episode_title,
+            artist,
+            album,
+            library: String::new(),
             address: session.remote_end_point,
+            hostname: None,
             location,
             local: false,
             secure: false,
@@ -158,27 +214,37 @@ impl AsyncFrom<jellyfin::SessionResponse> for Session {
         }
     }
 }
-#[async_trait]
-impl AsyncFrom<plex::SessionMetadata> for Session {
-    async fn from_async(session: plex::SessionMetadata) -> Self {
+impl From<plex::SessionMetadata> for Session {
+    fn from(session: plex::SessionMetadata) -> Self {
         let media_type = session.type_field.clone();
         let user = session.user.title.clone();
         let state = session.player.state_field.clone();
         let progress = session.progress();
-        let part = &session.media[0].part[0];
-        let video_stream: &plex::Stream = &part.stream.iter().find(|s| s.stream_type == 1).unwrap();
-        let quality = video_stream.display_title.to_string();
-        let season_number = match session.parent_index {
-            Some(index) => Some(index.to_string()),
-            None => None,
+        let part = session.media.first().and_then(|media| media.part.first());
+        let find_stream = |stream_type| {
+            part.and_then(|part| part.stream.iter().find(|s| s.stream_type == stream_type))
+        };
+        // Music and photo sessions have no video stream.
+        let video_stream = find_stream(1);
+        let quality = match video_stream {
+            Some(video_stream) => video_stream.display_title.to_string(),
+            None => "N/A".to_string(),
         };
-        let episode_number = match session.index {
-            Some(index) => Some(index.to_string()),
-            None => None,
+        let is_music_or_photo = matches!(media_type.as_str(), "track" | "photo");
+        let (season_number, episode_number) = match is_music_or_photo {
+            true => (None, None),
+            false => (
+                session.parent_index.map(|index| index.to_string()),
+                session.index.map(|index| index.to_string()),
+            ),
         };
-        let location = get_ip_info(&session.player.remote_public_address).await;
-        let decision = part.decision.clone();
-        let video_stream_decision = match &video_stream.decision {
+        let location = Location::empty(&session.player.remote_public_address);
+        let decision = part.map(|part| part.decision.clone()).unwrap_or_default();
+        // The audio stream tells a direct stream apart for music.
+        let video_stream_decision = match video_stream
+            .or_else(|| find_stream(2))
+            .and_then(|stream| stream.decision.as_ref())
+        {
             Some(decision) => decision.to_string(),
             None => "transcode".to_string(),
         };
@@ -195,26 +261,57 @@ impl AsyncFrom<plex::SessionMetadata> for Session {
         let secure = session.player.secure;
         let relayed = session.player.relayed;
         let platform = session.player.platform.clone();
+        // Tracks have the artist and album as grandparent and parent, photos
+        // have their album as parent.
+        let (artist, album) = match media_type.as_str() {
+            "track" => (
+                session.grand_parent_title.clone(),
+                session.parent_title.clone(),
+            ),
+            "photo" => (None, session.parent_title.clone()),
+            _ => (None, None),
+        };
         let title = match &session.grand_parent_title {
-            Some(parent) => parent.to_string(),
-            None => session.title.clone(),
+            Some(parent) if !is_music_or_photo => parent.to_string(),
+            _ => session.title.clone(),
+        };
+        let (show_title, season_title, episode_title) = match &session.grand_parent_title {
+            Some(show) if !is_music_or_photo => (
+                Some(show.to_string()),
+                session.parent_title.clone(),
+                Some(session.title.clone()),
+            ),
+            _ => (None, None, None),
         };
         let bandwidth = Bandwidth {
             bandwidth: session.session.bandwidth,
             location: session.session.location.clone().into(),
         };
         Session {
+            id: session.session.id.clone(),
             title,
             user,
             stream_decision,
             media_type,
             state,
             progress: progress as f64,
+            duration: session
+                .media
+                .first()
+                .map_or(0.0, |media| media.duration as f64 / 1000.0),
+            offset: session.view_offset as f64 / 1000.0,
             quality,
             season_number,
             episode_number,
+            show_title,
+            season_title,
+            episode_title,
+            artist,
+            album,
+            library: session.library_section_title.clone().unwrap_or_default(),
             location,
             address,
+            hostname: None,
             local,
             secure,
             relayed,
@@ -226,6 +323,7 @@ impl AsyncFrom<plex::SessionMetadata> for Session {
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Bandwidth {
+    // Kilobits per second, as reported by Plex.
     pub bandwidth: i64,
     pub location: BandwidthLocation,
 }
@@ -272,6 +370,16 @@ impl From<String> for StreamDecision {
         }
     }
 }
+impl StreamDecision {
+    pub fn play_method(&self) -> Option<&'static str> {
+        match self {
+            StreamDecision::DirectPlay => Some("DirectPlay"),
+            StreamDecision::DirectStream => Some("DirectStream"),
+            StreamDecision::Transcode => Some("Transcode"),
+            StreamDecision::None => None,
+        }
+    }
+}
 impl Display for StreamDecision {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -290,6 +398,17 @@ pub struct Location {
     pub latitude: String,
     pub longitude: String,
 }
+impl Location {
+    pub fn empty(ip: &str) -> Self {
+        Location {
+            city: String::new(),
+            country: String::new(),
+            ip_address: ip.to_string(),
+            latitude: String::new(),
+            longitude: String::new(),
+        }
+    }
+}
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum MediaType {
@@ -351,3 +470,99 @@ impl From<jellyfin::LibraryInfos> for LibraryCount {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn plex_session(metadata: serde_json::Value) -> Session {
+        let mut metadata = metadata;
+        metadata["User"] = json!({"title": "alice"});
+        metadata["Player"] = json!({"state": "playing", "address": "10.0.0.3"});
+        metadata["Session"] = json!({"id": "abc", "location": "lan", "bandwidth": 1000});
+        serde_json::from_value::<plex::SessionMetadata>(metadata)
+            .unwrap()
+            .into()
+    }
+
+    #[test]
+    fn plex_music_sessions_have_no_video_stream() {
+        let session = plex_session(json!({
+            "title": "Song",
+            "parentTitle": "Album",
+            "grandparentTitle": "Artist",
+            "index": 3,
+            "parentIndex": 1,
+            "type": "track",
+            "Media": [{"duration": 4000, "Part": [{"decision": "transcode", "Stream": [
+                {"streamType": 2, "displayTitle": "FLAC (Stereo)", "decision": "copy"},
+            ]}]}],
+        }));
+        assert_eq!(session.title, "Song");
+        assert_eq!(session.quality, "N/A");
+        assert_eq!(session.stream_decision, StreamDecision::DirectStream);
+        assert_eq!(session.artist.as_deref(), Some("Artist"));
+        assert_eq!(session.album.as_deref(), Some("Album"));
+        assert_eq!(
+            (session.season_number, session.episode_number),
+            (None, None)
+        );
+        assert_eq!(session.show_title, None);
+    }
+
+    #[test]
+    fn plex_photo_sessions_may_have_no_media() {
+        let session = plex_session(json!({
+            "title": "IMG_1",
+            "parentTitle": "Holidays",
+            "type": "photo",
+            "Media": [],
+        }));
+        assert_eq!(session.title, "IMG_1");
+        assert_eq!(session.album.as_deref(), Some("Holidays"));
+        assert_eq!(session.duration, 0.0);
+        assert_eq!(session.stream_decision, StreamDecision::Transcode);
+    }
+
+    #[test]
+    fn plex_episode_sessions_have_show_labels() {
+        let session = plex_session(json!({
+            "title": "Pilot",
+            "parentTitle": "Season 1",
+            "grandparentTitle": "The Show",
+            "index": 1,
+            "parentIndex": 1,
+            "type": "episode",
+            "Media": [{"duration": 60000, "Part": [{"decision": "directplay", "Stream": [
+                {"streamType": 1, "displayTitle": "1080p"},
+            ]}]}],
+        }));
+        assert_eq!(session.title, "The Show");
+        assert_eq!(session.show_title.as_deref(), Some("The Show"));
+        assert_eq!(session.season_title.as_deref(), Some("Season 1"));
+        assert_eq!(session.episode_title.as_deref(), Some("Pilot"));
+        assert_eq!(session.season_number.as_deref(), Some("1"));
+        assert_eq!(session.episode_number.as_deref(), Some("1"));
+        assert_eq!(session.quality, "1080p");
+    }
+
+    #[test]
+    fn lan_addresses_include_private_ranges_and_configured_networks() {
+        let networks: Vec<IpNet> = vec!["100.64.0.0/10".parse().unwrap()];
+        for address in [
+            "192.168.1.10",
+            "10.0.0.3:8096",
+            "127.0.0.1",
+            "[fd00::1]:8096",
+            "fe80::1",
+            "100.64.1.2",
+        ] {
+            assert!(is_lan_address(address, &networks), "{address} is not LAN");
+        }
+        for address in ["8.8.8.8", "[2001:db8::1]:8096", "100.64.1.2:80x", ""] {
+            assert!(!is_lan_address(address, &networks), "{address} is LAN");
+        }
+        assert!(!is_lan_address("100.64.1.2", &[]));
+    }
+}
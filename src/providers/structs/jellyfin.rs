@@ -1,8 +1,12 @@
 use serde::{Deserialize, Serialize};
 
+use crate::providers::deserialize_items;
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
+#[serde(default)]
 pub struct SessionResponse {
+    pub id: String,
     pub play_state: PlayState,
     pub user_name: String,
     pub device_type: Option<String>,
@@ -14,6 +18,7 @@ pub struct SessionResponse {
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
+#[serde(default)]
 pub struct PlayState {
     pub position_ticks: Option<i64>,
     pub is_paused: Option<bool>,
@@ -22,6 +27,7 @@ pub struct PlayState {
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
+#[serde(default)]
 pub struct TranscodingInfo {
     pub is_video_direct: bool,
     pub is_audio_direct: bool,
@@ -29,8 +35,17 @@ pub struct TranscodingInfo {
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
+#[serde(default)]
 pub struct NowPlayingItem {
     pub name: String,
+    #[serde(default)]
+    pub series_name: Option<String>,
+    #[serde(default)]
+    pub season_name: Option<String>,
+    #[serde(default)]
+    pub album: Option<String>,
+    #[serde(default)]
+    pub album_artist: Option<String>,
     pub run_time_ticks: i64,
     #[serde(rename = "Type")]
     pub type_field: String,
@@ -39,6 +54,7 @@ pub struct NowPlayingItem {
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
+#[serde(default)]
 pub struct MediaStream {
     pub codec: String,
     #[serde(rename = "Type")]
@@ -48,6 +64,7 @@ pub struct MediaStream {
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
+#[serde(default)]
 pub struct JellyfinLibraryCounts {
     pub movie_count: i64,
     pub series_count: i64,
@@ -63,6 +80,135 @@ pub struct JellyfinLibraryCounts {
     pub item_count: i64,
 }
 
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+#[serde(default)]
+pub struct VirtualFolder {
+    pub name: String,
+    #[serde(default)]
+    pub collection_type: Option<String>,
+    pub item_id: String,
+    #[serde(default)]
+    pub locations: Vec<String>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+#[serde(default)]
+pub struct ItemsResponse {
+    #[serde(deserialize_with = "deserialize_items")]
+    pub items: Vec<Item>,
+    pub total_record_count: i64,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+#[serde(default)]
+pub struct Item {
+    pub name: String,
+    #[serde(rename = "Type")]
+    pub type_field: String,
+    #[serde(default)]
+    pub date_created: Option<String>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+#[serde(default)]
+pub struct DevicesResponse {
+    #[serde(deserialize_with = "deserialize_items")]
+    pub items: Vec<Device>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+#[serde(default)]
+pub struct Device {
+    pub name: String,
+    #[serde(default)]
+    pub app_name: String,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+#[serde(default)]
+pub struct Plugin {
+    pub name: String,
+    pub version: String,
+    pub id: String,
+    #[serde(default)]
+    pub status: Option<String>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[serde(default)]
+pub struct Package {
+    pub name: String,
+    #[serde(default)]
+    pub guid: String,
+    #[serde(default)]
+    pub versions: Vec<PackageVersion>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[serde(default)]
+pub struct PackageVersion {
+    pub version: String,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+#[serde(default)]
+pub struct PublicSystemInfo {
+    pub version: String,
+    #[serde(default)]
+    pub product_name: Option<String>,
+    #[serde(default)]
+    pub operating_system: Option<String>,
+    #[serde(default)]
+    pub startup_wizard_completed: Option<bool>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+#[serde(default)]
+pub struct SystemInfo {
+    #[serde(default)]
+    pub operating_system: Option<String>,
+    #[serde(default)]
+    pub has_pending_restart: bool,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+#[serde(default)]
+pub struct StorageInfo {
+    #[serde(default)]
+    pub libraries: Vec<LibraryStorage>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+#[serde(default)]
+pub struct LibraryStorage {
+    pub name: String,
+    #[serde(default)]
+    pub folders: Vec<FolderStorage>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+#[serde(default)]
+pub struct FolderStorage {
+    pub path: String,
+    #[serde(default)]
+    pub free_space: i64,
+    #[serde(default)]
+    pub used_space: i64,
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct Users {
@@ -71,6 +217,7 @@ pub struct Users {
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
+#[serde(default)]
 pub struct User {
     pub name: String,
 }
@@ -117,3 +264,43 @@ impl From<JellyfinLibraryCounts> for Vec<LibraryInfos> {
         ]
     }
 }
+
+// Body of the Jellyfin webhook plugin, built from the template given in the
+// README.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+#[serde(default)]
+pub struct WebhookPayload {
+    pub notification_type: String,
+    pub notification_username: String,
+    pub item_type: String,
+    pub play_method: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn virtual_folders_read_the_library_type() {
+        let folders: Vec<VirtualFolder> = serde_json::from_value(json!([
+            {"Name": "Movies", "CollectionType": "movies", "ItemId": "1", "Locations": ["/media/movies"]},
+            {"Name": "Mixed", "ItemId": "2", "RefreshStatus": "Idle"},
+        ]))
+        .unwrap();
+        assert_eq!(folders[0].collection_type.as_deref(), Some("movies"));
+        assert_eq!(folders[0].locations, ["/media/movies"]);
+        assert_eq!(folders[1].collection_type, None);
+        assert_eq!(folders[1].item_id, "2");
+    }
+
+    #[test]
+    fn item_counts_come_without_items() {
+        let items: ItemsResponse =
+            serde_json::from_value(json!({"Items": [], "TotalRecordCount": 42, "StartIndex": 0}))
+                .unwrap();
+        assert!(items.items.is_empty());
+        assert_eq!(items.total_record_count, 42);
+    }
+}
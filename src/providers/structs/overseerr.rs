@@ -1,13 +1,30 @@
 use serde::{Deserialize, Serialize};
 
+use crate::providers::deserialize_items;
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
+#[serde(default)]
 pub struct Request {
+    #[serde(default)]
+    pub page_info: PageInfo,
+    #[serde(deserialize_with = "deserialize_items")]
     pub results: Vec<Result>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
+#[serde(default)]
+pub struct PageInfo {
+    pub pages: i64,
+    pub page_size: i64,
+    pub results: i64,
+    pub page: i64,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[serde(default)]
 pub struct Result {
     pub status: i64,
     pub created_at: String,
@@ -18,6 +35,7 @@ pub struct Result {
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
+#[serde(default)]
 pub struct Media {
     pub id: i64,
     pub media_type: String,
@@ -28,6 +46,7 @@ pub struct Media {
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
+#[serde(default)]
 pub struct RequestedBy {
     pub permissions: i64,
     pub id: i64,
@@ -39,12 +58,91 @@ pub struct RequestedBy {
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
+#[serde(default)]
 pub struct Movie {
     pub original_title: Option<String>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
+#[serde(default)]
 pub struct Tv {
     pub name: String,
 }
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[serde(default)]
+pub struct RequestCount {
+    pub total: i64,
+    pub movie: i64,
+    pub tv: i64,
+    pub pending: i64,
+    pub approved: i64,
+    pub declined: i64,
+    pub processing: i64,
+    pub available: i64,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[serde(default)]
+pub struct Job {
+    pub id: String,
+    pub name: String,
+    #[serde(rename = "type")]
+    pub type_field: String,
+    #[serde(default)]
+    pub next_execution_time: Option<String>,
+    #[serde(default)]
+    pub running: bool,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[serde(default)]
+pub struct Status {
+    pub version: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn request_count_reads_the_count_endpoint() {
+        let count: RequestCount = serde_json::from_value(json!({
+            "total": 12,
+            "movie": 7,
+            "tv": 5,
+            "pending": 2,
+            "approved": 6,
+            "declined": 1,
+            "processing": 3,
+            "available": 4,
+            "completed": 9,
+        }))
+        .unwrap();
+        assert_eq!(
+            count,
+            RequestCount {
+                total: 12,
+                movie: 7,
+                tv: 5,
+                pending: 2,
+                approved: 6,
+                declined: 1,
+                processing: 3,
+                available: 4,
+            }
+        );
+        // Older versions have no processing or available count.
+        let count: RequestCount =
+            serde_json::from_value(json!({"total": 1, "pending": 1})).unwrap();
+        assert_eq!(
+            (count.pending, count.processing, count.available),
+            (1, 0, 0)
+        );
+    }
+}
@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 use std::fmt::Display;
 
+use crate::providers::deserialize_items;
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PlexResponse {
@@ -46,7 +48,7 @@ pub struct MediaContainerDefault {
 #[serde(rename_all = "camelCase")]
 pub struct StatisticsContainer {
     pub size: i64,
-    #[serde(rename = "Account")]
+    #[serde(rename = "Account", deserialize_with = "deserialize_items")]
     pub account: Vec<StatUser>,
 }
 
@@ -54,7 +56,7 @@ pub struct StatisticsContainer {
 #[serde(rename_all = "camelCase")]
 pub struct LibraryContainer {
     pub size: i64,
-    #[serde(rename = "Directory")]
+    #[serde(rename = "Directory", deserialize_with = "deserialize_items")]
     pub directory: Vec<Directory>,
 }
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -83,6 +85,7 @@ impl Default for LibraryItemsContainer {
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
+#[serde(default)]
 pub struct Directory {
     pub key: String,
     pub title: String,
@@ -114,6 +117,7 @@ pub struct SessionMetadata {
     pub title: String,
     pub original_title: Option<String>,
     pub parent_title: Option<String>,
+    #[serde(alias = "grandparentTitle")]
     pub grand_parent_title: Option<String>,
     pub index: Option<i64>,
     pub parent_index: Option<i64>,
@@ -127,11 +131,17 @@ pub struct SessionMetadata {
     pub player: Player,
     #[serde(rename = "Session")]
     pub session: Session,
+    #[serde(default)]
     pub view_offset: i64,
+    #[serde(default)]
+    pub library_section_title: Option<String>,
 }
 impl SessionMetadata {
     pub fn progress(&self) -> i64 {
-        let duration = self.media[0].duration;
+        let duration = match self.media.first() {
+            Some(media) if media.duration > 0 => media.duration,
+            _ => return 0,
+        };
         let offset = self.view_offset;
         let progress = (offset as f64 / duration as f64) * 100.0;
         progress as i64
@@ -140,6 +150,7 @@ impl SessionMetadata {
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
+#[serde(default)]
 pub struct Media {
     #[serde(rename = "Part")]
     pub part: Vec<Part>,
@@ -147,6 +158,7 @@ pub struct Media {
 }
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
+#[serde(default)]
 pub struct Part {
     pub decision: String,
     pub container: String,
@@ -155,6 +167,7 @@ pub struct Part {
 }
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
+#[serde(default)]
 pub struct Stream {
     pub display_title: String,
     pub stream_type: i64,
@@ -163,11 +176,13 @@ pub struct Stream {
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
+#[serde(default)]
 pub struct StatUser {
     pub name: String,
 }
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
+#[serde(default)]
 pub struct User {
     pub title: String,
 }
@@ -181,6 +196,7 @@ impl From<StatUser> for User {
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
+#[serde(default)]
 pub struct Player {
     pub platform: String,
     #[serde(rename = "state")]
@@ -195,7 +211,9 @@ pub struct Player {
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
+#[serde(default)]
 pub struct Session {
+    pub id: String,
     pub location: String,
     pub bandwidth: i64,
 }
@@ -241,3 +259,62 @@ pub struct LibraryInfos {
     pub library_child_size: Option<i64>,
     pub library_grand_child_size: Option<i64>,
 }
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookPayload {
+    pub event: String,
+    #[serde(rename = "Account")]
+    pub account: Option<WebhookAccount>,
+    #[serde(rename = "Metadata")]
+    pub metadata: Option<WebhookMetadata>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[serde(default)]
+pub struct WebhookAccount {
+    pub title: String,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[serde(default)]
+pub struct WebhookMetadata {
+    #[serde(rename = "type")]
+    pub type_field: Option<String>,
+    pub title: Option<String>,
+    pub library_section_title: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MyPlexResponse {
+    #[serde(rename = "MyPlex")]
+    pub my_plex: MyPlexAccount,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[serde(default)]
+pub struct MyPlexAccount {
+    #[serde(default)]
+    pub mapping_state: String,
+    #[serde(default)]
+    pub mapping_error: String,
+    #[serde(default)]
+    pub sign_in_state: String,
+}
+
+// Answer of /identity, which needs no token.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Identity {
+    #[serde(rename = "MediaContainer")]
+    pub media_container: IdentityContainer,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[serde(default)]
+pub struct IdentityContainer {
+    pub version: String,
+}
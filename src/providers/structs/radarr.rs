@@ -3,6 +3,7 @@ use serde_json::Value;
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
+#[serde(default)]
 pub struct Movie {
     pub title: String,
     #[serde(rename = "originalTitle")]
@@ -64,6 +65,7 @@ pub struct Movie {
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
+#[serde(default)]
 pub struct OriginalLanguage {
     pub id: i64,
     pub name: String,
@@ -71,6 +73,7 @@ pub struct OriginalLanguage {
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
+#[serde(default)]
 pub struct AlternateTitle {
     pub source_type: String,
     pub movie_metadata_id: i64,
@@ -80,6 +83,7 @@ pub struct AlternateTitle {
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
+#[serde(default)]
 pub struct Image {
     pub cover_type: String,
     pub url: String,
@@ -88,12 +92,14 @@ pub struct Image {
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
+#[serde(default)]
 pub struct Ratings {
     pub tmdb: Tmdb,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
+#[serde(default)]
 pub struct Tmdb {
     pub votes: i64,
     pub value: f64,
@@ -103,8 +109,16 @@ pub struct Tmdb {
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
+#[serde(default)]
 pub struct Statistics {
     pub movie_file_count: i64,
     pub size_on_disk: i64,
     pub release_groups: Vec<Value>,
 }
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[serde(default)]
+pub struct Status {
+    pub version: String,
+}
@@ -3,6 +3,7 @@ use serde_json::Value;
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
+#[serde(default)]
 pub struct Status {
     pub app_name: String,
     pub instance_name: String,
@@ -47,6 +48,7 @@ pub struct Status {
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
+#[serde(default)]
 pub struct SqliteVersion {
     pub major: i64,
     pub minor: i64,
@@ -58,6 +60,7 @@ pub struct SqliteVersion {
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
+#[serde(default)]
 pub struct RuntimeVersion {
     pub major: i64,
     pub minor: i64,
@@ -69,6 +72,7 @@ pub struct RuntimeVersion {
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
+#[serde(default)]
 pub struct DatabaseVersion {
     pub major: i64,
     pub minor: i64,
@@ -80,6 +84,7 @@ pub struct DatabaseVersion {
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
+#[serde(default)]
 pub struct Calendar {
     pub series_id: i64,
     pub tvdb_id: i64,
@@ -102,6 +107,7 @@ pub struct Calendar {
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
+#[serde(default)]
 pub struct EpisodeFile {
     pub series_id: i64,
     pub season_number: i64,
@@ -119,6 +125,7 @@ pub struct EpisodeFile {
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
+#[serde(default)]
 pub struct Episode {
     pub series_id: i64,
     pub season_number: i64,
@@ -136,6 +143,7 @@ pub struct Episode {
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
+#[serde(default)]
 pub struct Series {
     pub title: String,
     pub sort_title: String,
@@ -175,6 +183,7 @@ pub struct Series {
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
+#[serde(default)]
 pub struct Image {
     pub cover_type: String,
     pub remote_url: String,
@@ -182,6 +191,7 @@ pub struct Image {
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
+#[serde(default)]
 pub struct OriginalLanguage {
     pub id: i64,
     pub name: String,
@@ -189,6 +199,7 @@ pub struct OriginalLanguage {
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
+#[serde(default)]
 pub struct Season {
     pub season_number: i64,
     pub monitored: bool,
@@ -196,6 +207,7 @@ pub struct Season {
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
+#[serde(default)]
 pub struct Ratings {
     pub votes: i64,
     pub value: f64,
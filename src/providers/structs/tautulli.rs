@@ -1,44 +1,69 @@
-use serde::{Deserialize, Serialize};
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize};
 use serde_json::Value;
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct TautulliResponse {
-    pub response: ActivityResponse,
-}
+use crate::providers::deserialize_items;
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct ActivityResponse {
-    pub result: String,
-    pub message: Option<String>,
-    pub data: TautulliData,
+// Depending on its version, Tautulli sends some of its numbers as strings and
+// some of its strings as numbers. A null is read as the default value.
+fn string_or_number<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    match Value::deserialize(deserializer)? {
+        Value::String(value) => Ok(value),
+        Value::Number(value) => Ok(value.to_string()),
+        Value::Bool(value) => Ok(u8::from(value).to_string()),
+        Value::Null => Ok(String::new()),
+        value => Err(D::Error::custom(format!(
+            "expected a string or a number, found {value}"
+        ))),
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(untagged)]
-pub enum TautulliData {
-    Activity(Activity),
-    Libraries(Vec<Library>),
-}
-impl Into<Activity> for TautulliData {
-    fn into(self) -> Activity {
-        match self {
-            TautulliData::Activity(activity) => activity,
-            _ => panic!("TautulliData is not Activity"),
-        }
+fn option_string_or_number<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<String>, D::Error> {
+    match Value::deserialize(deserializer)? {
+        Value::Null => Ok(None),
+        value => string_or_number(value).map(Some).map_err(D::Error::custom),
     }
 }
-impl Into<Vec<Library>> for TautulliData {
-    fn into(self) -> Vec<Library> {
-        match self {
-            TautulliData::Libraries(libraries) => libraries,
-            _ => panic!("TautulliData is not Libraries"),
-        }
+
+fn number_or_string<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i64, D::Error> {
+    match Value::deserialize(deserializer)? {
+        Value::Number(value) => value
+            .as_i64()
+            .or_else(|| value.as_f64().map(|value| value as i64))
+            .ok_or_else(|| D::Error::custom(format!("{value} is out of range"))),
+        Value::String(value) if value.trim().is_empty() => Ok(0),
+        Value::String(value) => value
+            .trim()
+            .parse::<f64>()
+            .map(|value| value as i64)
+            .map_err(|_| D::Error::custom(format!("expected a number, found {value:?}"))),
+        Value::Bool(value) => Ok(i64::from(value)),
+        Value::Null => Ok(0),
+        value => Err(D::Error::custom(format!(
+            "expected a number, found {value}"
+        ))),
     }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+pub struct CommandResponse<T> {
+    pub response: CommandData<T>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CommandData<T> {
+    pub result: String,
+    pub message: Option<String>,
+    pub data: T,
+}
+
+#[derive(Default, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Activity {
     pub stream_count: String,
+    #[serde(deserialize_with = "deserialize_items")]
     pub sessions: Vec<Session>,
     pub stream_count_direct_play: i64,
     pub stream_count_direct_stream: i64,
@@ -48,21 +73,30 @@ pub struct Activity {
     pub wan_bandwidth: i64,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Default, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Session {
+    #[serde(deserialize_with = "string_or_number")]
     pub session_key: String,
     pub media_type: String,
+    #[serde(deserialize_with = "string_or_number")]
     pub view_offset: String,
+    #[serde(deserialize_with = "string_or_number")]
     pub progress_percent: String,
     pub quality_profile: String,
     pub synced_version_profile: String,
     pub optimized_version_profile: String,
     pub user: String,
+    #[serde(deserialize_with = "number_or_string")]
     pub channel_stream: i64,
+    #[serde(deserialize_with = "string_or_number")]
     pub section_id: String,
     pub library_name: String,
+    #[serde(deserialize_with = "string_or_number")]
     pub rating_key: String,
+    #[serde(deserialize_with = "string_or_number")]
     pub parent_rating_key: String,
+    #[serde(deserialize_with = "string_or_number")]
     pub grandparent_rating_key: String,
     pub title: String,
     pub parent_title: String,
@@ -70,20 +104,29 @@ pub struct Session {
     pub original_title: String,
     pub sort_title: String,
     pub edition_title: String,
+    #[serde(deserialize_with = "string_or_number")]
     pub media_index: String,
+    #[serde(deserialize_with = "string_or_number")]
     pub parent_media_index: String,
     pub studio: String,
     pub content_rating: String,
     pub summary: String,
     pub tagline: String,
+    #[serde(deserialize_with = "string_or_number")]
     pub rating: String,
     pub rating_image: String,
+    #[serde(deserialize_with = "string_or_number")]
     pub audience_rating: String,
     pub audience_rating_image: String,
+    #[serde(deserialize_with = "string_or_number")]
     pub user_rating: String,
+    #[serde(deserialize_with = "string_or_number")]
     pub duration: String,
+    #[serde(deserialize_with = "string_or_number")]
     pub year: String,
+    #[serde(deserialize_with = "string_or_number")]
     pub parent_year: String,
+    #[serde(deserialize_with = "string_or_number")]
     pub grandparent_year: String,
     pub thumb: String,
     pub parent_thumb: String,
@@ -91,8 +134,11 @@ pub struct Session {
     pub art: String,
     pub banner: String,
     pub originally_available_at: String,
+    #[serde(deserialize_with = "string_or_number")]
     pub added_at: String,
+    #[serde(deserialize_with = "string_or_number")]
     pub updated_at: String,
+    #[serde(deserialize_with = "string_or_number")]
     pub last_viewed_at: String,
     pub guid: String,
     pub parent_guid: String,
@@ -108,13 +154,20 @@ pub struct Session {
     pub parent_guids: Vec<Value>,
     pub grandparent_guids: Vec<Value>,
     pub full_title: String,
+    #[serde(deserialize_with = "number_or_string")]
     pub children_count: i64,
+    #[serde(deserialize_with = "number_or_string")]
     pub live: i64,
+    #[serde(deserialize_with = "string_or_number")]
     pub id: String,
     pub container: String,
+    #[serde(deserialize_with = "string_or_number")]
     pub bitrate: String,
+    #[serde(deserialize_with = "string_or_number")]
     pub height: String,
+    #[serde(deserialize_with = "string_or_number")]
     pub width: String,
+    #[serde(deserialize_with = "string_or_number")]
     pub aspect_ratio: String,
     pub video_codec: String,
     pub video_resolution: String,
@@ -122,21 +175,28 @@ pub struct Session {
     pub video_framerate: String,
     pub video_profile: String,
     pub audio_codec: String,
+    #[serde(deserialize_with = "string_or_number")]
     pub audio_channels: String,
     pub audio_channel_layout: String,
     pub audio_profile: String,
+    #[serde(deserialize_with = "number_or_string")]
     pub optimized_version: i64,
     pub channel_call_sign: String,
     pub channel_identifier: String,
     pub channel_thumb: String,
     pub file: String,
+    #[serde(deserialize_with = "string_or_number")]
     pub file_size: String,
+    #[serde(deserialize_with = "number_or_string")]
     pub indexes: i64,
+    #[serde(deserialize_with = "number_or_string")]
     pub selected: i64,
     #[serde(rename = "type")]
     pub type_field: Option<String>,
     pub video_codec_level: String,
+    #[serde(deserialize_with = "string_or_number")]
     pub video_bitrate: String,
+    #[serde(deserialize_with = "string_or_number")]
     pub video_bit_depth: String,
     pub video_chroma_subsampling: String,
     pub video_color_primaries: String,
@@ -145,38 +205,55 @@ pub struct Session {
     pub video_color_trc: String,
     pub video_dynamic_range: String,
     pub video_frame_rate: String,
+    #[serde(deserialize_with = "string_or_number")]
     pub video_ref_frames: String,
+    #[serde(deserialize_with = "string_or_number")]
     pub video_height: String,
+    #[serde(deserialize_with = "string_or_number")]
     pub video_width: String,
     pub video_language: String,
     pub video_language_code: String,
     pub video_scan_type: String,
+    #[serde(deserialize_with = "string_or_number")]
     pub audio_bitrate: String,
     pub audio_bitrate_mode: String,
+    #[serde(deserialize_with = "string_or_number")]
     pub audio_sample_rate: String,
     pub audio_language: String,
     pub audio_language_code: String,
     pub subtitle_codec: String,
     pub subtitle_container: String,
     pub subtitle_format: String,
+    #[serde(deserialize_with = "number_or_string")]
     pub subtitle_forced: i64,
     pub subtitle_location: String,
     pub subtitle_language: String,
     pub subtitle_language_code: String,
+    #[serde(deserialize_with = "number_or_string")]
     pub row_id: i64,
+    #[serde(deserialize_with = "number_or_string")]
     pub user_id: i64,
     pub username: String,
     pub friendly_name: String,
     pub user_thumb: String,
     pub email: String,
+    #[serde(deserialize_with = "number_or_string")]
     pub is_active: i64,
+    #[serde(deserialize_with = "number_or_string")]
     pub is_admin: i64,
+    #[serde(deserialize_with = "number_or_string")]
     pub is_home_user: i64,
+    #[serde(deserialize_with = "number_or_string")]
     pub is_allow_sync: i64,
+    #[serde(deserialize_with = "number_or_string")]
     pub is_restricted: i64,
+    #[serde(deserialize_with = "number_or_string")]
     pub do_notify: i64,
+    #[serde(deserialize_with = "number_or_string")]
     pub keep_history: i64,
+    #[serde(deserialize_with = "number_or_string")]
     pub deleted_user: i64,
+    #[serde(deserialize_with = "number_or_string")]
     pub allow_guest: i64,
     pub shared_libraries: Vec<String>,
     pub last_seen: Value,
@@ -277,7 +354,8 @@ pub struct Session {
     pub stream_subtitle_transient: i64,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Default, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Marker {
     pub id: i64,
     #[serde(rename = "type")]
@@ -291,6 +369,7 @@ pub struct Marker {
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
+#[serde(default)]
 pub struct LibraryResponse {
     #[serde(default)]
     #[serde(rename = "recordsFiltered")]
@@ -298,14 +377,16 @@ pub struct LibraryResponse {
     #[serde(default)]
     #[serde(rename = "recordsTotal")]
     pub records_total: Option<i64>,
+    #[serde(deserialize_with = "deserialize_items")]
     pub data: Vec<Library>,
     pub draw: i64,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
+#[serde(default)]
 pub struct Library {
-    #[serde(rename = "section_id")]
+    #[serde(rename = "section_id", deserialize_with = "string_or_number")]
     pub section_id: String,
     #[serde(rename = "section_name")]
     pub section_name: String,
@@ -314,12 +395,13 @@ pub struct Library {
     pub agent: String,
     pub thumb: String,
     pub art: String,
+    #[serde(deserialize_with = "string_or_number")]
     pub count: String,
-    #[serde(rename = "is_active")]
+    #[serde(rename = "is_active", deserialize_with = "number_or_string")]
     pub is_active: i64,
-    #[serde(rename = "parent_count")]
+    #[serde(rename = "parent_count", deserialize_with = "option_string_or_number")]
     pub parent_count: Option<String>,
-    #[serde(rename = "child_count")]
+    #[serde(rename = "child_count", deserialize_with = "option_string_or_number")]
     pub child_count: Option<String>,
 }
 impl std::fmt::Display for Library {
@@ -349,3 +431,224 @@ impl std::fmt::Display for Library {
         }
     }
 }
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HomeStat {
+    pub stat_id: String,
+    #[serde(deserialize_with = "deserialize_items")]
+    pub rows: Vec<HomeStatRow>,
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HomeStatRow {
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub friendly_name: Option<String>,
+    #[serde(default)]
+    pub platform: Option<String>,
+    #[serde(default)]
+    pub total_plays: Option<i64>,
+    #[serde(default)]
+    pub total_duration: Option<i64>,
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct History {
+    #[serde(rename = "recordsFiltered")]
+    pub records_filtered: i64,
+    #[serde(deserialize_with = "deserialize_items")]
+    pub data: Vec<HistoryRow>,
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HistoryRow {
+    #[serde(default)]
+    pub id: Option<i64>,
+    #[serde(default)]
+    pub row_id: Option<i64>,
+    #[serde(default)]
+    pub friendly_name: Option<String>,
+    #[serde(default)]
+    pub user: Option<String>,
+    #[serde(default)]
+    pub media_type: Option<String>,
+    #[serde(default)]
+    pub play_duration: Option<i64>,
+    #[serde(default)]
+    pub duration: Option<i64>,
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct User {
+    pub user_id: i64,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub friendly_name: Option<String>,
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WatchTimeStat {
+    pub query_days: i64,
+    #[serde(default)]
+    pub total_time: i64,
+    #[serde(default)]
+    pub total_plays: i64,
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LibraryMediaInfo {
+    #[serde(default)]
+    pub total_file_size: Option<i64>,
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TautulliInfo {
+    pub tautulli_version: String,
+    #[serde(default)]
+    pub tautulli_branch: Option<String>,
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UpdateCheck {
+    #[serde(default)]
+    pub update: Option<bool>,
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ServerStatus {
+    #[serde(default)]
+    pub connected: Option<bool>,
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotificationLog {
+    #[serde(deserialize_with = "deserialize_items")]
+    pub data: Vec<NotificationLogRow>,
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotificationLogRow {
+    #[serde(default)]
+    pub agent_name: Option<String>,
+    #[serde(default)]
+    pub notify_action: Option<String>,
+    #[serde(default)]
+    pub success: Option<i64>,
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlaysByDate {
+    #[serde(default)]
+    pub categories: Vec<String>,
+    #[serde(default)]
+    pub series: Vec<PlaysSeries>,
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlaysSeries {
+    pub name: String,
+    #[serde(default)]
+    pub data: Vec<Option<i64>>,
+}
+
+// Body of the Tautulli webhook notification agent, built from the JSON
+// template given in the README.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebhookPayload {
+    pub action: String,
+    pub user: String,
+    pub media_type: String,
+    pub transcode_decision: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn numbers_and_strings_are_read_either_way() {
+        let library: Library = serde_json::from_value(json!({
+            "section_id": 1,
+            "section_name": "Shows",
+            "section_type": "show",
+            "count": "12",
+            "is_active": "1",
+            "parent_count": 40,
+            "child_count": null,
+        }))
+        .unwrap();
+        assert_eq!(library.section_id, "1");
+        assert_eq!(library.count, "12");
+        assert_eq!(library.is_active, 1);
+        assert_eq!(library.parent_count.as_deref(), Some("40"));
+        assert_eq!(library.child_count, None);
+    }
+
+    #[test]
+    fn empty_and_null_values_are_defaults() {
+        let session: Session = serde_json::from_value(json!({
+            "session_key": null,
+            "view_offset": 1500,
+            "rating": 7.5,
+            "channel_stream": " ",
+            "year": true,
+        }))
+        .unwrap();
+        assert_eq!(session.session_key, "");
+        assert_eq!(session.view_offset, "1500");
+        assert_eq!(session.rating, "7.5");
+        assert_eq!(session.channel_stream, 0);
+        assert_eq!(session.year, "1");
+    }
+
+    #[test]
+    fn other_values_are_rejected() {
+        let library = serde_json::from_value::<Library>(json!({"count": [12]}));
+        assert!(library.is_err());
+        let library = serde_json::from_value::<Library>(json!({"is_active": "yes"}));
+        assert!(library.is_err());
+    }
+
+    #[test]
+    fn activity_reads_the_stream_counts() {
+        let activity: Activity = serde_json::from_value(json!({
+            "stream_count": "3",
+            "stream_count_direct_play": 1,
+            "stream_count_direct_stream": 0,
+            "stream_count_transcode": 2,
+            "total_bandwidth": 12000,
+            "lan_bandwidth": 2000,
+            "wan_bandwidth": 10000,
+            "sessions": [{"session_key": 1, "user": "alice"}],
+        }))
+        .unwrap();
+        assert_eq!(activity.stream_count, "3");
+        assert_eq!(
+            (
+                activity.stream_count_direct_play,
+                activity.stream_count_direct_stream,
+                activity.stream_count_transcode
+            ),
+            (1, 0, 2)
+        );
+        assert_eq!(activity.sessions[0].user, "alice");
+    }
+}
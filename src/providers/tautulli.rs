@@ -1,21 +1,102 @@
-use ipgeolocate::{Locator, Service};
-use log::error;
+use async_trait::async_trait;
+use futures::future::join_all;
+use log::{debug, error};
 use reqwest;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use tracing::instrument;
 
-use crate::providers::structs::tautulli;
-pub use crate::providers::structs::tautulli::Library;
-use crate::providers::{Provider, ProviderError, ProviderErrorKind};
+use crate::providers::geoip::Geolocator;
+pub use crate::providers::structs::tautulli::{Library, WebhookPayload};
+use crate::providers::structs::{tautulli, Location};
+use crate::providers::{
+    check_endpoint, get_raw, parse_json, record_failure, ClientOptions, MetricProvider, Provider,
+    ProviderError, ProviderErrorKind, SendTimed,
+};
+use crate::tasks::{
+    enabled, TaskKind, TaskResult, TautulliHistoryResult, TautulliHomeStatsResult,
+    TautulliLibraryResult, TautulliNotificationsResult, TautulliPlaysByDateResult,
+    TautulliServerResult, TautulliSessionResult, TautulliUserWatchTimeResult,
+};
+
+const SESSIONS: TaskKind = TaskKind {
+    name: "tautulli_session",
+    flag: Some("sessions"),
+};
+const LIBRARIES: TaskKind = TaskKind {
+    name: "tautulli_library",
+    flag: Some("libraries"),
+};
+const HOME_STATS: TaskKind = TaskKind {
+    name: "tautulli_home_stats",
+    flag: Some("home_stats"),
+};
+const HISTORY: TaskKind = TaskKind {
+    name: "tautulli_history",
+    flag: Some("history"),
+};
+const USER_WATCH_TIME: TaskKind = TaskKind {
+    name: "tautulli_user_watch_time",
+    flag: Some("user_watch_time"),
+};
+const SERVER: TaskKind = TaskKind {
+    name: "tautulli_server",
+    flag: Some("server"),
+};
+const NOTIFICATIONS: TaskKind = TaskKind {
+    name: "tautulli_notifications",
+    flag: Some("notifications"),
+};
+const PLAYS_BY_DATE: TaskKind = TaskKind {
+    name: "tautulli_plays_by_date",
+    flag: Some("plays_by_date"),
+};
+
+// Id of the newest history row counted by the metrics registry, by instance.
+// Collections only page back to it, the registry skips the rows it already
+// counted.
+static HISTORY_CURSORS: Mutex<BTreeMap<String, i64>> = Mutex::new(BTreeMap::new());
+
+/// Called by the metrics registry once the rows up to `id` are counted.
+pub fn set_history_cursor(name: &str, id: i64) {
+    if let Ok(mut cursors) = HISTORY_CURSORS.lock() {
+        let cursor = cursors.entry(name.to_string()).or_insert(id);
+        *cursor = (*cursor).max(id);
+    }
+}
 
 #[derive(Debug, Deserialize, Clone, Serialize)]
 pub struct Tautulli {
+    #[serde(default = "default_name")]
+    pub name: String,
     pub address: String,
     #[serde(rename = "apikey")]
     pub api_key: String,
-    #[serde(default)]
+    pub home_stats_days: Option<i64>,
+    pub home_stats_count: Option<i64>,
+    pub plays_by_date_days: Option<i64>,
+    pub sessions: Option<bool>,
+    pub libraries: Option<bool>,
+    pub home_stats: Option<bool>,
+    pub history: Option<bool>,
+    pub user_watch_time: Option<bool>,
+    pub server: Option<bool>,
+    pub notifications: Option<bool>,
+    pub plays_by_date: Option<bool>,
+    #[serde(skip)]
     api_url: String,
+    #[serde(flatten)]
+    pub client_options: ClientOptions,
     #[serde(skip)]
     client: reqwest::Client,
+    #[serde(skip)]
+    pub geoip: Geolocator,
+}
+
+fn default_name() -> String {
+    "tautulli".to_string()
 }
 
 #[derive(Debug, Deserialize, Clone, Serialize)]
@@ -27,7 +108,88 @@ pub struct TautulliLocation {
     pub longitude: String,
 }
 
+impl From<Location> for TautulliLocation {
+    fn from(location: Location) -> Self {
+        TautulliLocation {
+            city: location.city,
+            country: location.country,
+            ip_address: location.ip_address,
+            latitude: location.latitude,
+            longitude: location.longitude,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HomeStatSummary {
+    pub stat: String,
+    pub label: String,
+    pub plays: i64,
+    pub duration: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryPlay {
+    pub id: i64,
+    pub user: String,
+    pub media_type: String,
+    pub duration: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserWatchTime {
+    pub user: String,
+    pub days: i64,
+    pub plays: i64,
+    pub duration: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LibraryFileSize {
+    pub section_name: String,
+    pub section_type: String,
+    pub file_size: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TautulliServerInfo {
+    pub version: String,
+    pub branch: String,
+    pub update_available: Option<bool>,
+    pub plex_connected: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotificationSummary {
+    pub agent: String,
+    pub sent: i64,
+    pub failed: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DailyPlays {
+    pub date: String,
+    pub media_type: String,
+    pub plays: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActivityStats {
+    pub total_bandwidth: i64,
+    pub lan_bandwidth: i64,
+    pub wan_bandwidth: i64,
+    pub stream_count_direct_play: i64,
+    pub stream_count_direct_stream: i64,
+    pub stream_count_transcode: i64,
+}
+
 #[derive(Debug, Clone)]
+pub struct ActivitySummary {
+    pub sessions: Vec<SessionSummary>,
+    pub stats: Option<ActivityStats>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SessionSummary {
     pub user: String,
     pub title: String,
@@ -61,19 +223,53 @@ impl std::fmt::Display for SessionSummary {
 }
 
 impl Tautulli {
-    pub fn new(address: &str, api_key: &str) -> Result<Tautulli, ProviderError> {
+    pub fn new(
+        name: &str,
+        address: &str,
+        api_key: &str,
+        home_stats_days: i64,
+        home_stats_count: i64,
+        plays_by_date_days: i64,
+        options: &ClientOptions,
+    ) -> Result<Tautulli, ProviderError> {
         let api_url = format!("{}/api/v2?apikey={}&cmd=", address, api_key);
-        let client = reqwest::Client::builder().build()?;
+        let client = options.apply(reqwest::Client::builder())?.build()?;
         Ok(Tautulli {
+            name: name.to_string(),
             api_key: api_key.to_string(),
             address: address.to_string(),
+            home_stats_days: Some(home_stats_days),
+            home_stats_count: Some(home_stats_count),
+            plays_by_date_days: Some(plays_by_date_days),
+            sessions: None,
+            libraries: None,
+            home_stats: None,
+            history: None,
+            user_watch_time: None,
+            server: None,
+            notifications: None,
+            plays_by_date: None,
             api_url,
+            client_options: options.clone(),
             client,
+            geoip: Geolocator::default(),
         })
     }
-    pub async fn get(&self, command: &str) -> Result<tautulli::TautulliData, ProviderError> {
+    #[instrument(skip(self, params))]
+    async fn get_command<T: DeserializeOwned>(
+        &self,
+        command: &str,
+        params: &[(&str, String)],
+    ) -> Result<T, ProviderError> {
         let url = format!("{}{}", self.api_url, command);
-        let response = match self.client.get(&url).send().await {
+        debug!("Requesting {} from Tautulli", command);
+        let response = match self
+            .client
+            .get(&url)
+            .query(params)
+            .send_timed(Provider::Tautulli)
+            .await
+        {
             Ok(response) => response,
             Err(e) => {
                 return Err(ProviderError::new(
@@ -83,60 +279,329 @@ impl Tautulli {
                 ));
             }
         };
-        let tautulli: tautulli::TautulliResponse = match response.json().await {
-            Ok(tautulli) => tautulli,
-            Err(e) => {
-                return Err(ProviderError::new(
-                    Provider::Tautulli,
-                    ProviderErrorKind::ParseError,
-                    &format!("{:?}", e),
-                ));
-            }
-        };
+        let tautulli: tautulli::CommandResponse<T> =
+            match parse_json(Provider::Tautulli, response).await {
+                Ok(tautulli) => tautulli,
+                Err(e) => {
+                    return Err(ProviderError::new(
+                        Provider::Tautulli,
+                        ProviderErrorKind::ParseError,
+                        &format!("{:?}", e),
+                    ));
+                }
+            };
         Ok(tautulli.response.data)
     }
     pub async fn get_libraries(&self) -> Vec<Library> {
-        let get_libraries = match self.get("get_libraries").await {
+        match self.get_command("get_libraries", &[]).await {
             Ok(libraries) => libraries,
             Err(e) => {
                 error!("Failed to get libraries: {}", e);
+                record_failure(e.reason());
+                Vec::new()
+            }
+        }
+    }
+    pub async fn get_library_file_sizes(&self, libraries: &[Library]) -> Vec<LibraryFileSize> {
+        let futures_sizes = libraries.iter().map(|library| async move {
+            let params = [
+                ("section_id", library.section_id.clone()),
+                ("length", "1".to_string()),
+            ];
+            let media_info: tautulli::LibraryMediaInfo =
+                match self.get_command("get_library_media_info", &params).await {
+                    Ok(media_info) => media_info,
+                    Err(e) => {
+                        error!(
+                            "Failed to get media info for library {}: {}",
+                            library.section_name, e
+                        );
+                        record_failure(e.reason());
+                        return None;
+                    }
+                };
+            Some(LibraryFileSize {
+                section_name: library.section_name.clone(),
+                section_type: library.section_type.clone(),
+                file_size: media_info.total_file_size.unwrap_or(0),
+            })
+        });
+        futures::future::join_all(futures_sizes)
+            .await
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+    pub async fn get_home_stats(&self) -> Vec<HomeStatSummary> {
+        let params = [
+            ("time_range", self.home_stats_days.unwrap_or(30).to_string()),
+            (
+                "stats_count",
+                self.home_stats_count.unwrap_or(10).to_string(),
+            ),
+        ];
+        let stats: Vec<tautulli::HomeStat> = match self.get_command("get_home_stats", &params).await
+        {
+            Ok(stats) => stats,
+            Err(e) => {
+                error!("Failed to get home stats: {}", e);
+                record_failure(e.reason());
                 return Vec::new();
             }
         };
-        let libraries: Vec<Library> = get_libraries.into();
-        libraries
+        stats
+            .into_iter()
+            .flat_map(|stat| {
+                let stat_id = stat.stat_id;
+                stat.rows.into_iter().filter_map(move |row| {
+                    let label = match stat_id.as_str() {
+                        "top_users" => row.friendly_name,
+                        "top_platforms" => row.platform,
+                        "top_movies" | "top_tv" | "top_music" => row.title,
+                        _ => None,
+                    }?;
+                    Some(HomeStatSummary {
+                        stat: stat_id.clone(),
+                        label,
+                        plays: row.total_plays.unwrap_or(0),
+                        duration: row.total_duration.unwrap_or(0),
+                    })
+                })
+            })
+            .collect()
     }
-    async fn get_ip_info(&self, ip: &str) -> Result<TautulliLocation, ProviderError> {
-        let service = Service::IpApi;
-        match Locator::get(ip, service).await {
-            Ok(location) => Ok(TautulliLocation {
-                city: location.city,
-                country: location.country,
-                ip_address: ip.to_string(),
-                latitude: location.latitude,
-                longitude: location.longitude,
-            }),
-            Err(_) => Ok(TautulliLocation {
-                city: "Unknown".to_string(),
-                country: "Unknown".to_string(),
-                ip_address: ip.to_string(),
-                latitude: "0.0".to_string(),
-                longitude: "0.0".to_string(),
-            }),
+    // Only the rows newer than the last counted one are read, newest stopped
+    // first.
+    pub async fn get_history_plays(&self) -> Vec<HistoryPlay> {
+        let page_size: i64 = 1000;
+        let cursor = HISTORY_CURSORS
+            .lock()
+            .ok()
+            .and_then(|cursors| cursors.get(&self.name).copied());
+        let mut start: i64 = 0;
+        let mut plays = Vec::new();
+        'pages: loop {
+            let params = [
+                ("grouping", "0".to_string()),
+                ("order_column", "stopped".to_string()),
+                ("order_dir", "desc".to_string()),
+                ("start", start.to_string()),
+                ("length", page_size.to_string()),
+            ];
+            let history: tautulli::History = match self.get_command("get_history", &params).await {
+                Ok(history) => history,
+                Err(e) => {
+                    error!("Failed to get history: {}", e);
+                    record_failure(e.reason());
+                    return Vec::new();
+                }
+            };
+            let rows = history.data.len() as i64;
+            for row in history.data {
+                let Some(id) = row.id.or(row.row_id) else {
+                    continue;
+                };
+                if cursor.is_some_and(|cursor| id <= cursor) {
+                    break 'pages;
+                }
+                plays.push(HistoryPlay {
+                    id,
+                    user: row
+                        .friendly_name
+                        .or(row.user)
+                        .unwrap_or_else(|| "Unknown".to_string()),
+                    media_type: row.media_type.unwrap_or_else(|| "unknown".to_string()),
+                    duration: row.play_duration.or(row.duration).unwrap_or(0).max(0) as u64,
+                });
+            }
+            start += rows;
+            if rows == 0 || start >= history.records_filtered {
+                break;
+            }
         }
+        plays
     }
-    pub async fn get_session_summary(&self) -> Vec<SessionSummary> {
-        let get_activities = match self.get("get_activity").await {
-            Ok(activities) => activities,
+    pub async fn get_users_watch_time(&self) -> Vec<UserWatchTime> {
+        let users: Vec<tautulli::User> = match self.get_command("get_users", &[]).await {
+            Ok(users) => users,
             Err(e) => {
-                error!("Failed to get activities: {}", e);
+                error!("Failed to get users: {}", e);
+                record_failure(e.reason());
                 return Vec::new();
             }
         };
-        let activity: tautulli::Activity = get_activities.into();
+        let futures_stats = users.into_iter().map(|user| async move {
+            let params = [
+                ("user_id", user.user_id.to_string()),
+                ("query_days", "1,7,30".to_string()),
+            ];
+            let name = user
+                .friendly_name
+                .or(user.username)
+                .unwrap_or_else(|| user.user_id.to_string());
+            let stats: Vec<tautulli::WatchTimeStat> =
+                match self.get_command("get_user_watch_time_stats", &params).await {
+                    Ok(stats) => stats,
+                    Err(e) => {
+                        error!("Failed to get watch time stats for {}: {}", name, e);
+                        record_failure(e.reason());
+                        Vec::new()
+                    }
+                };
+            stats
+                .into_iter()
+                .map(|stat| UserWatchTime {
+                    user: name.clone(),
+                    days: stat.query_days,
+                    plays: stat.total_plays,
+                    duration: stat.total_time,
+                })
+                .collect::<Vec<UserWatchTime>>()
+        });
+        futures::future::join_all(futures_stats)
+            .await
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+    pub async fn get_server_info(&self) -> Option<TautulliServerInfo> {
+        let info: tautulli::TautulliInfo = match self.get_command("get_tautulli_info", &[]).await {
+            Ok(info) => info,
+            Err(e) => {
+                error!("Failed to get Tautulli info: {}", e);
+                record_failure(e.reason());
+                return None;
+            }
+        };
+        let update: Option<tautulli::UpdateCheck> =
+            match self.get_command("update_check", &[]).await {
+                Ok(update) => Some(update),
+                Err(e) => {
+                    error!("Failed to check for Tautulli update: {}", e);
+                    record_failure(e.reason());
+                    None
+                }
+            };
+        let status: Option<tautulli::ServerStatus> =
+            match self.get_command("server_status", &[]).await {
+                Ok(status) => Some(status),
+                Err(e) => {
+                    error!("Failed to get Plex server status: {}", e);
+                    record_failure(e.reason());
+                    None
+                }
+            };
+        Some(TautulliServerInfo {
+            version: info.tautulli_version,
+            branch: info
+                .tautulli_branch
+                .unwrap_or_else(|| "Unknown".to_string()),
+            update_available: update.and_then(|update| update.update),
+            plex_connected: status.and_then(|status| status.connected),
+        })
+    }
+    pub async fn get_notification_summary(&self) -> Vec<NotificationSummary> {
+        let params = [
+            ("start", "0".to_string()),
+            ("length", "100".to_string()),
+            ("order_column", "timestamp".to_string()),
+            ("order_dir", "desc".to_string()),
+        ];
+        let log: tautulli::NotificationLog =
+            match self.get_command("get_notification_log", &params).await {
+                Ok(log) => log,
+                Err(e) => {
+                    error!("Failed to get notification log: {}", e);
+                    record_failure(e.reason());
+                    return Vec::new();
+                }
+            };
+        let mut summaries: Vec<NotificationSummary> = Vec::new();
+        for row in log.data {
+            let agent = row.agent_name.unwrap_or_else(|| "unknown".to_string());
+            let failed = if row.success.unwrap_or(0) == 1 { 0 } else { 1 };
+            match summaries.iter_mut().find(|summary| summary.agent == agent) {
+                Some(summary) => {
+                    summary.sent += 1;
+                    summary.failed += failed;
+                }
+                None => summaries.push(NotificationSummary {
+                    agent,
+                    sent: 1,
+                    failed,
+                }),
+            }
+        }
+        summaries
+    }
+    pub async fn get_plays_by_date(&self) -> Vec<DailyPlays> {
+        let params = [
+            (
+                "time_range",
+                self.plays_by_date_days.unwrap_or(7).to_string(),
+            ),
+            ("y_axis", "plays".to_string()),
+        ];
+        let plays: tautulli::PlaysByDate =
+            match self.get_command("get_plays_by_date", &params).await {
+                Ok(plays) => plays,
+                Err(e) => {
+                    error!("Failed to get plays by date: {}", e);
+                    record_failure(e.reason());
+                    return Vec::new();
+                }
+            };
+        let categories = plays.categories;
+        plays
+            .series
+            .into_iter()
+            .flat_map(|series| {
+                categories
+                    .iter()
+                    .zip(series.data)
+                    .map(|(date, count)| DailyPlays {
+                        date: date.clone(),
+                        media_type: series.name.clone(),
+                        plays: count.unwrap_or(0),
+                    })
+                    .collect::<Vec<DailyPlays>>()
+            })
+            .collect()
+    }
+    #[instrument(skip_all)]
+    async fn get_ip_info(&self, ip: &str) -> Result<TautulliLocation, ProviderError> {
+        Ok(self.geoip.locate(ip).await.into())
+    }
+    pub async fn get_session_summary(&self) -> ActivitySummary {
+        let activity: tautulli::Activity = match self.get_command("get_activity", &[]).await {
+            Ok(activity) => activity,
+            Err(e) => {
+                error!("Failed to get activities: {}", e);
+                record_failure(e.reason());
+                return ActivitySummary {
+                    sessions: Vec::new(),
+                    stats: None,
+                };
+            }
+        };
+        let stats = ActivityStats {
+            total_bandwidth: activity.total_bandwidth,
+            lan_bandwidth: activity.lan_bandwidth,
+            wan_bandwidth: activity.wan_bandwidth,
+            stream_count_direct_play: activity.stream_count_direct_play,
+            stream_count_direct_stream: activity.stream_count_direct_stream,
+            stream_count_transcode: activity.stream_count_transcode,
+        };
         let mut session_summaries = Vec::new();
-        for session in &activity.sessions {
-            let location = match self.get_ip_info(&session.ip_address).await {
+        let locations = join_all(
+            activity
+                .sessions
+                .iter()
+                .map(|session| self.get_ip_info(&session.ip_address)),
+        )
+        .await;
+        for (session, location) in activity.sessions.iter().zip(locations) {
+            let location = match location {
                 Ok(location) => location,
                 Err(e) => {
                     error!("Failed to get location: {}", e);
@@ -180,6 +645,116 @@ impl Tautulli {
             };
             session_summaries.push(session_summary);
         }
-        session_summaries
+        ActivitySummary {
+            sessions: session_summaries,
+            stats: Some(stats),
+        }
+    }
+}
+
+#[async_trait]
+impl MetricProvider for Tautulli {
+    fn provider(&self) -> &'static str {
+        "tautulli"
+    }
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn address(&self) -> &str {
+        &self.address
+    }
+    async fn check(&self) -> Result<(), ProviderError> {
+        let url = format!("{}get_tautulli_info", self.api_url);
+        check_endpoint(Provider::Tautulli, &self.client, &url).await
+    }
+    async fn version(&self) -> Result<String, ProviderError> {
+        let info: tautulli::TautulliInfo = self.get_command("get_tautulli_info", &[]).await?;
+        Ok(info.tautulli_version)
+    }
+    // The endpoint is an API command, parameters can follow, e.g. `get_history&length=5`.
+    async fn debug(&self, endpoint: Option<&str>) -> Result<String, ProviderError> {
+        let url = format!(
+            "{}{}",
+            self.api_url,
+            endpoint.unwrap_or("get_tautulli_info")
+        );
+        get_raw(Provider::Tautulli, &self.client, &url).await
+    }
+    fn tasks(&self) -> Vec<TaskKind> {
+        let mut tasks = Vec::new();
+        if enabled(self.sessions) {
+            tasks.push(SESSIONS);
+        }
+        if enabled(self.libraries) {
+            tasks.push(LIBRARIES);
+        }
+        if enabled(self.home_stats) {
+            tasks.push(HOME_STATS);
+        }
+        // Opt-in, the first collection reads the whole history.
+        if self.history.unwrap_or(false) {
+            tasks.push(HISTORY);
+        }
+        if enabled(self.user_watch_time) {
+            tasks.push(USER_WATCH_TIME);
+        }
+        if enabled(self.server) {
+            tasks.push(SERVER);
+        }
+        if enabled(self.notifications) {
+            tasks.push(NOTIFICATIONS);
+        }
+        if enabled(self.plays_by_date) {
+            tasks.push(PLAYS_BY_DATE);
+        }
+        tasks
+    }
+    async fn collect(&self, task: TaskKind) -> Result<Vec<TaskResult>, ProviderError> {
+        let name = self.name.clone();
+        let result = match task {
+            SESSIONS => {
+                let summary = self.get_session_summary().await;
+                TaskResult::TautulliSession(TautulliSessionResult {
+                    name,
+                    sessions: summary.sessions,
+                    stats: summary.stats,
+                })
+            }
+            LIBRARIES => {
+                let libraries = self.get_libraries().await;
+                let file_sizes = self.get_library_file_sizes(&libraries).await;
+                TaskResult::TautulliLibrary(TautulliLibraryResult {
+                    name,
+                    libraries,
+                    file_sizes,
+                })
+            }
+            HOME_STATS => TaskResult::TautulliHomeStats(TautulliHomeStatsResult {
+                name,
+                stats: self.get_home_stats().await,
+            }),
+            HISTORY => TaskResult::TautulliHistory(TautulliHistoryResult {
+                name,
+                plays: self.get_history_plays().await,
+            }),
+            USER_WATCH_TIME => TaskResult::TautulliUserWatchTime(TautulliUserWatchTimeResult {
+                name,
+                watch_time: self.get_users_watch_time().await,
+            }),
+            SERVER => TaskResult::TautulliServer(TautulliServerResult {
+                name,
+                info: self.get_server_info().await,
+            }),
+            NOTIFICATIONS => TaskResult::TautulliNotifications(TautulliNotificationsResult {
+                name,
+                notifications: self.get_notification_summary().await,
+            }),
+            PLAYS_BY_DATE => TaskResult::TautulliPlaysByDate(TautulliPlaysByDateResult {
+                name,
+                plays: self.get_plays_by_date().await,
+            }),
+            _ => return Ok(Vec::new()),
+        };
+        Ok(vec![result])
     }
 }
@@ -0,0 +1,161 @@
+use base64::{engine::general_purpose::URL_SAFE, Engine};
+use log::{debug, info, warn};
+use reqwest::Url;
+use std::time::Duration;
+
+use crate::collector::Collector;
+use crate::config::{BasicAuth, PushOptions};
+
+const PUSH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Pushes the metrics of every task to a Pushgateway, for instances
+/// Prometheus cannot reach.
+pub struct Pusher {
+    url: Url,
+    basic_auth: Option<BasicAuth>,
+    interval: Duration,
+    client: reqwest::Client,
+    collector: Collector,
+}
+
+// `/metrics/job/<job>/<label>/<value>...`, values that are empty or hold a
+// slash are base64 encoded as the Pushgateway expects, with `=` for an empty
+// value since a path segment cannot be empty.
+fn grouping_url(options: &PushOptions) -> anyhow::Result<Url> {
+    let mut url = Url::parse(&options.url)?;
+    let mut labels = vec![("job", options.job())];
+    if let Some(instance) = &options.instance {
+        labels.push(("instance", instance));
+    }
+    let mut extra: Vec<(&str, &str)> = options
+        .labels
+        .iter()
+        .map(|(label, value)| (label.as_str(), value.as_str()))
+        .collect();
+    extra.sort();
+    labels.extend(extra);
+    let mut segments = url
+        .path_segments_mut()
+        .map_err(|_| anyhow::anyhow!("Invalid Pushgateway URL {}", options.url))?;
+    segments.pop_if_empty().push("metrics");
+    for (label, value) in labels {
+        match (value.is_empty(), value.contains('/')) {
+            (true, _) => segments.push(&format!("{label}@base64")).push("="),
+            (_, true) => segments
+                .push(&format!("{label}@base64"))
+                .push(&URL_SAFE.encode(value)),
+            _ => segments.push(label).push(value),
+        };
+    }
+    drop(segments);
+    Ok(url)
+}
+
+impl Pusher {
+    pub fn new(options: &PushOptions, collector: Collector) -> anyhow::Result<Self> {
+        Ok(Pusher {
+            url: grouping_url(options)?,
+            basic_auth: options.basic_auth.clone(),
+            interval: options.interval(),
+            client: reqwest::Client::builder().timeout(PUSH_TIMEOUT).build()?,
+            collector,
+        })
+    }
+
+    /// Pushes at startup, then every `interval`.
+    pub fn spawn(self) {
+        info!("Pushing metrics to {} every {:?}", self.url, self.interval);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.interval);
+            loop {
+                interval.tick().await;
+                match self.push().await {
+                    Ok(()) => debug!("Pushed metrics to {}", self.url),
+                    Err(e) => warn!("Failed to push metrics to {}: {e}", self.url),
+                }
+            }
+        });
+    }
+
+    // Replaces the whole group, series that are gone are not left behind.
+    async fn push(&self) -> anyhow::Result<()> {
+        let metrics = self.collector.latest().await?.prometheus();
+        let mut request = self
+            .client
+            .put(self.url.clone())
+            .header("Content-Type", "text/plain; version=0.0.4")
+            .body(metrics);
+        if let Some(auth) = &self.basic_auth {
+            request = request.basic_auth(&auth.username, Some(&auth.password));
+        }
+        let response = request.send().await?;
+        if !response.status().is_success() {
+            anyhow::bail!(
+                "{} {}",
+                response.status(),
+                response.text().await.unwrap_or_default()
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn options(url: &str, instance: Option<&str>, labels: &[(&str, &str)]) -> PushOptions {
+        PushOptions {
+            url: url.to_string(),
+            job: None,
+            instance: instance.map(str::to_string),
+            labels: labels
+                .iter()
+                .map(|(label, value)| (label.to_string(), value.to_string()))
+                .collect::<HashMap<_, _>>(),
+            interval: None,
+            basic_auth: None,
+        }
+    }
+
+    #[test]
+    fn grouping_url_sorts_extra_labels() {
+        let url = grouping_url(&options(
+            "http://pushgateway:9091",
+            Some("nas"),
+            &[("site", "home"), ("env", "prod")],
+        ))
+        .unwrap();
+        assert_eq!(
+            url.as_str(),
+            "http://pushgateway:9091/metrics/job/homers/instance/nas/env/prod/site/home"
+        );
+    }
+
+    #[test]
+    fn grouping_url_keeps_base_path() {
+        let url = grouping_url(&options("http://proxy/pushgateway/", None, &[])).unwrap();
+        assert_eq!(url.as_str(), "http://proxy/pushgateway/metrics/job/homers");
+    }
+
+    #[test]
+    fn grouping_url_encodes_empty_and_slash_values() {
+        let url = grouping_url(&options(
+            "http://pushgateway:9091",
+            Some(""),
+            &[("path", "/srv/media")],
+        ))
+        .unwrap();
+        assert_eq!(
+            url.as_str(),
+            "http://pushgateway:9091/metrics/job/homers/instance@base64/=/path@base64/L3Nydi9tZWRpYQ=="
+        );
+    }
+
+    #[test]
+    fn grouping_url_rejects_invalid_urls() {
+        assert!(grouping_url(&options("mailto:ops@example.com", None, &[])).is_err());
+        assert!(grouping_url(&options("not a url", None, &[])).is_err());
+    }
+}
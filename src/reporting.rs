@@ -0,0 +1,67 @@
+use reqwest::Url;
+use sentry::protocol::Value;
+
+use crate::config::SentryOptions;
+use crate::providers::Provider;
+
+const MAX_BODY_LENGTH: usize = 4096;
+
+// Query parameters that carry credentials, e.g. the Tautulli API key.
+const MASKED_PARAMETERS: [&str; 3] = ["apikey", "api_key", "token"];
+
+/// Panics and provider parse errors are sent to Sentry until the guard is dropped.
+pub fn init(options: &SentryOptions) -> sentry::ClientInitGuard {
+    sentry::init((
+        options.dsn.as_str(),
+        sentry::ClientOptions {
+            release: sentry::release_name!(),
+            environment: options.environment.clone().map(Into::into),
+            sample_rate: options.sample_rate.unwrap_or(1.0),
+            ..Default::default()
+        },
+    ))
+}
+
+fn mask_url(url: &Url) -> String {
+    let mut url = url.clone();
+    let pairs: Vec<(String, String)> = url
+        .query_pairs()
+        .map(|(key, value)| {
+            if MASKED_PARAMETERS.contains(&key.as_ref()) {
+                (key.to_string(), "********".to_string())
+            } else {
+                (key.to_string(), value.to_string())
+            }
+        })
+        .collect();
+    if !pairs.is_empty() {
+        url.query_pairs_mut().clear().extend_pairs(pairs);
+    }
+    let _ = url.set_password(None);
+    url.to_string()
+}
+
+fn truncate(body: &str) -> &str {
+    match body.char_indices().nth(MAX_BODY_LENGTH) {
+        Some((end, _)) => &body[..end],
+        None => body,
+    }
+}
+
+// Does nothing unless Sentry was initialized.
+pub fn report_parse_error(provider: &Provider, url: &Url, body: &str, error: &serde_json::Error) {
+    sentry::with_scope(
+        |scope| {
+            scope.set_tag("provider", provider);
+            scope.set_extra("endpoint", Value::from(mask_url(url)));
+            scope.set_extra("body", Value::from(truncate(body)));
+            scope.set_extra("body_length", Value::from(body.len()));
+        },
+        || {
+            sentry::capture_message(
+                &format!("Failed to parse {provider} response: {error}"),
+                sentry::Level::Error,
+            )
+        },
+    );
+}
@@ -0,0 +1,140 @@
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::config::CacheOptions;
+use crate::tasks::TaskResult;
+
+#[derive(Serialize, Deserialize, Clone)]
+struct CachedResult {
+    // Seconds since the epoch.
+    saved_at: u64,
+    results: Vec<TaskResult>,
+}
+
+#[derive(Default)]
+struct CacheState {
+    results: HashMap<String, CachedResult>,
+    // Tasks that succeeded since startup are not served from the cache anymore.
+    refreshed: HashSet<String>,
+    dirty: bool,
+}
+
+/// Last successful result of every task, written to disk after each scrape.
+/// After a restart, a failing task is answered with its cached result until
+/// it succeeds once.
+pub struct ResultCache {
+    path: PathBuf,
+    max_age: Duration,
+    state: Mutex<CacheState>,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_secs())
+        .unwrap_or_default()
+}
+
+impl ResultCache {
+    pub fn load(options: &CacheOptions) -> Self {
+        let max_age = options.max_age();
+        let results = match std::fs::read(&options.path) {
+            Ok(content) => {
+                match serde_json::from_slice::<HashMap<String, CachedResult>>(&content) {
+                    Ok(results) => {
+                        let results: HashMap<String, CachedResult> = results
+                            .into_iter()
+                            .filter(|(_, cached)| {
+                                now().saturating_sub(cached.saved_at) <= max_age.as_secs()
+                            })
+                            .collect();
+                        info!(
+                            "Loaded {} cached task results from {:?}",
+                            results.len(),
+                            options.path
+                        );
+                        results
+                    }
+                    Err(e) => {
+                        warn!("Ignoring the result cache {:?}: {e}", options.path);
+                        HashMap::new()
+                    }
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => {
+                warn!("Failed to read the result cache {:?}: {e}", options.path);
+                HashMap::new()
+            }
+        };
+        ResultCache {
+            path: options.path.clone(),
+            max_age,
+            state: Mutex::new(CacheState {
+                results,
+                ..Default::default()
+            }),
+        }
+    }
+
+    pub fn store(&self, key: &str, results: &[TaskResult]) {
+        if let Ok(mut state) = self.state.lock() {
+            state.results.insert(
+                key.to_string(),
+                CachedResult {
+                    saved_at: now(),
+                    results: results.to_vec(),
+                },
+            );
+            state.refreshed.insert(key.to_string());
+            state.dirty = true;
+        }
+    }
+
+    // The result of a previous run, for a task that has not succeeded since startup.
+    pub fn stale(&self, key: &str) -> Option<Vec<TaskResult>> {
+        let state = self.state.lock().ok()?;
+        if state.refreshed.contains(key) {
+            return None;
+        }
+        state
+            .results
+            .get(key)
+            .filter(|cached| now().saturating_sub(cached.saved_at) <= self.max_age.as_secs())
+            .map(|cached| cached.results.clone())
+    }
+
+    // Written to a temporary file first so a crash never leaves half a cache.
+    pub async fn save(&self) {
+        let content = match self.state.lock() {
+            Ok(mut state) if state.dirty => {
+                state.dirty = false;
+                let max_age = self.max_age.as_secs();
+                state
+                    .results
+                    .retain(|_, cached| now().saturating_sub(cached.saved_at) <= max_age);
+                serde_json::to_vec(&state.results)
+            }
+            _ => return,
+        };
+        let content = match content {
+            Ok(content) => content,
+            Err(e) => {
+                warn!("Failed to serialize the result cache: {e}");
+                return;
+            }
+        };
+        let temporary = self.path.with_extension("tmp");
+        let written = match tokio::fs::write(&temporary, content).await {
+            Ok(()) => tokio::fs::rename(&temporary, &self.path).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = written {
+            warn!("Failed to write the result cache {:?}: {e}", self.path);
+        }
+    }
+}
@@ -0,0 +1,169 @@
+use futures::future::join_all;
+use log::{debug, info};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+use crate::providers::structs::Session;
+use crate::tasks::{Task, TaskList, TaskResult};
+
+const CHANNEL_CAPACITY: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionEventKind {
+    Start,
+    Stop,
+    Progress,
+}
+impl SessionEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SessionEventKind::Start => "start",
+            SessionEventKind::Stop => "stop",
+            SessionEventKind::Progress => "progress",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionEvent {
+    pub event: SessionEventKind,
+    pub provider: String,
+    pub name: String,
+    pub session: Session,
+}
+
+// Polls the Plex and Jellyfin session tasks while someone is subscribed and
+// broadcasts the differences between two polls.
+#[derive(Clone)]
+pub struct SessionFeed {
+    interval: Duration,
+    sender: broadcast::Sender<SessionEvent>,
+    current: Arc<Mutex<HashMap<String, SessionEvent>>>,
+    started: Arc<AtomicBool>,
+}
+
+impl SessionFeed {
+    pub fn new(interval: Duration) -> Self {
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+        Self {
+            interval,
+            sender,
+            current: Arc::new(Mutex::new(HashMap::new())),
+            started: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn subscribe(&self, tasks: &TaskList) -> broadcast::Receiver<SessionEvent> {
+        let receiver = self.sender.subscribe();
+        if !self.started.swap(true, Ordering::SeqCst) {
+            info!("Starting session feed every {:?}", self.interval);
+            tokio::spawn(self.clone().poll(tasks.clone()));
+        }
+        receiver
+    }
+
+    // Sessions known from the last poll, sent as start events to new subscribers.
+    pub fn snapshot(&self) -> Vec<SessionEvent> {
+        match self.current.lock() {
+            Ok(current) => current.values().cloned().collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    async fn poll(self, tasks: TaskList) {
+        let mut interval = tokio::time::interval(self.interval);
+        loop {
+            interval.tick().await;
+            if self.sender.receiver_count() == 0 {
+                continue;
+            }
+            let tasks: Vec<Task> = tasks
+                .get()
+                .into_iter()
+                .filter(|task| matches!(task.kind(), "plex_session" | "jellyfin_session"))
+                .collect();
+            let sessions = collect_sessions(&tasks).await;
+            for event in self.update(sessions) {
+                debug!(
+                    "Session {} for {} on {} {}",
+                    event.event.as_str(),
+                    event.session.user,
+                    event.provider,
+                    event.name
+                );
+                let _ = self.sender.send(event);
+            }
+        }
+    }
+
+    fn update(&self, sessions: HashMap<String, SessionEvent>) -> Vec<SessionEvent> {
+        let mut current = match self.current.lock() {
+            Ok(current) => current,
+            Err(_) => return Vec::new(),
+        };
+        let mut events = Vec::new();
+        for (key, session) in current.iter() {
+            if !sessions.contains_key(key) {
+                events.push(SessionEvent {
+                    event: SessionEventKind::Stop,
+                    ..session.clone()
+                });
+            }
+        }
+        for (key, session) in sessions.iter() {
+            match current.get(key) {
+                None => events.push(session.clone()),
+                Some(previous)
+                    if previous.session.progress != session.session.progress
+                        || previous.session.state != session.session.state =>
+                {
+                    events.push(SessionEvent {
+                        event: SessionEventKind::Progress,
+                        ..session.clone()
+                    });
+                }
+                Some(_) => {}
+            }
+        }
+        *current = sessions;
+        events
+    }
+}
+
+async fn collect_sessions(tasks: &[Task]) -> HashMap<String, SessionEvent> {
+    let results = join_all(tasks.iter().map(Task::collect)).await;
+    let results = results
+        .into_iter()
+        .flatten()
+        .filter_map(|result| match result {
+            TaskResult::PlexSession(result) | TaskResult::JellyfinSession(result) => {
+                Some((result.kind, result.name, result.sessions))
+            }
+            _ => None,
+        });
+    let mut sessions = HashMap::new();
+    for (provider, name, provider_sessions) in results {
+        for session in provider_sessions {
+            // Providers do not expose a stable session id across both backends.
+            let key = format!(
+                "{provider}/{name}/{}/{}/{}/{}",
+                session.user, session.title, session.platform, session.address
+            );
+            sessions.insert(
+                key,
+                SessionEvent {
+                    event: SessionEventKind::Start,
+                    provider: provider.clone(),
+                    name: name.clone(),
+                    session,
+                },
+            );
+        }
+    }
+    sessions
+}
@@ -1,48 +1,123 @@
-use prometheus_client::registry::Registry;
-use serde::Deserialize;
-
-use crate::prometheus::FormatAsPrometheus;
-use crate::providers::jellyfin::Jellyfin;
-use crate::providers::overseerr::{Overseerr, OverseerrRequest};
-use crate::providers::plex::Plex;
-use crate::providers::radarr::{Radarr, RadarrMovie};
-use crate::providers::sonarr::{Sonarr, SonarrEpisode};
+use log::error;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, RwLock};
+
+use crate::prometheus::{FormatAsPrometheus, MetricRegistry};
+use crate::providers::jellyfin::{
+    JellyfinClient, JellyfinPlugin, JellyfinRecentlyAdded, JellyfinServerInfo, JellyfinStoragePath,
+};
+use crate::providers::overseerr::{OverseerrJob, OverseerrRequest, OverseerrRequestCount};
+use crate::providers::plex::PlexRemoteAccess;
+use crate::providers::radarr::RadarrMovie;
+use crate::providers::sonarr::SonarrEpisode;
 use crate::providers::structs::tautulli::Library;
 use crate::providers::structs::{LibraryCount, Session, User};
-use crate::providers::tautulli::SessionSummary;
-use crate::providers::tautulli::Tautulli;
-
-#[derive(Debug, Deserialize, Clone)]
-pub enum Task {
-    SonarrToday(Sonarr),
-    SonarrMissing(Sonarr),
-    Radarr(Radarr),
-    Overseerr(Overseerr),
-    Jellyseerr(Overseerr),
-    TautulliSession(Tautulli),
-    TautulliLibrary(Tautulli),
-    PlexSession(Plex),
-    PlexLibrary(Plex),
-    JellyfinSession(Jellyfin),
-    JellyfinLibrary(Jellyfin),
-    Default,
+use crate::providers::tautulli::{
+    ActivityStats, DailyPlays, HistoryPlay, HomeStatSummary, LibraryFileSize, NotificationSummary,
+    SessionSummary, TautulliServerInfo, UserWatchTime,
+};
+use crate::providers::{record_failure, MetricProvider, ProviderError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TaskKind {
+    pub name: &'static str,
+    pub flag: Option<&'static str>,
+}
+
+/// One task of a provider client, collected through `MetricProvider::collect`.
+#[derive(Debug, Clone)]
+pub struct Task {
+    kind: TaskKind,
+    client: Arc<dyn MetricProvider>,
+}
+impl Task {
+    /// The tasks enabled in the block of `client`.
+    pub fn all(client: impl MetricProvider + 'static) -> Vec<Task> {
+        let client: Arc<dyn MetricProvider> = Arc::new(client);
+        client
+            .tasks()
+            .into_iter()
+            .map(|kind| Task {
+                kind,
+                client: client.clone(),
+            })
+            .collect()
+    }
+    pub fn kind(&self) -> &'static str {
+        self.kind.name
+    }
+    pub fn flag(&self) -> Option<&'static str> {
+        self.kind.flag
+    }
+    pub async fn check(&self) -> Result<(), ProviderError> {
+        self.client.check().await
+    }
+    // Version reported by the provider, see `version_check`.
+    pub async fn version(&self) -> Result<String, ProviderError> {
+        self.client.version().await
+    }
+    pub async fn debug(&self, endpoint: Option<&str>) -> Result<String, ProviderError> {
+        self.client.debug(endpoint).await
+    }
+    pub fn provider(&self) -> (&'static str, &str, &str) {
+        (
+            self.client.provider(),
+            self.client.name(),
+            self.client.address(),
+        )
+    }
+    // Provider errors are logged and give empty results, see `track_failure`.
+    pub async fn collect(&self) -> Vec<TaskResult> {
+        match self.client.collect(self.kind).await {
+            Ok(results) => results,
+            Err(e) => {
+                error!("{e}");
+                record_failure(e.reason());
+                Vec::new()
+            }
+        }
+    }
+}
+
+// Tasks are enabled unless their flag is set to false in the provider block.
+pub fn enabled(flag: Option<bool>) -> bool {
+    flag.unwrap_or(true)
 }
+
+#[derive(Clone, Serialize, Deserialize)]
 pub enum TaskResult {
     SonarrToday(SonarrEpisodeResult),
     SonarrMissing(SonarrMissingResult),
     TautulliSession(TautulliSessionResult),
     TautulliLibrary(TautulliLibraryResult),
+    TautulliHomeStats(TautulliHomeStatsResult),
+    TautulliHistory(TautulliHistoryResult),
+    TautulliUserWatchTime(TautulliUserWatchTimeResult),
+    TautulliServer(TautulliServerResult),
+    TautulliNotifications(TautulliNotificationsResult),
+    TautulliPlaysByDate(TautulliPlaysByDateResult),
     Radarr(RadarrMovieResult),
     Overseerr(OverseerrRequestResult),
     Jellyseerr(OverseerrRequestResult),
+    OverseerrJobs(OverseerrJobsResult),
+    JellyseerrJobs(OverseerrJobsResult),
     PlexSession(SessionResult),
     PlexLibrary(LibraryResult),
+    PlexAccount(PlexAccountResult),
     JellyfinSession(SessionResult),
     JellyfinLibrary(LibraryResult),
-    Default,
+    JellyfinDevices(JellyfinDevicesResult),
+    JellyfinPlugins(JellyfinPluginsResult),
+    JellyfinInfo(JellyfinInfoResult),
+    JellyfinRecentlyAdded(JellyfinRecentlyAddedResult),
+    JellyfinStorage(JellyfinStorageResult),
+    #[serde(skip_deserializing)]
+    Collection(CollectionResult),
+    #[serde(skip_deserializing)]
+    History(HistoryResult),
 }
 impl FormatAsPrometheus for TaskResult {
-    fn format_as_prometheus(&self, registry: &mut Registry) {
+    fn format_as_prometheus(&self, registry: &mut MetricRegistry) {
         match self {
             TaskResult::SonarrToday(result) => {
                 result.format_as_prometheus(registry);
@@ -56,6 +131,24 @@ impl FormatAsPrometheus for TaskResult {
             TaskResult::TautulliLibrary(result) => {
                 result.format_as_prometheus(registry);
             }
+            TaskResult::TautulliHomeStats(result) => {
+                result.format_as_prometheus(registry);
+            }
+            TaskResult::TautulliHistory(result) => {
+                result.format_as_prometheus(registry);
+            }
+            TaskResult::TautulliUserWatchTime(result) => {
+                result.format_as_prometheus(registry);
+            }
+            TaskResult::TautulliServer(result) => {
+                result.format_as_prometheus(registry);
+            }
+            TaskResult::TautulliNotifications(result) => {
+                result.format_as_prometheus(registry);
+            }
+            TaskResult::TautulliPlaysByDate(result) => {
+                result.format_as_prometheus(registry);
+            }
             TaskResult::Radarr(result) => {
                 result.format_as_prometheus(registry);
             }
@@ -65,56 +158,135 @@ impl FormatAsPrometheus for TaskResult {
             TaskResult::Jellyseerr(result) => {
                 result.format_as_prometheus(registry);
             }
+            TaskResult::OverseerrJobs(result) => {
+                result.format_as_prometheus(registry);
+            }
+            TaskResult::JellyseerrJobs(result) => {
+                result.format_as_prometheus(registry);
+            }
             TaskResult::PlexSession(result) => {
                 result.format_as_prometheus(registry);
             }
             TaskResult::PlexLibrary(result) => {
                 result.format_as_prometheus(registry);
             }
+            TaskResult::PlexAccount(result) => {
+                result.format_as_prometheus(registry);
+            }
             TaskResult::JellyfinSession(result) => {
                 result.format_as_prometheus(registry);
             }
             TaskResult::JellyfinLibrary(result) => {
                 result.format_as_prometheus(registry);
             }
-            TaskResult::Default => {}
+            TaskResult::JellyfinDevices(result) => {
+                result.format_as_prometheus(registry);
+            }
+            TaskResult::JellyfinPlugins(result) => {
+                result.format_as_prometheus(registry);
+            }
+            TaskResult::JellyfinInfo(result) => {
+                result.format_as_prometheus(registry);
+            }
+            TaskResult::JellyfinRecentlyAdded(result) => {
+                result.format_as_prometheus(registry);
+            }
+            TaskResult::JellyfinStorage(result) => {
+                result.format_as_prometheus(registry);
+            }
+            TaskResult::Collection(result) => {
+                result.format_as_prometheus(registry);
+            }
+            TaskResult::History(result) => {
+                result.format_as_prometheus(registry);
+            }
         }
     }
 }
-#[derive(Clone, Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SonarrEpisodeResult {
     pub name: String,
     pub episodes: Vec<SonarrEpisode>,
 }
-#[derive(Clone, Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SonarrMissingResult {
     pub name: String,
     pub episodes: Vec<SonarrEpisode>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TautulliSessionResult {
+    pub name: String,
     pub sessions: Vec<SessionSummary>,
+    pub stats: Option<ActivityStats>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TautulliLibraryResult {
+    pub name: String,
     pub libraries: Vec<Library>,
+    pub file_sizes: Vec<LibraryFileSize>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TautulliHomeStatsResult {
+    pub name: String,
+    pub stats: Vec<HomeStatSummary>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TautulliHistoryResult {
+    pub name: String,
+    pub plays: Vec<HistoryPlay>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TautulliUserWatchTimeResult {
+    pub name: String,
+    pub watch_time: Vec<UserWatchTime>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TautulliServerResult {
+    pub name: String,
+    pub info: Option<TautulliServerInfo>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TautulliNotificationsResult {
+    pub name: String,
+    pub notifications: Vec<NotificationSummary>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TautulliPlaysByDateResult {
+    pub name: String,
+    pub plays: Vec<DailyPlays>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RadarrMovieResult {
     pub name: String,
     pub movies: Vec<RadarrMovie>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OverseerrRequestResult {
+    pub name: String,
     pub kind: String,
+    pub aggregate: bool,
     pub requests: Vec<OverseerrRequest>,
+    pub counts: Option<OverseerrRequestCount>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OverseerrJobsResult {
+    pub name: String,
+    pub kind: String,
+    pub jobs: Vec<OverseerrJob>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SessionResult {
     pub name: String,
     pub kind: String,
@@ -122,9 +294,102 @@ pub struct SessionResult {
     pub sessions: Vec<Session>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LibraryResult {
     pub name: String,
     pub kind: String,
     pub libraries: Vec<LibraryCount>,
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlexAccountResult {
+    pub name: String,
+    pub remote_access: Option<PlexRemoteAccess>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JellyfinDevicesResult {
+    pub name: String,
+    pub devices: Vec<JellyfinClient>,
+    pub connected: Vec<JellyfinClient>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JellyfinPluginsResult {
+    pub name: String,
+    pub plugins: Vec<JellyfinPlugin>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JellyfinInfoResult {
+    pub name: String,
+    pub info: Option<JellyfinServerInfo>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JellyfinRecentlyAddedResult {
+    pub name: String,
+    pub recently_added: Vec<JellyfinRecentlyAdded>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JellyfinStorageResult {
+    pub name: String,
+    pub paths: Vec<JellyfinStoragePath>,
+}
+
+// Whether each task of a scrape got its data, `failure` is the reason it did not.
+#[derive(Debug, Clone, Serialize)]
+pub struct CollectionOutcome {
+    pub provider: String,
+    pub name: String,
+    pub task: String,
+    pub failure: Option<&'static str>,
+    // Served from the result cache of a previous run.
+    pub stale: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CollectionResult {
+    pub outcomes: Vec<CollectionOutcome>,
+}
+
+// Derived from the snapshots of the history store, `library` is empty but
+// for library series.
+#[derive(Debug, Clone, Serialize)]
+pub struct HistoryValue {
+    pub provider: String,
+    pub name: String,
+    pub library: String,
+    pub value: f64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct HistoryResult {
+    pub library_items_added: Vec<HistoryValue>,
+    pub requests_added_today: Vec<HistoryValue>,
+    pub sessions_peak: Vec<HistoryValue>,
+}
+
+// Task list shared by every listener, replaced as a whole when the
+// configuration is reloaded so a scrape never sees a partial update.
+#[derive(Debug, Default, Clone)]
+pub struct TaskList(Arc<RwLock<Vec<Task>>>);
+
+impl TaskList {
+    pub fn new(tasks: Vec<Task>) -> Self {
+        Self(Arc::new(RwLock::new(tasks)))
+    }
+    pub fn get(&self) -> Vec<Task> {
+        match self.0.read() {
+            Ok(tasks) => tasks.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+    pub fn replace(&self, tasks: Vec<Task>) {
+        match self.0.write() {
+            Ok(mut current) => *current = tasks,
+            Err(poisoned) => *poisoned.into_inner() = tasks,
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use futures::future::join_all;
+use log::{debug, info, warn};
+use std::collections::HashSet;
+use std::time::Duration;
+
+use crate::tasks::{Task, TaskList};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+
+// Oldest major.minor and newest major version homers was tested with.
+const TESTED_VERSIONS: [(&str, (u64, u64), u64); 7] = [
+    ("sonarr", (2, 0), 4),
+    ("radarr", (3, 0), 5),
+    ("tautulli", (2, 0), 2),
+    ("overseerr", (1, 0), 1),
+    ("jellyseerr", (1, 0), 2),
+    ("plex", (1, 20), 1),
+    ("jellyfin", (10, 8), 10),
+];
+
+// "v4.0.1.929", "1.40.2.8395-c67dce28e" or "10.9.11" give (4, 0), (1, 40) and (10, 9).
+fn major_minor(version: &str) -> Option<(u64, u64)> {
+    let mut parts = version.trim_start_matches('v').split(['.', '-']);
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts
+        .next()
+        .and_then(|minor| minor.parse().ok())
+        .unwrap_or(0);
+    Some((major, minor))
+}
+
+fn report(provider: &str, name: &str, version: &str) {
+    let Some((_, (min_major, min_minor), max_major)) = TESTED_VERSIONS
+        .iter()
+        .find(|(tested, _, _)| *tested == provider)
+    else {
+        return;
+    };
+    match major_minor(version) {
+        Some(current) if current >= (*min_major, *min_minor) && current.0 <= *max_major => {
+            info!("{provider} {name} runs version {version}");
+        }
+        Some(_) => warn!(
+            "{provider} {name} runs version {version}, homers is tested with {min_major}.{min_minor} to {max_major}.x, some metrics may be missing"
+        ),
+        None => debug!("{provider} {name} reported an unknown version format {version:?}"),
+    }
+}
+
+/// Logs the version of every provider instance, a warning when it was not
+/// tested. Sonarr v2 is switched to its legacy API on the way.
+pub async fn check_versions(tasks: &[Task]) {
+    let mut seen = HashSet::new();
+    let tasks: Vec<&Task> = tasks
+        .iter()
+        .filter(|task| {
+            let (provider, name, _) = task.provider();
+            seen.insert((provider, name.to_string()))
+        })
+        .collect();
+    let versions = join_all(tasks.iter().map(|task| task.version())).await;
+    for (task, version) in tasks.iter().zip(versions) {
+        let (provider, name, _) = task.provider();
+        match version {
+            Ok(version) => report(provider, name, &version),
+            Err(e) => warn!("Failed to get the version of {provider} {name}: {e}"),
+        }
+    }
+}
+
+/// Checks the versions at startup, then every six hours.
+pub fn spawn(tasks: TaskList) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            check_versions(&tasks.get()).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn major_minor_reads_provider_versions() {
+        assert_eq!(major_minor("v4.0.1.929"), Some((4, 0)));
+        assert_eq!(major_minor("1.40.2.8395-c67dce28e"), Some((1, 40)));
+        assert_eq!(major_minor("10.9.11"), Some((10, 9)));
+        assert_eq!(major_minor("2"), Some((2, 0)));
+        assert_eq!(major_minor("develop"), None);
+        assert_eq!(major_minor(""), None);
+    }
+}